@@ -1,6 +1,7 @@
 use actix_web::web;
-use crate::controllers::{message_controller, chat_controller};
+use crate::controllers::{message_controller, chat_controller, ticket_controller};
 use crate::websocket;
+use shared::middleware::require::RequireAccess;
 
 
 pub fn config_services(cfg: &mut web::ServiceConfig) {
@@ -8,8 +9,9 @@ pub fn config_services(cfg: &mut web::ServiceConfig) {
         web::scope("/api")
             .service(
                 web::scope("/chats")
+                    .wrap(RequireAccess("chats"))
+                    .route("", web::get().to(chat_controller::get_user_chats))
                     .route("", web::post().to(chat_controller::create_chat))
-                    .route("/user/{user_id}", web::get().to(chat_controller::get_user_chats))
                     .route("/{id}", web::get().to(chat_controller::get_chat_by_uid))
                     .route("/{id}/participants", web::get().to(chat_controller::get_chat_participants))
                     .route("/{id}/participants/{user_id}", web::post().to(chat_controller::add_participant))
@@ -17,13 +19,71 @@ pub fn config_services(cfg: &mut web::ServiceConfig) {
             )
             .service(
                 web::scope("/messages")
+                    .wrap(RequireAccess("chats"))
                     .route("", web::post().to(message_controller::create_message))
                     .route("/chat/{chat_uid}", web::get().to(message_controller::get_chat_messages))
             )
+            .service(
+                web::scope("/ws")
+                    .wrap(RequireAccess("chats"))
+                    .route("/tickets", web::post().to(ticket_controller::issue_ws_ticket))
+            )
     );
 
     cfg.service(    
-        web::resource("/ws/messages/chat_uid/{chat_uid}/user_uid/{user_uid}")
+        web::resource("/ws/messages/chat_uid/{chat_uid}")
             .route(web::get().to(websocket::handler::chat_ws))
     );
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App, HttpMessage};
+    use actix_web::dev::Service;
+    use actix_web::http::Method;
+    use shared::models::user_token::UserToken;
+    use std::sync::Arc;
+    use uuid::Uuid;
+    use crate::services::message_service::MessageService;
+    use crate::websocket::ticket::TicketStore;
+
+    // The guards reject before any handler runs, so no services need to be registered. The socket
+    // handler checks scopes itself, so it gets its extractors' data, though no database behind it.
+    async fn status_for_api_key(method: Method, path: &str, scopes: &[&str]) -> u16 {
+        let token = UserToken::for_api_key(Uuid::new_v4(), Uuid::new_v4(), scopes.iter().map(|s| s.to_string()).collect(), None);
+        let pool = sqlx::PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(TicketStore::new(std::time::Duration::from_secs(30))))
+                .app_data(web::Data::new(Arc::new(MessageService::new(pool))))
+                .configure(config_services)
+                .wrap_fn(move |req, srv| {
+                    req.extensions_mut().insert(token.clone());
+                    srv.call(req)
+                }),
+        )
+        .await;
+        let req = test::TestRequest::default().method(method).uri(path).to_request();
+        match test::try_call_service(&app, req).await {
+            Ok(resp) => resp.status().as_u16(),
+            Err(e) => e.as_response_error().status_code().as_u16(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn writes_need_the_write_scope() {
+        let chat_uid = Uuid::new_v4();
+        assert_eq!(status_for_api_key(Method::POST, "/api/chats", &["chats:read"]).await, 403);
+        assert_eq!(status_for_api_key(Method::POST, "/api/messages", &["chats:read"]).await, 403);
+        assert_eq!(status_for_api_key(Method::DELETE, &format!("/api/chats/{}/participants/{}", chat_uid, Uuid::new_v4()), &["chats:read"]).await, 403);
+        assert_eq!(status_for_api_key(Method::GET, &format!("/api/messages/chat/{}", chat_uid), &["users:read"]).await, 403);
+    }
+
+    #[actix_web::test]
+    async fn sockets_need_the_write_scope() {
+        let path = format!("/ws/messages/chat_uid/{}", Uuid::new_v4());
+        assert_eq!(status_for_api_key(Method::GET, &path, &["users:read"]).await, 403);
+        assert_eq!(status_for_api_key(Method::GET, &path, &["chats:read"]).await, 403);
+    }
+}
@@ -1,14 +1,26 @@
 use std::env;
 use std::net::SocketAddr;
-use std::sync::Arc;
 use std::fmt;
+use shared::middleware::rules::AuthRules;
+use shared::grpc::service_auth::validate_service_key;
+use shared::grpc::tls::GrpcTlsConfig;
+
+const DEFAULT_PUBLIC_ROUTES: &str = "OPTIONS /**";
+const DEFAULT_OPTIONAL_ROUTES: &str = "GET /ws/messages/**";
 
 #[derive(Clone)]
 pub struct Config {
     pub database_url: String,
-    pub jwt_secret: Arc<String>,
+    pub jwks_url: String,
+    pub jwks_refresh_interval: std::time::Duration,
+    pub jwks_min_refetch_interval: std::time::Duration,
     pub http_addr: SocketAddr,
     pub grpc_addr: String,
+    pub grpc_service_key: String,
+    pub grpc_tls: GrpcTlsConfig,
+    pub revocation_cache_ttl: std::time::Duration,
+    pub auth_rules: AuthRules,
+    pub ws_ticket_ttl: std::time::Duration,
     pub log_level: log::LevelFilter,
 }
 
@@ -16,18 +28,53 @@ impl Config {
     pub fn from_env() -> Result<Self, String> {
         let database_url = env::var("DATABASE_URL_CHAT_SERVICE")
             .map_err(|_| "DATABASE_URL_CHAT_SERVICE must be set")?;
-        let jwt_secret = env::var("JWT_SECRET")
-            .map_err(|_| "JWT_SECRET must be set")?;
+        let jwks_url = env::var("USER_SERVICE_JWKS_URL")
+            .map_err(|_| "USER_SERVICE_JWKS_URL must be set")?;
+        let jwks_refresh_interval = env::var("JWKS_REFRESH_INTERVAL_SECONDS")
+            .unwrap_or("300".into())
+            .parse::<u64>()
+            .map_err(|_| "JWKS_REFRESH_INTERVAL_SECONDS must be a number")?;
+        let jwks_min_refetch_interval = env::var("JWKS_MIN_REFETCH_SECONDS")
+            .unwrap_or("30".into())
+            .parse::<u64>()
+            .map_err(|_| "JWKS_MIN_REFETCH_SECONDS must be a number")?;
         let host = env::var("CHAT_SERVICE_HOST")
             .map_err(|_| "CHAT_SERVICE_HOST must be set")?;
         let port = env::var("CHAT_SERVICE_PORT")
             .map_err(|_| "CHAT_SERVICE_PORT must be set")?
             .parse::<u16>()
             .map_err(|_| "CHAT_SERVICE_PORT must be a valid port number")?;
+        let grpc_host = env::var("USER_SERVICE_GRPC_HOST")
+            .unwrap_or("[::1]".into());
         let grpc_port = env::var("USER_SERVICE_GRPC_PORT")
             .unwrap_or("50052".into())
             .parse::<u16>()
             .map_err(|_| "USER_SERVICE_GRPC_PORT must be a valid port number")?;
+        let grpc_service_key = env::var("GRPC_SERVICE_KEY")
+            .map_err(|_| "GRPC_SERVICE_KEY must be set")?;
+        validate_service_key(&grpc_service_key).map_err(|e| format!("Invalid GRPC_SERVICE_KEY: {}", e))?;
+        let grpc_tls = GrpcTlsConfig {
+            cert_path: env::var("GRPC_TLS_CERT_PATH").ok(),
+            key_path: env::var("GRPC_TLS_KEY_PATH").ok(),
+            ca_path: env::var("GRPC_TLS_CA_PATH").ok(),
+            domain: env::var("GRPC_TLS_DOMAIN").ok(),
+        };
+        if grpc_tls.is_enabled() {
+            grpc_tls.client_config().map_err(|e| format!("Invalid gRPC TLS config: {}", e))?;
+        }
+        let grpc_scheme = if grpc_tls.is_enabled() { "https" } else { "http" };
+        let revocation_cache_ttl = env::var("REVOCATION_CACHE_TTL_SECONDS")
+            .unwrap_or("30".into())
+            .parse::<u64>()
+            .map_err(|_| "REVOCATION_CACHE_TTL_SECONDS must be a number")?;
+        let auth_rules = AuthRules::from_config(
+            &env::var("AUTH_PUBLIC_ROUTES").unwrap_or(DEFAULT_PUBLIC_ROUTES.into()),
+            &env::var("AUTH_OPTIONAL_ROUTES").unwrap_or(DEFAULT_OPTIONAL_ROUTES.into()),
+        )?;
+        let ws_ticket_ttl = env::var("WS_TICKET_TTL_SECONDS")
+            .unwrap_or("30".into())
+            .parse::<u64>()
+            .map_err(|_| "WS_TICKET_TTL_SECONDS must be a number")?;
         let log_level = env::var("LOG_LEVEL")
             .unwrap_or("info".into())
             .parse::<log::LevelFilter>()
@@ -35,9 +82,16 @@ impl Config {
 
         Ok(Self {
             database_url,
-            jwt_secret: Arc::new(jwt_secret),
+            jwks_url,
+            jwks_refresh_interval: std::time::Duration::from_secs(jwks_refresh_interval),
+            jwks_min_refetch_interval: std::time::Duration::from_secs(jwks_min_refetch_interval),
             http_addr: format!("{}:{}", host, port).parse().map_err(|e| format!("Invalid HTTP address: {}", e))?,
-            grpc_addr: format!("http://[::1]:{}", grpc_port).parse().map_err(|e| format!("Invalid gRPC address: {}", e))?,
+            grpc_addr: format!("{}://{}:{}", grpc_scheme, grpc_host, grpc_port).parse().map_err(|e| format!("Invalid gRPC address: {}", e))?,
+            grpc_service_key,
+            grpc_tls,
+            revocation_cache_ttl: std::time::Duration::from_secs(revocation_cache_ttl),
+            auth_rules,
+            ws_ticket_ttl: std::time::Duration::from_secs(ws_ticket_ttl),
             log_level,
         })
     }
@@ -48,7 +102,14 @@ impl fmt::Debug for Config {
         f.debug_struct("Config")
             .field("database_url", &self.database_url)
             .field("http_addr", &self.http_addr)
+            .field("jwks_url", &self.jwks_url)
+            .field("jwks_refresh_interval", &self.jwks_refresh_interval)
+            .field("jwks_min_refetch_interval", &self.jwks_min_refetch_interval)
             .field("grpc_addr", &self.grpc_addr)
+            .field("grpc_tls", &self.grpc_tls)
+            .field("revocation_cache_ttl", &self.revocation_cache_ttl)
+            .field("auth_rules", &self.auth_rules)
+            .field("ws_ticket_ttl", &self.ws_ticket_ttl)
             .field("log_level", &self.log_level)
             .finish() 
     }
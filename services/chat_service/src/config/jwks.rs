@@ -0,0 +1,90 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use jsonwebtoken::jwk::JwkSet;
+use shared::middleware::auth::KeyRefresh;
+use shared::models::keys::KeySet;
+
+async fn fetch_jwks(url: &str) -> Result<JwkSet, String> {
+    reqwest::get(url)
+        .await
+        .map_err(|e| format!("Failed to fetch JWKS from {}: {}", url, e))?
+        .error_for_status()
+        .map_err(|e| format!("Failed to fetch JWKS from {}: {}", url, e))?
+        .json::<JwkSet>()
+        .await
+        .map_err(|e| format!("Invalid JWKS from {}: {}", url, e))
+}
+
+async fn refresh_key_set(key_set: &KeySet, url: &str) {
+    match fetch_jwks(url).await {
+        Ok(jwks) => match key_set.replace(jwks) {
+            Ok(()) => log::debug!("JWKS refreshed from {}", url),
+            Err(e) => log::error!("Failed to apply JWKS from {}: {}", url, e),
+        },
+        Err(e) => log::error!("{}", e),
+    }
+}
+
+pub async fn init_key_set(url: &str) -> Result<Arc<KeySet>, String> {
+    let jwks = fetch_jwks(url).await?;
+    let key_set = KeySet::new(jwks).map_err(|e| format!("Invalid JWKS from {}: {}", url, e))?;
+    Ok(Arc::new(key_set))
+}
+
+pub fn spawn_key_set_refresh(key_set: Arc<KeySet>, url: String, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            refresh_key_set(&key_set, &url).await;
+        }
+    });
+}
+
+// Re-fetches on an unknown kid at most once per `min_interval`, so tokens with made-up kids can't
+// be used to flood the user service with JWKS requests.
+pub struct JwksRefresh {
+    key_set: Arc<KeySet>,
+    url: String,
+    min_interval: Duration,
+    last_fetch: Mutex<Option<Instant>>,
+}
+
+impl JwksRefresh {
+    pub fn new(key_set: Arc<KeySet>, url: String, min_interval: Duration) -> Self {
+        Self { key_set, url, min_interval, last_fetch: Mutex::new(None) }
+    }
+
+    fn start_fetch(&self, now: Instant) -> bool {
+        let mut last_fetch = self.last_fetch.lock().unwrap();
+        if last_fetch.is_some_and(|last_fetch| now.duration_since(last_fetch) < self.min_interval) {
+            return false;
+        }
+        *last_fetch = Some(now);
+        true
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl KeyRefresh for JwksRefresh {
+    async fn refresh(&self) {
+        if self.start_fetch(Instant::now()) {
+            refresh_key_set(&self.key_set, &self.url).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fetches_at_most_once_per_interval() {
+        let refresh = JwksRefresh::new(Arc::new(KeySet::default()), String::new(), Duration::from_secs(30));
+        let now = Instant::now();
+        assert!(refresh.start_fetch(now));
+        assert!(!refresh.start_fetch(now + Duration::from_secs(29)));
+        assert!(refresh.start_fetch(now + Duration::from_secs(30)));
+    }
+}
@@ -1,3 +1,5 @@
 pub mod app;
 pub mod db;
+pub mod jwks;
+#[allow(clippy::module_inception)]
 pub mod config;
\ No newline at end of file
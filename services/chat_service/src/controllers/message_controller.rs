@@ -1,4 +1,5 @@
 use actix_web::{web, HttpResponse};
+use shared::middleware::auth_user::AuthUser;
 use crate::models::message::CreateMessageDTO;
 use crate::models::response::ResponseBody;
 use crate::services::message_service::MessageService;
@@ -8,17 +9,19 @@ use crate::errors::service_error::ServiceError;
 
 pub async fn get_chat_messages(
     service: web::Data<MessageService<PgMessageRepository, PgChatRepository>>,
+    auth_user: AuthUser,
     chat_uid: web::Path<String>
 ) -> Result<HttpResponse, ServiceError> {
-    let messages = service.get_all_messages_by_chat_uid(chat_uid.into_inner()).await?;
+    let messages = service.get_all_messages_by_chat_uid(auth_user.uid, chat_uid.into_inner()).await?;
     Ok(HttpResponse::Ok().json(ResponseBody::new("Messages successfully retrieved", Some(messages))))
 }
 
 pub async fn create_message(
     service: web::Data<MessageService<PgMessageRepository,PgChatRepository>>,
+    auth_user: AuthUser,
     message_dto: web::Json<CreateMessageDTO>
 ) -> Result<HttpResponse, ServiceError> {  
-    let message = service.create(message_dto.0).await?;
+    let message = service.create(auth_user.uid, message_dto.0).await?;
     Ok(HttpResponse::Created().json(ResponseBody::new("Message successfully created", Some(message))))
 }
 
@@ -1,2 +1,3 @@
 pub mod chat_controller;
-pub mod message_controller;
\ No newline at end of file
+pub mod message_controller;
+pub mod ticket_controller;
\ No newline at end of file
@@ -0,0 +1,16 @@
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use shared::middleware::auth_user::AuthUser;
+use crate::models::ticket::WsTicketResponse;
+use crate::models::response::ResponseBody;
+use crate::websocket::ticket::TicketStore;
+use crate::errors::service_error::ServiceError;
+
+pub async fn issue_ws_ticket(
+    tickets: web::Data<TicketStore>,
+    auth_user: AuthUser,
+) -> Result<HttpResponse, ServiceError> {
+    let ticket = tickets.issue(auth_user.uid);
+    let expires_at = Utc::now() + tickets.ttl();
+    Ok(HttpResponse::Created().json(ResponseBody::new("WebSocket ticket issued", Some(WsTicketResponse { ticket, expires_at }))))
+}
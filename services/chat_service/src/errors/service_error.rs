@@ -1,18 +1,30 @@
 use actix_web::{HttpResponse, ResponseError};
+use actix_web::http::{header, StatusCode};
 use derive_more::Display;
 use serde::Serialize;
+use shared::errors::repository_error::RepositoryError;
+use log::{error, warn};
 
 #[derive(Debug, Display, Serialize)]
 #[display(fmt = "{}", message)]
 pub struct ServiceError {
     pub message: String,
     pub status_code: u16,
+    #[serde(skip)]
+    pub retry_after: Option<u64>,
 }
 
 impl ResponseError for ServiceError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.status_code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
     fn error_response(&self) -> HttpResponse {
-        HttpResponse::build(self.status_code().into())
-            .json(self)
+        let mut response = HttpResponse::build(self.status_code());
+        if let Some(retry_after) = self.retry_after {
+            response.insert_header((header::RETRY_AFTER, retry_after.to_string()));
+        }
+        response.json(self)
     }
 }
 
@@ -21,6 +33,7 @@ impl ServiceError {
         Self {
             message: message.to_string(),
             status_code,
+            retry_after: None,
         }
     }
     
@@ -28,11 +41,68 @@ impl ServiceError {
         Self::new(message, 400)
     }
     
+    pub fn unauthorized(message: &str) -> Self {
+        Self::new(message, 401)
+    }
+    
+    pub fn forbidden(message: &str) -> Self {
+        Self::new(message, 403)
+    }
+    
     pub fn not_found(message: &str) -> Self {
         Self::new(message, 404)
     }
     
+    pub fn conflict(message: &str) -> Self {
+        Self::new(message, 409)
+    }
+    
     pub fn internal_error(message: &str) -> Self {
         Self::new(message, 500)
     }
-}
\ No newline at end of file
+    
+    pub fn unavailable(message: &str, retry_after_seconds: u64) -> Self {
+        Self {
+            retry_after: Some(retry_after_seconds),
+            ..Self::new(message, 503)
+        }
+    }
+}
+
+// Services override these messages where they know better, e.g. who is already in a chat.
+impl From<RepositoryError> for ServiceError {
+    fn from(e: RepositoryError) -> Self {
+        match e {
+            RepositoryError::NotFound(message) => Self::not_found(&message),
+            RepositoryError::Conflict { .. } => Self::conflict("Resource already exists"),
+            RepositoryError::ForeignKey { .. } => Self::conflict("A related resource does not exist or is still in use"),
+            RepositoryError::Transient(message) => {
+                warn!("Transient database error: {}", message);
+                Self::unavailable("The database is temporarily unavailable, try again", 1)
+            }
+            RepositoryError::Other(message) => {
+                error!("Database error: {}", message);
+                Self::internal_error("Database error")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repository_errors_map_to_statuses() {
+        let error = ServiceError::from(RepositoryError::not_found("Chat not found"));
+        assert_eq!((error.status_code, error.message.as_str()), (404, "Chat not found"));
+
+        let conflict = RepositoryError::Conflict { constraint: Some("chat_members_pkey".into()), field: None };
+        assert_eq!(ServiceError::from(conflict).status_code, 409);
+
+        let error = ServiceError::from(RepositoryError::Transient("connection reset".into()));
+        assert_eq!((error.status_code, error.retry_after), (503, Some(1)));
+        let response = error.error_response();
+        assert_eq!(response.headers().get(header::RETRY_AFTER).unwrap(), "1");
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use sha2::{Digest, Sha256};
+use shared::middleware::auth::ApiKeyVerifier;
+use shared::models::user_token::UserToken;
+use crate::grpc::client::UserGrpcClient;
+
+const MAX_CACHED_KEYS: usize = 10_000;
+
+// Like token revocation, a revoked key keeps working here for at most one cache TTL. Only keys
+// that verified are cached, under their hash, so guessing keys can't fill the cache and a memory
+// dump doesn't hold usable secrets.
+pub struct CachedApiKeyVerifier {
+    verifier: Arc<dyn ApiKeyVerifier>,
+    ttl: Duration,
+    cache: Mutex<KeyCache>,
+}
+
+#[derive(Default)]
+struct KeyCache {
+    tokens: HashMap<String, (UserToken, Instant)>,
+    // Insertion order, so the oldest key can be dropped when full without scanning.
+    order: VecDeque<String>,
+}
+
+impl CachedApiKeyVerifier {
+    pub fn new(verifier: Arc<dyn ApiKeyVerifier>, ttl: Duration) -> Self {
+        Self {
+            verifier,
+            ttl,
+            cache: Mutex::new(KeyCache::default()),
+        }
+    }
+
+    fn cached(&self, key_hash: &str) -> Option<UserToken> {
+        let cache = self.cache.lock().unwrap();
+        match cache.tokens.get(key_hash) {
+            Some((user_token, checked_at)) if checked_at.elapsed() < self.ttl => Some(user_token.clone()),
+            _ => None,
+        }
+    }
+
+    fn store(&self, key_hash: String, user_token: Option<UserToken>) {
+        let mut cache = self.cache.lock().unwrap();
+        let Some(user_token) = user_token else {
+            cache.tokens.remove(&key_hash);
+            return;
+        };
+        if !cache.tokens.contains_key(&key_hash) {
+            while cache.order.len() >= MAX_CACHED_KEYS {
+                if let Some(oldest) = cache.order.pop_front() {
+                    cache.tokens.remove(&oldest);
+                }
+            }
+            cache.order.push_back(key_hash.clone());
+        }
+        cache.tokens.insert(key_hash, (user_token, Instant::now()));
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl ApiKeyVerifier for CachedApiKeyVerifier {
+    async fn verify(&self, api_key: &str) -> Result<Option<UserToken>, actix_web::Error> {
+        let key_hash = hex::encode(Sha256::digest(api_key.as_bytes()));
+        if let Some(user_token) = self.cached(&key_hash) {
+            return Ok(Some(user_token));
+        }
+
+        let user_token = self.verifier.verify(api_key).await?;
+        self.store(key_hash, user_token.clone());
+        Ok(user_token)
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl ApiKeyVerifier for UserGrpcClient {
+    async fn verify(&self, api_key: &str) -> Result<Option<UserToken>, actix_web::Error> {
+        Ok(self.verify_api_key(api_key).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use uuid::Uuid;
+
+    // Accepts only "tb_valid" and counts how often it is asked.
+    #[derive(Default)]
+    struct CountingVerifier {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait::async_trait(?Send)]
+    impl ApiKeyVerifier for CountingVerifier {
+        async fn verify(&self, api_key: &str) -> Result<Option<UserToken>, actix_web::Error> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok((api_key == "tb_valid").then(|| UserToken::for_api_key(Uuid::nil(), Uuid::nil(), vec![], None)))
+        }
+    }
+
+    #[tokio::test]
+    async fn caches_valid_keys_by_hash_and_never_misses() {
+        let counting = Arc::new(CountingVerifier::default());
+        let verifier = CachedApiKeyVerifier::new(counting.clone(), Duration::from_secs(60));
+
+        for _ in 0..3 {
+            assert!(verifier.verify("tb_valid").await.unwrap().is_some());
+            assert!(verifier.verify("tb_unknown").await.unwrap().is_none());
+        }
+        assert_eq!(counting.calls.load(Ordering::SeqCst), 4);
+
+        let cache = verifier.cache.lock().unwrap();
+        assert_eq!(cache.tokens.len(), 1);
+        assert!(cache.tokens.keys().all(|key| key.len() == 64 && !key.contains("tb_")));
+    }
+
+    #[tokio::test]
+    async fn rechecks_keys_after_the_ttl() {
+        let counting = Arc::new(CountingVerifier::default());
+        let verifier = CachedApiKeyVerifier::new(counting.clone(), Duration::ZERO);
+
+        verifier.verify("tb_valid").await.unwrap();
+        verifier.verify("tb_valid").await.unwrap();
+        assert_eq!(counting.calls.load(Ordering::SeqCst), 2);
+        assert_eq!(verifier.cache.lock().unwrap().order.len(), 1);
+    }
+}
@@ -1,5 +1,9 @@
+use tonic::service::interceptor::InterceptedService;
 use tonic::transport::{Channel, Uri};
-use shared::user_service_grpc::{UserResponse, UserRequest};
+use shared::grpc::service_auth::AttachServiceKey;
+use shared::grpc::tls::GrpcTlsConfig;
+use shared::user_service_grpc::{UserResponse, UserRequest, TokenRevocationRequest, ApiKeyRequest};
+use shared::models::user_token::UserToken;
 use shared::user_service_grpc::user_service_grpc_client::UserServiceGrpcClient;
 use crate::errors::service_error::ServiceError;
 use std::sync::Arc;
@@ -9,30 +13,43 @@ use uuid::Uuid;
 pub struct GrpcClientConfig {
     url: String,
     timeout: std::time::Duration,
-
+    service_key: String,
+    tls: GrpcTlsConfig,
 }
 
 #[derive(Clone)]
 pub struct UserGrpcClient {
-    inner: UserServiceGrpcClient<Channel>,
+    inner: UserServiceGrpcClient<InterceptedService<Channel, AttachServiceKey>>,
 }
 
 impl UserGrpcClient {
     pub async fn new(config: GrpcClientConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let service_key = AttachServiceKey::new(&config.service_key)?;
         let uri = Uri::try_from(config.url.clone())
             .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
         
-        let channel = Channel::builder(uri)
-            .timeout(config.timeout)
+        let mut endpoint = Channel::builder(uri).timeout(config.timeout);
+        if config.tls.is_enabled() {
+            endpoint = endpoint.tls_config(config.tls.client_config()?)?;
+        }
+
+        let channel = endpoint
             .connect()
             .await
             .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
 
-        let client = UserServiceGrpcClient::new(channel);
+        let client = UserServiceGrpcClient::with_interceptor(channel, service_key);
 
         Ok(Self { inner: client })
     }
 
+    #[cfg(test)]
+    pub fn disconnected() -> Self {
+        let channel = Channel::from_static("http://[::1]:1").connect_lazy();
+        let service_key = AttachServiceKey::new("0123456789abcdef0123456789abcdef").unwrap();
+        Self { inner: UserServiceGrpcClient::with_interceptor(channel, service_key) }
+    }
+
     pub async fn get_user_by_uid(&self, user_uid: Uuid) -> Result<UserResponse, ServiceError> {
         let request = tonic::Request::new(UserRequest {
             uid: user_uid.to_string(),
@@ -52,10 +69,63 @@ impl UserGrpcClient {
             })
             .map(|resp| resp.into_inner())
     }
+
+    pub async fn is_token_revoked(&self, user_token: &UserToken) -> Result<bool, ServiceError> {
+        let request = tonic::Request::new(TokenRevocationRequest {
+            jti: user_token.jti.clone(),
+            sub: user_token.sub.clone(),
+            iat: user_token.iat,
+            sid: user_token.sid.clone().unwrap_or_default(),
+        });
+
+        self.inner
+            .clone()
+            .is_token_revoked(request)
+            .await
+            .map_err(|e| {
+                log::error!("Failed to check revocation of token {}: {:?}", user_token.jti, e);
+                match e.code() {
+                    tonic::Code::Unavailable => ServiceError::new("gRPC server unavailable", 503),
+                    _ => ServiceError::internal_error(&format!("gRPC error: {}", e)),
+                }
+            })
+            .map(|resp| resp.into_inner().revoked)
+    }
+
+    pub async fn verify_api_key(&self, api_key: &str) -> Result<Option<UserToken>, ServiceError> {
+        let request = tonic::Request::new(ApiKeyRequest { key: api_key.to_string() });
+
+        let response = self.inner
+            .clone()
+            .verify_api_key(request)
+            .await
+            .map_err(|e| {
+                log::error!("Failed to verify API key: {:?}", e);
+                match e.code() {
+                    tonic::Code::Unavailable => ServiceError::new("gRPC server unavailable", 503),
+                    _ => ServiceError::internal_error(&format!("gRPC error: {}", e)),
+                }
+            })?
+            .into_inner();
+        if !response.valid {
+            return Ok(None);
+        }
+
+        let (Ok(user_uid), Ok(key_uid)) = (Uuid::parse_str(&response.user_uid), Uuid::parse_str(&response.uid)) else {
+            return Err(ServiceError::internal_error("gRPC returned an invalid API key identity"));
+        };
+        let expires_at = (response.expires_at != 0).then_some(response.expires_at);
+        Ok(Some(UserToken::for_api_key(user_uid, key_uid, response.scopes, expires_at)))
+    }
 }
 
-pub async fn init_grpc_client(url: String, timeout: std::time::Duration ) -> Result<Arc<UserGrpcClient>, ServiceError> {
-    let config = GrpcClientConfig { url, timeout };
+pub async fn init_grpc_client(
+    url: String,
+    timeout: std::time::Duration,
+    service_key: String,
+    tls: GrpcTlsConfig,
+) -> Result<Arc<UserGrpcClient>, ServiceError> {
+    let config = GrpcClientConfig { url, timeout, service_key, tls };
     let client = UserGrpcClient::new(config)
         .await
         .map_err(|e| ServiceError::internal_error(&format!("Failed to init gRPC client: {}", e)))?;
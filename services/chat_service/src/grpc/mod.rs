@@ -1 +1,3 @@
-pub mod client;
\ No newline at end of file
+pub mod client;
+pub mod revocation;
+pub mod api_keys;
\ No newline at end of file
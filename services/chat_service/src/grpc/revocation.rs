@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use shared::middleware::auth::TokenRevocation;
+use shared::models::user_token::UserToken;
+use crate::grpc::client::UserGrpcClient;
+
+const MAX_CACHED_TOKENS: usize = 10_000;
+
+pub struct CachedTokenRevocation {
+    user_client: Arc<UserGrpcClient>,
+    ttl: Duration,
+    cache: Mutex<HashMap<String, (bool, Instant)>>,
+}
+
+impl CachedTokenRevocation {
+    pub fn new(user_client: Arc<UserGrpcClient>, ttl: Duration) -> Self {
+        Self {
+            user_client,
+            ttl,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn cached(&self, jti: &str) -> Option<bool> {
+        let cache = self.cache.lock().unwrap();
+        match cache.get(jti) {
+            Some((true, _)) => Some(true),
+            Some((false, checked_at)) if checked_at.elapsed() < self.ttl => Some(false),
+            _ => None,
+        }
+    }
+
+    fn store(&self, jti: &str, revoked: bool) {
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= MAX_CACHED_TOKENS {
+            let ttl = self.ttl;
+            cache.retain(|_, (_, checked_at)| checked_at.elapsed() < ttl);
+        }
+        cache.insert(jti.to_string(), (revoked, Instant::now()));
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl TokenRevocation for CachedTokenRevocation {
+    async fn is_revoked(&self, token: &UserToken) -> Result<bool, actix_web::Error> {
+        if let Some(revoked) = self.cached(&token.jti) {
+            return Ok(revoked);
+        }
+
+        let revoked = self.user_client.is_token_revoked(token).await?;
+        self.store(&token.jti, revoked);
+        Ok(revoked)
+    }
+}
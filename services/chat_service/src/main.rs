@@ -12,15 +12,18 @@ use actix_web::middleware::Logger;
 use actix_cors::Cors;
 use config::app::config_services;
 use config::db::init_db_pool;
+use config::jwks::{init_key_set, spawn_key_set_refresh, JwksRefresh};
 use services::message_service::MessageService;
 use std::sync::Arc;
-use env_logger;
 use shared::middleware::auth::Authentication;
 use dotenvy::dotenv;
 use config::config::Config;
 use log::{info, error};
 use services::chat_service::ChatService;
 use grpc::client::init_grpc_client;
+use grpc::revocation::CachedTokenRevocation;
+use grpc::api_keys::CachedApiKeyVerifier;
+use websocket::ticket::TicketStore;
 
 
 #[actix_web::main]
@@ -38,17 +41,32 @@ async fn main() -> std::io::Result<()> {
 
     let pool = init_db_pool(&config.database_url).await.map_err(|e| {
         error!("Failed to create DB pool: {}", e);
-        std::io::Error::new(std::io::ErrorKind::Other, e)
+        std::io::Error::other(e)
     })?;
 
-    let grpc_client = match init_grpc_client(config.grpc_addr.clone(), std::time::Duration::from_secs(5)).await {
+    let jwt_keys = init_key_set(&config.jwks_url).await.map_err(|e| {
+        error!("Failed to load JWKS: {}", e);
+        std::io::Error::other(e)
+    })?;
+    spawn_key_set_refresh(jwt_keys.clone(), config.jwks_url.clone(), config.jwks_refresh_interval);
+    let key_refresh = Arc::new(JwksRefresh::new(jwt_keys.clone(), config.jwks_url.clone(), config.jwks_min_refetch_interval));
+
+    let grpc_client = match init_grpc_client(
+        config.grpc_addr.clone(),
+        std::time::Duration::from_secs(5),
+        config.grpc_service_key.clone(),
+        config.grpc_tls.clone(),
+    ).await {
         Ok(client) => client,
         Err(e) => {
             log::error!("Failed to initialize gRPC client: {}", e);
-            return Err(std::io::Error::new(std::io::ErrorKind::Other, e.to_string()));
+            return Err(std::io::Error::other(e.to_string()));
         }
     };
 
+    let token_revocation = Arc::new(CachedTokenRevocation::new(grpc_client.clone(), config.revocation_cache_ttl));
+    let api_keys = Arc::new(CachedApiKeyVerifier::new(grpc_client.clone(), config.revocation_cache_ttl));
+    let ws_tickets = web::Data::new(TicketStore::new(config.ws_ticket_ttl));
     let message_service = Arc::new(MessageService::new(pool.clone()));
     let chat_service = Arc::new(ChatService::new(pool.clone(), grpc_client.clone()));
 
@@ -62,11 +80,16 @@ async fn main() -> std::io::Result<()> {
         App::new()
             .wrap(cors)
             .wrap(Logger::default())
-            .wrap(Authentication::new(config.jwt_secret.clone(), None))
+            .wrap(
+                Authentication::new(jwt_keys.clone(), token_revocation.clone(), config.auth_rules.clone())
+                    .with_api_keys(api_keys.clone())
+                    .with_key_refresh(key_refresh.clone())
+            )
             .configure(config_services)
             .app_data(web::Data::new(message_service.clone()))
             .app_data(web::Data::from(message_service.clone()))
             .app_data(web::Data::from(chat_service.clone()))
+            .app_data(ws_tickets.clone())
     })
     .bind(config.http_addr)?
     .workers(4)
@@ -17,6 +17,7 @@ pub struct CreateChatDTO {
     pub name: Option<String>,  
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct ChatParticipant {
     pub chat_uid: Uuid,
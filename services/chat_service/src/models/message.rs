@@ -15,6 +15,5 @@ pub struct Message {
 #[derive(Debug, Deserialize)]
 pub struct CreateMessageDTO {
     pub chat_uid: Uuid,
-    pub user_uid: Uuid,
     pub content: String,
 }
\ No newline at end of file
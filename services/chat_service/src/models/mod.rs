@@ -1,3 +1,4 @@
 pub mod message;
 pub mod chat;
-pub mod response;
\ No newline at end of file
+pub mod response;
+pub mod ticket;
\ No newline at end of file
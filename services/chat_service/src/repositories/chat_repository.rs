@@ -1,16 +1,17 @@
 use sqlx::PgPool;
 use uuid::Uuid;
 use crate::models::chat::{Chat, CreateChatDTO};
-use crate::errors::service_error::ServiceError;
+use shared::errors::repository_error::RepositoryError;
 
 #[async_trait::async_trait]
 pub trait ChatRepository {
-    async fn get_user_chats(&self, user_uid: &Uuid) -> Result<Vec<Chat>, ServiceError>;
-    async fn get_by_uid(&self, uid: &Uuid) -> Result<Chat, ServiceError>;
-    async fn create(&self, chat_dto: &CreateChatDTO) -> Result<Chat, ServiceError>;
-    async fn add_participant(&self, chat_uid: &Uuid, user_uid: &Uuid) -> Result<(), ServiceError>;
-    async fn remove_participant(&self, chat_uid: &Uuid, user_uid: &Uuid) -> Result<(), ServiceError>;
-    async fn get_chat_participants(&self, chat_uid: &Uuid) -> Result<Vec<Uuid>, ServiceError>;
+    async fn get_user_chats(&self, user_uid: &Uuid) -> Result<Vec<Chat>, RepositoryError>;
+    async fn get_by_uid(&self, uid: &Uuid) -> Result<Chat, RepositoryError>;
+    async fn create(&self, chat_dto: &CreateChatDTO) -> Result<Chat, RepositoryError>;
+    async fn add_participant(&self, chat_uid: &Uuid, user_uid: &Uuid) -> Result<(), RepositoryError>;
+    async fn remove_participant(&self, chat_uid: &Uuid, user_uid: &Uuid) -> Result<(), RepositoryError>;
+    async fn get_chat_participants(&self, chat_uid: &Uuid) -> Result<Vec<Uuid>, RepositoryError>;
+    async fn is_participant(&self, chat_uid: &Uuid, user_uid: &Uuid) -> Result<bool, RepositoryError>;
 }
 
 pub struct PgChatRepository {
@@ -25,7 +26,7 @@ impl PgChatRepository {
 
 #[async_trait::async_trait]
 impl ChatRepository for PgChatRepository {
-    async fn get_user_chats(&self, user_uid: &Uuid) -> Result<Vec<Chat>, ServiceError> {
+    async fn get_user_chats(&self, user_uid: &Uuid) -> Result<Vec<Chat>, RepositoryError> {
         sqlx::query_as::<_, Chat>("
             SELECT c.uid, c.name, c.created_at, c.updated_at
             FROM chats c
@@ -35,23 +36,23 @@ impl ChatRepository for PgChatRepository {
         .bind(user_uid)
         .fetch_all(&self.pool)
         .await
-        .map_err(|e| ServiceError::internal_error(&format!("Database error: {}", e)))
+        .map_err(RepositoryError::from)
     }
     
-    async fn get_by_uid(&self, uid: &Uuid) -> Result<Chat, ServiceError> {
+    async fn get_by_uid(&self, uid: &Uuid) -> Result<Chat, RepositoryError> {
         sqlx::query_as::<_, Chat>("
             SELECT * FROM chats WHERE uid = $1"
         )
         .bind(uid)
         .fetch_optional(&self.pool)
         .await
-        .map_err(|e| ServiceError::internal_error(&format!("Database error: {}", e)))?
-        .ok_or_else(|| ServiceError::not_found(&format!("Chat with uid {} not found", uid)))
+        .map_err(RepositoryError::from)?
+        .ok_or_else(|| RepositoryError::NotFound(format!("Chat with uid {} not found", uid)))
     }
     
-    async fn create(&self, chat_dto: &CreateChatDTO) -> Result<Chat, ServiceError> {
+    async fn create(&self, chat_dto: &CreateChatDTO) -> Result<Chat, RepositoryError> {
         let mut tx = self.pool.begin().await
-            .map_err(|e| ServiceError::internal_error(&format!("Transaction error: {}", e)))?;
+            .map_err(RepositoryError::from)?;
         
         let chat = sqlx::query_as::<_, Chat>("
             INSERT INTO chats (name)
@@ -61,7 +62,7 @@ impl ChatRepository for PgChatRepository {
         .bind(&chat_dto.name)
         .fetch_one(&mut *tx)
         .await
-        .map_err(|e| ServiceError::internal_error(&format!("Error creating a chat room: {}", e)))?;
+        .map_err(RepositoryError::from)?;
     
         for user_uid in &chat_dto.participants {
             sqlx::query(
@@ -72,44 +73,18 @@ impl ChatRepository for PgChatRepository {
             .bind(user_uid)
             .execute(&mut *tx)
             .await
-            .map_err(|e| ServiceError::internal_error(&format!("Error adding participant {}: {}", user_uid, e)))?;
+            .map_err(RepositoryError::from)?;
         }
     
         tx.commit().await
-            .map_err(|e| ServiceError::internal_error(&format!("Transaction commit error: {}", e)))?;
+            .map_err(RepositoryError::from)?;
     
         Ok(chat)
     }
 
-    async fn add_participant(&self, chat_uid: &Uuid, user_uid: &Uuid) -> Result<(), ServiceError> {
-        let chat_exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM chats WHERE uid = $1) as exists"
-        )
-        .bind(chat_uid)
-        .fetch_one(&self.pool)
-        .await
-        .map_err(|e| ServiceError::internal_error(&format!("Database error: {}", e)))?;
-
-        if !chat_exists {
-            return Err(ServiceError::not_found(&format!("Chat with uid {} not found", chat_uid)));
-        }
-        
-        let already_participant = sqlx::query_scalar::<_, bool>("
-            SELECT EXISTS(
-                SELECT 1 FROM chat_participants 
-                WHERE chat_uid = $1 AND user_uid = $2
-            ) as exists
-            "
-        )
-        .bind(chat_uid)
-        .bind(user_uid)   
-        .fetch_one(&self.pool)
-        .await
-        .map_err(|e| ServiceError::internal_error(&format!("Database error: {}", e)))?;
-
-        if already_participant {
-            return Err(ServiceError::bad_request("The user is already a member of the chat room"));
-        }
-
+    // The primary key rejects duplicate members and the foreign key unknown chats; the service
+    // turns those into messages.
+    async fn add_participant(&self, chat_uid: &Uuid, user_uid: &Uuid) -> Result<(), RepositoryError> {
         sqlx::query("
             INSERT INTO chat_participants (chat_uid, user_uid)
             VALUES ($1, $2)"
@@ -118,12 +93,12 @@ impl ChatRepository for PgChatRepository {
         .bind(user_uid)
         .execute(&self.pool)
         .await
-        .map_err(|e| ServiceError::internal_error(&format!("Error adding a participant: {}", e)))?;
+        .map_err(RepositoryError::from)?;
 
         Ok(())
     }
 
-    async fn remove_participant(&self, chat_uid: &Uuid, user_uid: &Uuid) -> Result<(), ServiceError> {
+    async fn remove_participant(&self, chat_uid: &Uuid, user_uid: &Uuid) -> Result<(), RepositoryError> {
         let result = sqlx::query("
             DELETE FROM chat_participants
             WHERE chat_uid = $1 AND user_uid = $2"
@@ -132,22 +107,37 @@ impl ChatRepository for PgChatRepository {
         .bind(user_uid)
         .execute(&self.pool)
         .await
-        .map_err(|e| ServiceError::internal_error(&format!("Participant deletion error {}", e)))?;
+        .map_err(RepositoryError::from)?;
 
         if result.rows_affected() == 0 {
-            return Err(ServiceError::not_found("Participant not found in chat"));
+            return Err(RepositoryError::not_found("Participant not found in chat"));
         }
 
         Ok(())
     }
 
-    async fn get_chat_participants(&self, chat_uid: &Uuid) -> Result<Vec<Uuid>, ServiceError> {
+    async fn get_chat_participants(&self, chat_uid: &Uuid) -> Result<Vec<Uuid>, RepositoryError> {
         sqlx::query_scalar::<_, Uuid>(
             "SELECT user_uid FROM chat_participants WHERE chat_uid = $1"
         )
         .bind(chat_uid)
         .fetch_all(&self.pool)
         .await
-        .map_err(|e| ServiceError::internal_error(&format!("Error fetching participants: {}", e)))
+        .map_err(RepositoryError::from)
+    }
+
+    async fn is_participant(&self, chat_uid: &Uuid, user_uid: &Uuid) -> Result<bool, RepositoryError> {
+        sqlx::query_scalar::<_, bool>("
+            SELECT EXISTS(
+                SELECT 1 FROM chat_participants
+                WHERE chat_uid = $1 AND user_uid = $2
+            ) as exists
+            "
+        )
+        .bind(chat_uid)
+        .bind(user_uid)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(RepositoryError::from)
     }
 }
\ No newline at end of file
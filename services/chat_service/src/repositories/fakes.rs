@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use chrono::Utc;
+use uuid::Uuid;
+use crate::models::chat::{Chat, CreateChatDTO};
+use crate::models::message::{CreateMessageDTO, Message};
+use crate::repositories::chat_repository::ChatRepository;
+use crate::repositories::message_repository::MessageRepository;
+use shared::errors::repository_error::RepositoryError;
+
+#[derive(Default)]
+pub struct FakeChatRepository {
+    chats: Mutex<HashMap<Uuid, (Chat, Vec<Uuid>)>>,
+}
+
+impl FakeChatRepository {
+    pub fn with_chat(chat_uid: Uuid, participants: &[Uuid]) -> Self {
+        let repository = Self::default();
+        repository.chats.lock().unwrap().insert(chat_uid, (new_chat(chat_uid, None), participants.to_vec()));
+        repository
+    }
+}
+
+#[async_trait::async_trait]
+impl ChatRepository for FakeChatRepository {
+    async fn get_user_chats(&self, user_uid: &Uuid) -> Result<Vec<Chat>, RepositoryError> {
+        Ok(self.chats.lock().unwrap()
+            .values()
+            .filter(|(_, participants)| participants.contains(user_uid))
+            .map(|(chat, _)| chat.clone())
+            .collect())
+    }
+
+    async fn get_by_uid(&self, uid: &Uuid) -> Result<Chat, RepositoryError> {
+        self.chats.lock().unwrap()
+            .get(uid)
+            .map(|(chat, _)| chat.clone())
+            .ok_or_else(|| RepositoryError::NotFound(format!("Chat with uid {} not found", uid)))
+    }
+
+    async fn create(&self, chat_dto: &CreateChatDTO) -> Result<Chat, RepositoryError> {
+        let chat = new_chat(Uuid::new_v4(), chat_dto.name.clone());
+        self.chats.lock().unwrap().insert(chat.uid, (chat.clone(), chat_dto.participants.clone()));
+        Ok(chat)
+    }
+
+    async fn add_participant(&self, chat_uid: &Uuid, user_uid: &Uuid) -> Result<(), RepositoryError> {
+        let mut chats = self.chats.lock().unwrap();
+        let (_, participants) = chats.get_mut(chat_uid)
+            .ok_or_else(|| RepositoryError::NotFound(format!("Chat with uid {} not found", chat_uid)))?;
+        if participants.contains(user_uid) {
+            return Err(RepositoryError::Conflict { constraint: Some("chat_participants_pkey".into()), field: None });
+        }
+        participants.push(*user_uid);
+        Ok(())
+    }
+
+    async fn remove_participant(&self, chat_uid: &Uuid, user_uid: &Uuid) -> Result<(), RepositoryError> {
+        let mut chats = self.chats.lock().unwrap();
+        let (_, participants) = chats.get_mut(chat_uid)
+            .ok_or_else(|| RepositoryError::NotFound(format!("Chat with uid {} not found", chat_uid)))?;
+        participants.retain(|participant| participant != user_uid);
+        Ok(())
+    }
+
+    async fn get_chat_participants(&self, chat_uid: &Uuid) -> Result<Vec<Uuid>, RepositoryError> {
+        Ok(self.chats.lock().unwrap()
+            .get(chat_uid)
+            .map(|(_, participants)| participants.clone())
+            .unwrap_or_default())
+    }
+
+    async fn is_participant(&self, chat_uid: &Uuid, user_uid: &Uuid) -> Result<bool, RepositoryError> {
+        Ok(self.chats.lock().unwrap()
+            .get(chat_uid)
+            .is_some_and(|(_, participants)| participants.contains(user_uid)))
+    }
+}
+
+#[derive(Default)]
+pub struct FakeMessageRepository {
+    pub messages: Mutex<Vec<Message>>,
+}
+
+#[async_trait::async_trait]
+impl MessageRepository for FakeMessageRepository {
+    async fn create(&self, user_uid: &Uuid, create_message_dto: &CreateMessageDTO) -> Result<Message, RepositoryError> {
+        let message = Message {
+            uid: Uuid::new_v4(),
+            chat_uid: create_message_dto.chat_uid,
+            user_uid: *user_uid,
+            content: create_message_dto.content.clone(),
+            created_at: Utc::now(),
+        };
+        self.messages.lock().unwrap().push(message.clone());
+        Ok(message)
+    }
+
+    async fn get_all_by_chat_uid(&self, chat_uid: &Uuid) -> Result<Vec<Message>, RepositoryError> {
+        Ok(self.messages.lock().unwrap()
+            .iter()
+            .filter(|message| &message.chat_uid == chat_uid)
+            .cloned()
+            .collect())
+    }
+}
+
+fn new_chat(uid: Uuid, name: Option<String>) -> Chat {
+    Chat {
+        uid,
+        name,
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+    }
+}
@@ -1,4 +1,4 @@
-use crate::errors::service_error::ServiceError;
+use shared::errors::repository_error::RepositoryError;
 use crate::models::message::{CreateMessageDTO, Message};
 use async_trait::async_trait;
 use sqlx::PgPool;
@@ -6,8 +6,8 @@ use uuid::Uuid;
 
 #[async_trait]
 pub trait MessageRepository {
-    async fn create(&self, create_message_dto: &CreateMessageDTO) -> Result<Message, ServiceError>;
-    async fn get_all_by_chat_uid(&self, chat_uid: &Uuid) -> Result<Vec<Message>, ServiceError>;
+    async fn create(&self, user_uid: &Uuid, create_message_dto: &CreateMessageDTO) -> Result<Message, RepositoryError>;
+    async fn get_all_by_chat_uid(&self, chat_uid: &Uuid) -> Result<Vec<Message>, RepositoryError>;
 }
 
 pub struct PgMessageRepository {
@@ -22,7 +22,7 @@ impl PgMessageRepository {
 
 #[async_trait]
 impl MessageRepository for PgMessageRepository {
-    async fn get_all_by_chat_uid(&self, chat_uid: &Uuid) -> Result<Vec<Message>, ServiceError> {
+    async fn get_all_by_chat_uid(&self, chat_uid: &Uuid) -> Result<Vec<Message>, RepositoryError> {
         sqlx::query_as::<_, Message>(
             "SELECT uid, chat_uid, user_uid, content, created_at 
              FROM messages 
@@ -32,21 +32,21 @@ impl MessageRepository for PgMessageRepository {
         .bind(chat_uid)
         .fetch_all(&self.pool)
         .await
-        .map_err(|e| ServiceError::internal_error(&format!("Database error: {}", e)))
+        .map_err(RepositoryError::from)
     }
-    async fn create(&self, create_message_dto: &CreateMessageDTO) -> Result<Message, ServiceError> {
+    async fn create(&self, user_uid: &Uuid, create_message_dto: &CreateMessageDTO) -> Result<Message, RepositoryError> {
         sqlx::query_as::<_, Message>(
             "INSERT INTO messages (chat_uid, user_uid, content)
              VALUES ($1, $2, $3)
              RETURNING uid, chat_uid, user_uid, content, created_at
             ",
         )
-        .bind(&create_message_dto.chat_uid)
-        .bind(&create_message_dto.user_uid)
+        .bind(create_message_dto.chat_uid)
+        .bind(user_uid)
         .bind(&create_message_dto.content)
         .fetch_one(&self.pool)
         .await
-        .map_err(|e| ServiceError::internal_error(&format!("Database error: {}", e)))
+        .map_err(RepositoryError::from)
 
     }
 }
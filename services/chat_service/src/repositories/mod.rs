@@ -1,2 +1,4 @@
 pub mod chat_repository;
-pub mod message_repository;  
\ No newline at end of file
+pub mod message_repository;
+#[cfg(test)]
+pub mod fakes;
\ No newline at end of file
@@ -0,0 +1,18 @@
+use uuid::Uuid;
+use crate::repositories::chat_repository::ChatRepository;
+use crate::errors::service_error::ServiceError;
+
+// A chat that doesn't exist has no participants, so outsiders get the same 403 either way and
+// can't tell which chat uids exist.
+pub async fn ensure_participant<C: ChatRepository>(
+    chat_repository: &C,
+    chat_uid: &Uuid,
+    user_uid: &Uuid,
+) -> Result<(), ServiceError> {
+    if !chat_repository.is_participant(chat_uid, user_uid).await? {
+        log::warn!("User {} denied access to chat {}", user_uid, chat_uid);
+        return Err(ServiceError::forbidden("You are not a participant of this chat"));
+    }
+
+    Ok(())
+}
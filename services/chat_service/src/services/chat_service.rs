@@ -2,7 +2,9 @@ use std::sync::Arc;
 use crate::grpc::client::UserGrpcClient;
 use crate::models::chat::{Chat, CreateChatDTO};
 use crate::repositories::chat_repository::{ChatRepository, PgChatRepository};
+use crate::services::authorization::ensure_participant;
 use crate::errors::service_error::ServiceError;
+use shared::errors::repository_error::RepositoryError;
 use sqlx::PgPool;
 use uuid::Uuid;
 use futures::future::join_all;
@@ -24,16 +26,19 @@ impl ChatService<PgChatRepository> {
 
 impl<T: ChatRepository> ChatService<T> {
 
-    pub async fn get_user_chats(&self, user_uid: String) -> Result<Vec<Chat>, ServiceError> {
-        let user_uid = parse_uuid(&user_uid)?;
-        self.repository.get_user_chats(&user_uid).await
+    pub async fn get_user_chats(&self, user_uid: Uuid) -> Result<Vec<Chat>, ServiceError> {
+        Ok(self.repository.get_user_chats(&user_uid).await?)
     }
-    pub async fn get_chat_by_uid(&self, chat_uid: String) -> Result<Chat, ServiceError> {
+    pub async fn get_chat_by_uid(&self, caller_uid: Uuid, chat_uid: String) -> Result<Chat, ServiceError> {
         let chat_uid = parse_uuid(&chat_uid)?;
-        self.repository.get_by_uid(&chat_uid).await
+        ensure_participant(&self.repository, &chat_uid, &caller_uid).await?;
+        Ok(self.repository.get_by_uid(&chat_uid).await?)
     }
 
-    pub async fn create(&self, chat_dto: CreateChatDTO) -> Result<Chat, ServiceError> {
+    pub async fn create(&self, caller_uid: Uuid, mut chat_dto: CreateChatDTO) -> Result<Chat, ServiceError> {
+        if !chat_dto.participants.contains(&caller_uid) {
+            chat_dto.participants.push(caller_uid);
+        }
 
         if chat_dto.participants.is_empty() {
             return Err(ServiceError::bad_request("Chat must have at least one participant"));
@@ -59,29 +64,36 @@ impl<T: ChatRepository> ChatService<T> {
             return Err(ServiceError::not_found(&errors.join("; ")));
         }
 
-        self.repository.create(&chat_dto).await
+        Ok(self.repository.create(&chat_dto).await?)
     }
 
-    pub async fn add_participant(&self, chat_uid: String, user_uid: String) -> Result<(), ServiceError> {
+    pub async fn add_participant(&self, caller_uid: Uuid, chat_uid: String, user_uid: String) -> Result<(), ServiceError> {
         let user_uid = parse_uuid(&user_uid)?;
         let chat_uid = parse_uuid(&chat_uid)?;
+        ensure_participant(&self.repository, &chat_uid, &caller_uid).await?;
 
         self.user_client.get_user_by_uid(user_uid)
             .await
             .map_err(|e| ServiceError::not_found(&format!("User {} not found: {}", user_uid, e)))?;
-        self.repository.add_participant(&chat_uid, &user_uid).await
+        self.repository.add_participant(&chat_uid, &user_uid).await.map_err(|e| match e {
+            RepositoryError::Conflict { .. } => ServiceError::conflict("The user is already a member of the chat room"),
+            RepositoryError::ForeignKey { .. } => ServiceError::not_found(&format!("Chat with uid {} not found", chat_uid)),
+            e => e.into(),
+        })
     }
 
-    pub async fn remove_participant(&self, chat_uid: String, user_uid: String) -> Result<(), ServiceError> {
+    pub async fn remove_participant(&self, caller_uid: Uuid, chat_uid: String, user_uid: String) -> Result<(), ServiceError> {
         let chat_uid = parse_uuid(&chat_uid)?;
         let user_uid = parse_uuid(&user_uid)?;
+        ensure_participant(&self.repository, &chat_uid, &caller_uid).await?;
         
-        self.repository.remove_participant(&chat_uid, &user_uid).await
+        Ok(self.repository.remove_participant(&chat_uid, &user_uid).await?)
     }
 
-    pub async fn get_chat_participants(&self, chat_uid: String) -> Result<Vec<Uuid>, ServiceError> {
+    pub async fn get_chat_participants(&self, caller_uid: Uuid, chat_uid: String) -> Result<Vec<Uuid>, ServiceError> {
         let chat_uid = parse_uuid(&chat_uid)?;
-        self.repository.get_chat_participants(&chat_uid).await
+        ensure_participant(&self.repository, &chat_uid, &caller_uid).await?;
+        Ok(self.repository.get_chat_participants(&chat_uid).await?)
     }
 
 }
@@ -90,4 +102,83 @@ impl<T: ChatRepository> ChatService<T> {
 fn parse_uuid(uuid_str: &str) -> Result<Uuid, ServiceError> {
     Uuid::parse_str(uuid_str)
         .map_err(|e| ServiceError::bad_request(&format!("Invalid UUID: {}", e)))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::fakes::FakeChatRepository;
+
+    fn service(chat_uid: Uuid, participants: &[Uuid]) -> ChatService<FakeChatRepository> {
+        ChatService {
+            repository: FakeChatRepository::with_chat(chat_uid, participants),
+            user_client: Arc::new(UserGrpcClient::disconnected()),
+        }
+    }
+
+    #[tokio::test]
+    async fn participant_can_read_chat_and_participants() {
+        let (chat_uid, member) = (Uuid::new_v4(), Uuid::new_v4());
+        let service = service(chat_uid, &[member]);
+
+        let chat = service.get_chat_by_uid(member, chat_uid.to_string()).await.unwrap();
+        assert_eq!(chat.uid, chat_uid);
+
+        let participants = service.get_chat_participants(member, chat_uid.to_string()).await.unwrap();
+        assert_eq!(participants, vec![member]);
+    }
+
+    #[tokio::test]
+    async fn non_participant_cannot_read_chat_or_participants() {
+        let (chat_uid, member, outsider) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let service = service(chat_uid, &[member]);
+
+        let err = service.get_chat_by_uid(outsider, chat_uid.to_string()).await.unwrap_err();
+        assert_eq!(err.status_code, 403);
+
+        let err = service.get_chat_participants(outsider, chat_uid.to_string()).await.unwrap_err();
+        assert_eq!(err.status_code, 403);
+    }
+
+    #[tokio::test]
+    async fn unknown_chats_look_like_chats_of_others() {
+        let (chat_uid, member) = (Uuid::new_v4(), Uuid::new_v4());
+        let service = service(chat_uid, &[member]);
+
+        let unknown = service.get_chat_by_uid(member, Uuid::new_v4().to_string()).await.unwrap_err();
+        let foreign = service.get_chat_by_uid(Uuid::new_v4(), chat_uid.to_string()).await.unwrap_err();
+        assert_eq!((unknown.status_code, &unknown.message), (foreign.status_code, &foreign.message));
+        assert_eq!(unknown.status_code, 403);
+    }
+
+    #[tokio::test]
+    async fn non_participant_cannot_manage_members() {
+        let (chat_uid, member, outsider) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let service = service(chat_uid, &[member]);
+
+        let err = service.add_participant(outsider, chat_uid.to_string(), outsider.to_string()).await.unwrap_err();
+        assert_eq!(err.status_code, 403);
+
+        let err = service.remove_participant(outsider, chat_uid.to_string(), member.to_string()).await.unwrap_err();
+        assert_eq!(err.status_code, 403);
+        assert!(service.repository.is_participant(&chat_uid, &member).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn participant_can_remove_member() {
+        let (chat_uid, member, other) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let service = service(chat_uid, &[member, other]);
+
+        service.remove_participant(member, chat_uid.to_string(), other.to_string()).await.unwrap();
+        assert!(!service.repository.is_participant(&chat_uid, &other).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn user_chats_only_include_own_chats() {
+        let (chat_uid, member, outsider) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let service = service(chat_uid, &[member]);
+
+        assert_eq!(service.get_user_chats(member).await.unwrap().len(), 1);
+        assert!(service.get_user_chats(outsider).await.unwrap().is_empty());
+    }
+}
@@ -2,6 +2,7 @@ use crate::errors::service_error::ServiceError;
 use crate::models::message::{CreateMessageDTO, Message};
 use crate::repositories::chat_repository::{ChatRepository, PgChatRepository};
 use crate::repositories::message_repository::{MessageRepository, PgMessageRepository};
+use crate::services::authorization::ensure_participant;
 use sqlx::PgPool;
 use uuid::Uuid;
 
@@ -23,21 +24,23 @@ impl MessageService<PgMessageRepository, PgChatRepository> {
 impl<M: MessageRepository, C: ChatRepository> MessageService<M, C> {
     pub async fn get_all_messages_by_chat_uid(
         &self,
+        caller_uid: Uuid,
         chat_uid: String,
     ) -> Result<Vec<Message>, ServiceError> {
 
         let chat_uid = Uuid::parse_str(&chat_uid)
             .map_err(|e| ServiceError::bad_request(&format!("Invalid UUID format: {}", e)))?;
 
-        let _ = self
-            .chat_repository
-            .get_by_uid(&chat_uid)
-            .await?;
+        ensure_participant(&self.chat_repository, &chat_uid, &caller_uid).await?;
 
-        self.repository.get_all_by_chat_uid(&chat_uid).await
+        Ok(self.repository.get_all_by_chat_uid(&chat_uid).await?)
     }
 
-    pub async fn create(&self, message_dto: CreateMessageDTO) -> Result<Message, ServiceError> {
+    pub async fn ensure_participant(&self, chat_uid: &Uuid, user_uid: &Uuid) -> Result<(), ServiceError> {
+        ensure_participant(&self.chat_repository, chat_uid, user_uid).await
+    }
+
+    pub async fn create(&self, user_uid: Uuid, message_dto: CreateMessageDTO) -> Result<Message, ServiceError> {
         if message_dto.content.trim().is_empty() {
             return Err(ServiceError::bad_request("Message content cannot be empty"));
         }
@@ -48,6 +51,66 @@ impl<M: MessageRepository, C: ChatRepository> MessageService<M, C> {
             ));
         }
 
-        self.repository.create(&message_dto).await
+        ensure_participant(&self.chat_repository, &message_dto.chat_uid, &user_uid).await?;
+
+        Ok(self.repository.create(&user_uid, &message_dto).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::fakes::{FakeChatRepository, FakeMessageRepository};
+
+    fn service(chat_uid: Uuid, participants: &[Uuid]) -> MessageService<FakeMessageRepository, FakeChatRepository> {
+        MessageService {
+            repository: FakeMessageRepository::default(),
+            chat_repository: FakeChatRepository::with_chat(chat_uid, participants),
+        }
+    }
+
+    fn message(chat_uid: Uuid) -> CreateMessageDTO {
+        CreateMessageDTO { chat_uid, content: "hello".to_string() }
+    }
+
+    #[tokio::test]
+    async fn participant_can_post_and_read_messages() {
+        let (chat_uid, member) = (Uuid::new_v4(), Uuid::new_v4());
+        let service = service(chat_uid, &[member]);
+
+        let created = service.create(member, message(chat_uid)).await.unwrap();
+        assert_eq!(created.user_uid, member);
+
+        let messages = service.get_all_messages_by_chat_uid(member, chat_uid.to_string()).await.unwrap();
+        assert_eq!(messages.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn non_participant_cannot_post() {
+        let (chat_uid, member, outsider) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let service = service(chat_uid, &[member]);
+
+        let err = service.create(outsider, message(chat_uid)).await.unwrap_err();
+        assert_eq!(err.status_code, 403);
+        assert!(service.repository.messages.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn non_participant_cannot_read_messages() {
+        let (chat_uid, member, outsider) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let service = service(chat_uid, &[member]);
+        service.create(member, message(chat_uid)).await.unwrap();
+
+        let err = service.get_all_messages_by_chat_uid(outsider, chat_uid.to_string()).await.unwrap_err();
+        assert_eq!(err.status_code, 403);
+    }
+
+    #[tokio::test]
+    async fn unknown_chat_is_forbidden() {
+        let member = Uuid::new_v4();
+        let service = service(Uuid::new_v4(), &[member]);
+
+        let err = service.get_all_messages_by_chat_uid(member, Uuid::new_v4().to_string()).await.unwrap_err();
+        assert_eq!(err.status_code, 403);
     }
 }
@@ -1,2 +1,3 @@
+pub mod authorization;
 pub mod chat_service;
 pub mod message_service;
\ No newline at end of file
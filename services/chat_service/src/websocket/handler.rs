@@ -1,8 +1,11 @@
 use actix_web::{web, Error, HttpRequest, HttpResponse};
 use actix_web_actors::ws;
+use shared::middleware::auth_user::AuthUser;
 use uuid::Uuid;
 use std::sync::Arc;
 use crate::websocket::session::ChatSession;
+use crate::websocket::ticket::TicketStore;
+use crate::models::ticket::WsConnectQuery;
 use crate::errors::service_error::ServiceError;
 use log;
 use crate::services::message_service::MessageService;
@@ -12,30 +15,60 @@ use crate::repositories::message_repository::PgMessageRepository;
 pub async fn chat_ws(
     req: HttpRequest,
     stream: web::Payload,
-    path: web::Path<(String, String)>,
+    chat_uid: web::Path<String>,
+    query: web::Query<WsConnectQuery>,
+    auth_user: Option<AuthUser>,
+    tickets: web::Data<TicketStore>,
     message_service: web::Data<Arc<MessageService<PgMessageRepository, PgChatRepository>>>
 ) -> Result<HttpResponse, Error> {
-    log::info!("Received connection request: {:?}", req);
+    // The query string and headers carry the ticket or bearer token, so only these are logged.
+    log::info!(
+        "Received connection request: {} {} from {}",
+        req.method(),
+        req.path(),
+        req.connection_info().peer_addr().unwrap_or("unknown")
+    );
     
-    let (chat_uid, user_uid) = path.into_inner();
-
+    let chat_uid = chat_uid.into_inner();
+    let ticket_protocol = ticket_protocol(&req);
+    let user_uid = match auth_user {
+        // A socket reads and posts, so a bearer token needs write access to chats, the same as
+        // /api/ws/tickets asks before handing out a ticket.
+        Some(auth_user) if !auth_user.token.has_scope("chats:write") => {
+            log::warn!("Rejected WebSocket connection for user {}: missing chats:write", auth_user.uid);
+            return Err(ServiceError::forbidden("Insufficient privileges").into());
+        }
+        Some(auth_user) => auth_user.uid,
+        None => {
+            let protocol_ticket = ticket_protocol.as_deref()
+                .and_then(|protocol| protocol.strip_prefix(TICKET_PROTOCOL_PREFIX))
+                .map(String::from);
+            let ticket = query.into_inner().ticket
+                .or(protocol_ticket)
+                .ok_or_else(|| ServiceError::unauthorized("Missing WebSocket ticket"))?;
+            tickets.redeem(&ticket)
+                .ok_or_else(|| ServiceError::unauthorized("Invalid or expired WebSocket ticket"))?
+        }
+    };
 
-    log::info!("Parsing chat_uid: {} and user_uid: {}", chat_uid, user_uid);
+    log::info!("Parsing chat_uid: {} for user_uid: {}", chat_uid, user_uid);
 
      let chat_uid = Uuid::parse_str(&chat_uid)
       .map_err(|_| ServiceError::bad_request("Invalid chat UUID"))?;
-     let user_uid = Uuid::parse_str(&user_uid)
-         .map_err(|_| ServiceError::bad_request("Invalid user UUID"))?;
 
     log::info!("UUIDs parsed successfully: chat_uid: {:?}, user_uid: {:?}", chat_uid, user_uid);
 
+    message_service.ensure_participant(&chat_uid, &user_uid).await?;
+
     let session = ChatSession::new(
         chat_uid,
         user_uid,
         message_service.get_ref().clone()
     );
     
-    match ws::start(session, &req, stream) {
+    // Browsers drop the connection unless one of the offered subprotocols is echoed back.
+    let protocols: Vec<&str> = ticket_protocol.as_deref().into_iter().collect();
+    match ws::WsResponseBuilder::new(session, &req, stream).protocols(&protocols).start() {
         Ok(resp) => {
             log::info!("WebSocket connection established successfully.");
             Ok(resp)
@@ -45,4 +78,17 @@ pub async fn chat_ws(
             Err(ServiceError::internal_error(&format!("Failed to start WebSocket: {}", e)).into())
         }
     }
-}
\ No newline at end of file
+}
+
+const TICKET_PROTOCOL_PREFIX: &str = "ticket.";
+
+fn ticket_protocol(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get("Sec-WebSocket-Protocol")?
+        .to_str()
+        .ok()?
+        .split(',')
+        .map(str::trim)
+        .find(|protocol| protocol.starts_with(TICKET_PROTOCOL_PREFIX))
+        .map(String::from)
+}
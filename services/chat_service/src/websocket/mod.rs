@@ -1,2 +1,3 @@
 pub mod handler;
-pub mod session; 
\ No newline at end of file
+pub mod session;
+pub mod ticket;
\ No newline at end of file
@@ -41,7 +41,7 @@ impl ChatSession {
         Self {
             chat_uid,
             user_uid,
-            message_service: message_service 
+            message_service,
         }
     }
 }
@@ -54,7 +54,7 @@ impl Actor for ChatSession {
         let mut sessions = SESSIONS.lock().unwrap();
         sessions
             .entry(self.chat_uid)
-            .or_insert_with(Vec::new)
+            .or_default()
             .push(addr);
         log::info!("User {} joined chat {}", self.user_uid, self.chat_uid);
     }
@@ -108,15 +108,14 @@ impl ChatSession {
         let _drop_logger = DropLogger("save_to_message variables");
     
         let message = CreateMessageDTO { 
-            chat_uid: chat_uid, 
-            user_uid: user_uid,
+            chat_uid, 
             content: content.clone(),
         };
     
         tokio::spawn(async move {
             let _inside_logger = DropLogger("inside async task");
 
-            let result = message_service.create(message).await;
+            let result = message_service.create(user_uid, message).await;
             
             match result {
                 Ok(_) => log::info!(
@@ -142,10 +141,10 @@ impl ChatSession {
             }
         };
 
-        if ws_message.event == "message" {
-            if let Some(content) = ws_message.data.get("content").and_then(|c| c.as_str()) {
-                self.broadcast_message(ctx, content);
-            }
+        if ws_message.event == "message"
+            && let Some(content) = ws_message.data.get("content").and_then(|c| c.as_str())
+        {
+            self.broadcast_message(ctx, content);
         }
     }
 }
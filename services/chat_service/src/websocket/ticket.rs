@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use rand::RngCore;
+use rand::rngs::OsRng;
+use uuid::Uuid;
+
+pub struct TicketStore {
+    ttl: Duration,
+    tickets: Mutex<HashMap<String, (Uuid, Instant)>>,
+}
+
+impl TicketStore {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            tickets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    pub fn issue(&self, user_uid: Uuid) -> String {
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        let ticket = hex::encode(bytes);
+
+        let mut tickets = self.tickets.lock().unwrap();
+        tickets.retain(|_, (_, expires_at)| *expires_at > Instant::now());
+        tickets.insert(ticket.clone(), (user_uid, Instant::now() + self.ttl));
+        ticket
+    }
+
+    pub fn redeem(&self, ticket: &str) -> Option<Uuid> {
+        let (user_uid, expires_at) = self.tickets.lock().unwrap().remove(ticket)?;
+        (expires_at > Instant::now()).then_some(user_uid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tickets_are_single_use() {
+        let store = TicketStore::new(Duration::from_secs(30));
+        let user_uid = Uuid::new_v4();
+        let ticket = store.issue(user_uid);
+
+        assert_eq!(store.redeem(&ticket), Some(user_uid));
+        assert_eq!(store.redeem(&ticket), None);
+    }
+
+    #[test]
+    fn expired_tickets_are_rejected() {
+        let store = TicketStore::new(Duration::ZERO);
+        let ticket = store.issue(Uuid::new_v4());
+
+        assert_eq!(store.redeem(&ticket), None);
+    }
+
+    #[test]
+    fn unknown_tickets_are_rejected() {
+        let store = TicketStore::new(Duration::from_secs(30));
+        assert_eq!(store.redeem("not-a-ticket"), None);
+    }
+}
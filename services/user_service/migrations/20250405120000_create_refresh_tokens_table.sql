@@ -0,0 +1,12 @@
+CREATE TABLE refresh_tokens (
+    uid UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_uid UUID NOT NULL REFERENCES users(uid) ON DELETE CASCADE,
+    family_uid UUID NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_refresh_tokens_family_uid ON refresh_tokens (family_uid);
+CREATE INDEX idx_refresh_tokens_user_uid ON refresh_tokens (user_uid);
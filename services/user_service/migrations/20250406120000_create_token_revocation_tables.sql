@@ -0,0 +1,13 @@
+CREATE TABLE revoked_tokens (
+    jti UUID PRIMARY KEY,
+    user_uid UUID NOT NULL REFERENCES users(uid) ON DELETE CASCADE,
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_revoked_tokens_expires_at ON revoked_tokens (expires_at);
+
+CREATE TABLE user_token_revocations (
+    user_uid UUID PRIMARY KEY REFERENCES users(uid) ON DELETE CASCADE,
+    revoked_before TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,21 @@
+CREATE TABLE IF NOT EXISTS roles (
+    name TEXT PRIMARY KEY,
+    scopes TEXT[] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS user_roles (
+    user_uid UUID NOT NULL REFERENCES users(uid) ON DELETE CASCADE,
+    role TEXT NOT NULL REFERENCES roles(name) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    PRIMARY KEY (user_uid, role)
+);
+
+INSERT INTO roles (name, scopes) VALUES
+    ('user', '{users:read,chats:read,chats:write}'),
+    ('admin', '{users:read,users:write,roles:write}')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO user_roles (user_uid, role)
+SELECT uid, 'user' FROM users
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,28 @@
+CREATE TABLE IF NOT EXISTS user_totp (
+    user_uid UUID PRIMARY KEY REFERENCES users(uid) ON DELETE CASCADE,
+    -- Kept as base32 because it is needed to compute codes; enabled_at stays NULL until confirmed.
+    secret TEXT NOT NULL,
+    enabled_at TIMESTAMPTZ,
+    last_used_step BIGINT,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS mfa_recovery_codes (
+    uid UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_uid UUID NOT NULL REFERENCES users(uid) ON DELETE CASCADE,
+    code_hash TEXT NOT NULL,
+    used_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_mfa_recovery_codes_user_uid ON mfa_recovery_codes(user_uid);
+
+CREATE TABLE IF NOT EXISTS mfa_challenges (
+    uid UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_uid UUID NOT NULL REFERENCES users(uid) ON DELETE CASCADE,
+    token_hash TEXT NOT NULL UNIQUE,
+    failed_attempts INTEGER NOT NULL DEFAULT 0,
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
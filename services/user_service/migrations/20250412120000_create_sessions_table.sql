@@ -0,0 +1,14 @@
+-- A session is one login; its uid is the family_uid of the refresh tokens it issues.
+CREATE TABLE IF NOT EXISTS sessions (
+    uid UUID PRIMARY KEY,
+    user_uid UUID NOT NULL REFERENCES users(uid) ON DELETE CASCADE,
+    device_name TEXT,
+    user_agent TEXT,
+    ip_address TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_used_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_sessions_user_uid ON sessions(user_uid);
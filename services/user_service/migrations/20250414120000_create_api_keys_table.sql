@@ -0,0 +1,15 @@
+-- Only the SHA-256 of a key is stored; `prefix` is the non-secret start shown in listings.
+CREATE TABLE IF NOT EXISTS api_keys (
+    uid UUID PRIMARY KEY,
+    user_uid UUID NOT NULL REFERENCES users(uid) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    prefix TEXT NOT NULL,
+    key_hash TEXT NOT NULL UNIQUE,
+    scopes TEXT[] NOT NULL DEFAULT '{}',
+    expires_at TIMESTAMPTZ,
+    last_used_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_api_keys_user_uid ON api_keys(user_uid);
@@ -0,0 +1,24 @@
+-- `public_key` is the COSE key from the authenticator, kept as is.
+CREATE TABLE IF NOT EXISTS passkeys (
+    uid UUID PRIMARY KEY,
+    user_uid UUID NOT NULL REFERENCES users(uid) ON DELETE CASCADE,
+    credential_id TEXT NOT NULL UNIQUE,
+    public_key BYTEA NOT NULL,
+    algorithm INTEGER NOT NULL,
+    sign_count BIGINT NOT NULL DEFAULT 0,
+    name TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_used_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_passkeys_user_uid ON passkeys(user_uid);
+
+-- Challenges are looked up by the hash of the value echoed back in clientDataJSON.
+CREATE TABLE IF NOT EXISTS webauthn_challenges (
+    challenge_hash TEXT PRIMARY KEY,
+    user_uid UUID REFERENCES users(uid) ON DELETE CASCADE,
+    purpose TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,9 @@
+-- Optional profile fields shown next to a user in chats. An expired status is hidden on read.
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS display_name VARCHAR(50),
+    ADD COLUMN IF NOT EXISTS avatar_url TEXT,
+    ADD COLUMN IF NOT EXISTS bio VARCHAR(500),
+    ADD COLUMN IF NOT EXISTS status_text VARCHAR(100),
+    ADD COLUMN IF NOT EXISTS status_expires_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS locale VARCHAR(35),
+    ADD COLUMN IF NOT EXISTS time_zone VARCHAR(64);
@@ -0,0 +1,25 @@
+-- Emails and usernames are unique regardless of case, and logins containing `@` are looked up as
+-- emails. Accounts that already differ only in case, or whose username contains `@`, have to be
+-- merged or renamed by hand first, so the migration lists them and stops.
+DO $$
+DECLARE
+    collisions TEXT;
+BEGIN
+    SELECT string_agg(kind || ' ' || accounts, '; ') INTO collisions FROM (
+        SELECT 'email' AS kind, string_agg(email || ' (' || uid || ')', ', ') AS accounts
+        FROM users GROUP BY LOWER(email) HAVING COUNT(*) > 1
+        UNION ALL
+        SELECT 'username', string_agg(username || ' (' || uid || ')', ', ')
+        FROM users GROUP BY LOWER(username) HAVING COUNT(*) > 1
+        UNION ALL
+        SELECT 'username with @', string_agg(username || ' (' || uid || ')', ', ')
+        FROM users WHERE username LIKE '%@%' HAVING COUNT(*) > 0
+    ) AS conflicts;
+
+    IF collisions IS NOT NULL THEN
+        RAISE EXCEPTION 'Accounts clash with case-insensitive logins, resolve them before migrating: %', collisions;
+    END IF;
+END $$;
+
+CREATE UNIQUE INDEX IF NOT EXISTS users_email_lower_key ON users (LOWER(email));
+CREATE UNIQUE INDEX IF NOT EXISTS users_username_lower_key ON users (LOWER(username));
@@ -0,0 +1,8 @@
+-- Trigram indexes serve both prefix (LIKE 'abc%') and fuzzy (%) directory searches.
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX IF NOT EXISTS users_username_trgm_idx ON users USING GIN (LOWER(username) gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS users_display_name_trgm_idx ON users USING GIN (LOWER(display_name) gin_trgm_ops);
+
+-- Paging by username uses users_username_lower_key; newest first needs its own keyset index.
+CREATE INDEX IF NOT EXISTS users_created_at_uid_idx ON users (created_at DESC, uid DESC);
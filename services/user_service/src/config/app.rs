@@ -1,19 +1,116 @@
 use crate::controllers::*;
 use actix_web::web;
+use shared::middleware::require::{RequireAccess, RequireLogin, RequireRole};
 
 pub fn config_services(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/api")
             .service(
                 web::scope("/users")
-                    .route("", web::get().to(user_controller::get_users))
-                    .route("/{uid}", web::get().to(user_controller::get_user_by_id))
-                    
+                    .service(
+                        web::scope("/me")
+                            .wrap(RequireLogin)
+                            .route("", web::patch().to(user_controller::update_profile))
+                            .route("/password", web::put().to(user_controller::change_password))
+                            .route("/email", web::put().to(user_controller::change_email))
+                            .route("/mfa/totp", web::post().to(mfa_controller::start_totp_enrollment))
+                            .route("/mfa/totp", web::delete().to(mfa_controller::disable_totp))
+                            .route("/mfa/totp/confirm", web::post().to(mfa_controller::confirm_totp))
+                            .route("/sessions", web::get().to(user_controller::get_sessions))
+                            .route("/sessions/{session_uid}", web::delete().to(user_controller::revoke_session))
+                            .route("/api-keys", web::get().to(api_key_controller::get_api_keys))
+                            .route("/api-keys", web::post().to(api_key_controller::create_api_key))
+                            .route("/api-keys/{key_uid}", web::delete().to(api_key_controller::revoke_api_key))
+                            .route("/passkeys", web::get().to(passkey_controller::get_passkeys))
+                            .route("/passkeys", web::post().to(passkey_controller::finish_registration))
+                            .route("/passkeys/options", web::post().to(passkey_controller::start_registration))
+                            .route("/passkeys/{passkey_uid}", web::delete().to(passkey_controller::delete_passkey))
+                    )
+                    .service(
+                        web::resource("")
+                            .wrap(RequireAccess("users"))
+                            .route(web::get().to(user_controller::get_users))
+                    )
+                    .service(
+                        web::resource("/{uid}")
+                            .wrap(RequireAccess("users"))
+                            .route(web::get().to(user_controller::get_user_by_id))
+                    )
             )
             .service(
                 web::scope("/auth") 
                     .route("/signup", web::post().to(user_controller::signup))
-                    .route("/login", web::post().to(user_controller::login))
+                    .route("/login", web::post().to(auth_controller::login))
+                    .route("/mfa", web::post().to(auth_controller::verify_mfa))
+                    .route("/mfa/passkey/options", web::post().to(passkey_controller::start_mfa))
+                    .route("/mfa/passkey", web::post().to(passkey_controller::finish_mfa))
+                    .route("/passkey/options", web::post().to(passkey_controller::start_login))
+                    .route("/passkey", web::post().to(passkey_controller::finish_login))
+                    .route("/oidc/{provider}/authorize", web::get().to(auth_controller::oidc_authorize))
+                    .route("/oidc/{provider}/callback", web::get().to(auth_controller::oidc_callback))
+                    .route("/refresh", web::post().to(auth_controller::refresh))
+                    .service(web::resource("/logout").wrap(RequireLogin).route(web::post().to(auth_controller::logout)))
+                    .service(web::resource("/logout-all").wrap(RequireLogin).route(web::post().to(auth_controller::logout_all)))
+                    .route("/verify", web::get().to(auth_controller::verify_email))
+                    .route("/verify/resend", web::post().to(auth_controller::resend_verification))
+                    .route("/forgot-password", web::post().to(auth_controller::forgot_password))
+                    .route("/reset-password", web::post().to(auth_controller::reset_password))
             )
+            .service(
+                web::scope("/admin")
+                    .wrap(RequireRole("admin"))
+                    .route("/users/{uid}/roles", web::get().to(admin_controller::get_user_roles))
+                    .route("/users/{uid}/roles", web::post().to(admin_controller::add_user_role))
+                    .route("/users/{uid}/roles/{role}", web::delete().to(admin_controller::remove_user_role))
+            )
+    );
+
+    cfg.service(
+        web::resource("/.well-known/jwks.json")
+            .route(web::get().to(auth_controller::jwks))
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App, HttpMessage};
+    use actix_web::dev::Service;
+    use actix_web::http::Method;
+    use shared::models::user_token::UserToken;
+    use uuid::Uuid;
+
+    // The guards reject before any handler runs, so no services need to be registered.
+    async fn status_for_api_key(method: Method, path: &str, scopes: &[&str]) -> u16 {
+        let token = UserToken::for_api_key(Uuid::new_v4(), Uuid::new_v4(), scopes.iter().map(|s| s.to_string()).collect(), None);
+        let app = test::init_service(
+            App::new()
+                .configure(config_services)
+                .wrap_fn(move |req, srv| {
+                    req.extensions_mut().insert(token.clone());
+                    srv.call(req)
+                }),
+        )
+        .await;
+        let req = test::TestRequest::default().method(method).uri(path).to_request();
+        match test::try_call_service(&app, req).await {
+            Ok(resp) => resp.status().as_u16(),
+            Err(e) => e.as_response_error().status_code().as_u16(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn api_keys_cannot_manage_the_account() {
+        let all = ["users:read", "users:write", "chats:read", "chats:write"];
+        assert_eq!(status_for_api_key(Method::POST, "/api/users/me/passkeys/options", &all).await, 403);
+        assert_eq!(status_for_api_key(Method::PATCH, "/api/users/me", &all).await, 403);
+        assert_eq!(status_for_api_key(Method::POST, "/api/users/me/api-keys", &all).await, 403);
+        assert_eq!(status_for_api_key(Method::POST, "/api/auth/logout-all", &all).await, 403);
+    }
+
+    #[actix_web::test]
+    async fn the_directory_needs_the_read_scope() {
+        assert_eq!(status_for_api_key(Method::GET, "/api/users", &["chats:read"]).await, 403);
+        assert_eq!(status_for_api_key(Method::GET, &format!("/api/users/{}", Uuid::new_v4()), &[]).await, 403);
+    }
+}
@@ -1,14 +1,61 @@
 use std::env;
+use std::fs;
 use std::net::SocketAddr;
+use std::path::Path;
 use std::sync::Arc;
 use std::fmt;
+use jsonwebtoken::jwk::JwkSet;
+use shared::models::keys::{public_jwk_from_ed_pem, KeySet, SigningKey};
+use shared::middleware::rules::AuthRules;
+use shared::grpc::service_auth::validate_service_key;
+use shared::grpc::tls::GrpcTlsConfig;
+use crate::mailer::MailerConfig;
+use crate::oidc::OidcProviderConfig;
+use crate::webauthn::WebauthnConfig;
+use crate::services::login_throttle::LoginThrottleSettings;
+use crate::services::password_hasher::{Argon2Hasher, Argon2Settings};
+use crate::services::password_policy::{PasswordPolicy, PasswordPolicySettings};
+
+const DEFAULT_PUBLIC_ROUTES: &str = "POST /api/auth/signup, POST /api/auth/login, POST /api/auth/mfa, POST /api/auth/mfa/passkey/options, POST /api/auth/mfa/passkey, POST /api/auth/passkey/options, POST /api/auth/passkey, GET /api/auth/oidc/*/authorize, GET /api/auth/oidc/*/callback, POST /api/auth/refresh, GET /api/auth/verify, POST /api/auth/verify/resend, POST /api/auth/forgot-password, POST /api/auth/reset-password, GET /.well-known/jwks.json, OPTIONS /**";
+
+// The only piece of the config the request handlers need: whether the client address may be
+// taken from X-Forwarded-For / Forwarded headers set by a trusted proxy.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIpConfig {
+    pub trust_proxy_headers: bool,
+}
 
 #[derive(Clone)]
 pub struct Config {
     pub database_url: String,
-    pub jwt_secret: Arc<String>,
+    pub jwt_signing_key: Arc<SigningKey>,
+    pub jwt_keys: Arc<KeySet>,
+    pub access_token_ttl: chrono::Duration,
+    pub refresh_token_ttl: chrono::Duration,
+    pub email_verification_ttl: chrono::Duration,
+    pub email_resend_cooldown: chrono::Duration,
+    pub require_email_verification: bool,
+    pub password_reset_ttl: chrono::Duration,
+    pub password_reset_url: String,
+    pub public_base_url: String,
+    pub mailer: MailerConfig,
+    pub login_throttle: LoginThrottleSettings,
+    pub argon2: Argon2Settings,
+    pub password_policy: PasswordPolicySettings,
+    pub trust_proxy_headers: bool,
+    pub token_purge_interval: std::time::Duration,
+    pub mfa_issuer: String,
+    pub mfa_challenge_ttl: chrono::Duration,
+    pub mfa_max_attempts: i32,
+    pub oidc_providers: Vec<OidcProviderConfig>,
+    pub oidc_login_ttl: chrono::Duration,
+    pub webauthn: WebauthnConfig,
+    pub webauthn_challenge_ttl: chrono::Duration,
     pub http_addr: SocketAddr,
     pub grpc_addr: SocketAddr,
+    pub grpc_service_keys: Vec<String>,
+    pub grpc_tls: GrpcTlsConfig,
+    pub auth_rules: AuthRules,
     pub log_level: log::LevelFilter,
 }
 
@@ -16,18 +63,147 @@ impl Config {
     pub fn from_env() -> Result<Self, String> {
         let database_url = env::var("DATABASE_URL_USER_SERVICE")
             .map_err(|_| "DATABASE_URL_USER_SERVICE must be set")?;
-        let jwt_secret = env::var("JWT_SECRET")
-            .map_err(|_| "JWT_SECRET must be set")?;
+        let signing_key_id = env::var("JWT_SIGNING_KEY_ID")
+            .map_err(|_| "JWT_SIGNING_KEY_ID must be set")?;
+        let signing_key_path = env::var("JWT_SIGNING_KEY_PATH")
+            .map_err(|_| "JWT_SIGNING_KEY_PATH must be set")?;
+        let signing_key_pem = fs::read(&signing_key_path)
+            .map_err(|e| format!("Failed to read JWT_SIGNING_KEY_PATH {}: {}", signing_key_path, e))?;
+        let signing_key = SigningKey::from_ed_pem(&signing_key_id, &signing_key_pem)
+            .map_err(|e| format!("Invalid JWT signing key: {}", e))?;
+        let mut jwks = JwkSet { keys: vec![signing_key.public_jwk().clone()] };
+        if let Ok(public_keys_dir) = env::var("JWT_PUBLIC_KEYS_DIR") {
+            jwks.keys.extend(load_public_keys(Path::new(&public_keys_dir), &signing_key_id)?);
+        }
+        let jwt_keys = KeySet::new(jwks).map_err(|e| format!("Invalid JWT key set: {}", e))?;
+        let access_token_ttl = env::var("ACCESS_TOKEN_TTL_MINUTES")
+            .unwrap_or("15".into())
+            .parse::<i64>()
+            .map_err(|_| "ACCESS_TOKEN_TTL_MINUTES must be a number")?;
+        let refresh_token_ttl = env::var("REFRESH_TOKEN_TTL_DAYS")
+            .unwrap_or("30".into())
+            .parse::<i64>()
+            .map_err(|_| "REFRESH_TOKEN_TTL_DAYS must be a number")?;
+        let email_verification_ttl = env::var("EMAIL_VERIFICATION_TTL_HOURS")
+            .unwrap_or("24".into())
+            .parse::<i64>()
+            .map_err(|_| "EMAIL_VERIFICATION_TTL_HOURS must be a number")?;
+        let email_resend_cooldown = chrono::Duration::seconds(parse_env("EMAIL_RESEND_COOLDOWN_SECONDS", 60)?);
+        let require_email_verification = env::var("REQUIRE_EMAIL_VERIFICATION")
+            .unwrap_or("false".into())
+            .parse::<bool>()
+            .map_err(|_| "REQUIRE_EMAIL_VERIFICATION must be true or false")?;
+        let password_reset_ttl = env::var("PASSWORD_RESET_TTL_MINUTES")
+            .unwrap_or("60".into())
+            .parse::<i64>()
+            .map_err(|_| "PASSWORD_RESET_TTL_MINUTES must be a number")?;
+        let mailer = match env::var("MAILER").unwrap_or("log".into()).as_str() {
+            "log" => MailerConfig::Log {
+                outbox: env::var("MAIL_OUTBOX_PATH").ok().map(Into::into),
+            },
+            "smtp" => MailerConfig::Smtp {
+                host: env::var("SMTP_HOST").map_err(|_| "SMTP_HOST must be set when MAILER=smtp")?,
+                port: env::var("SMTP_PORT")
+                    .unwrap_or("587".into())
+                    .parse::<u16>()
+                    .map_err(|_| "SMTP_PORT must be a valid port number")?,
+                credentials: match (env::var("SMTP_USERNAME"), env::var("SMTP_PASSWORD")) {
+                    (Ok(username), Ok(password)) => Some((username, password)),
+                    _ => None,
+                },
+                from: env::var("MAIL_FROM").map_err(|_| "MAIL_FROM must be set when MAILER=smtp")?,
+            },
+            other => return Err(format!("MAILER must be either log or smtp, got {}", other)),
+        };
+        let login_throttle = LoginThrottleSettings {
+            max_attempts_per_email: parse_env("LOGIN_MAX_ATTEMPTS_PER_EMAIL", 5)?,
+            max_attempts_per_ip: parse_env("LOGIN_MAX_ATTEMPTS_PER_IP", 20)?,
+            base_lockout: chrono::Duration::seconds(parse_env("LOGIN_LOCKOUT_BASE_SECONDS", 30)?),
+            max_lockout: chrono::Duration::seconds(parse_env("LOGIN_LOCKOUT_MAX_SECONDS", 900)?),
+            window: chrono::Duration::minutes(parse_env("LOGIN_ATTEMPT_WINDOW_MINUTES", 15)?),
+        };
+        let argon2 = Argon2Settings {
+            memory_kib: parse_env("ARGON2_MEMORY_KIB", argon2::Params::DEFAULT_M_COST)?,
+            iterations: parse_env("ARGON2_ITERATIONS", argon2::Params::DEFAULT_T_COST)?,
+            parallelism: parse_env("ARGON2_PARALLELISM", argon2::Params::DEFAULT_P_COST)?,
+            pepper: env::var("PASSWORD_PEPPER").ok().filter(|pepper| !pepper.is_empty()),
+            pepper_id: env::var("PASSWORD_PEPPER_ID").unwrap_or("1".into()),
+        };
+        Argon2Hasher::new(&argon2)?;
+        let password_policy = PasswordPolicySettings {
+            min_length: parse_env("PASSWORD_MIN_LENGTH", 8)?,
+            max_length: parse_env("PASSWORD_MAX_LENGTH", 128)?,
+            min_strength_bits: parse_env("PASSWORD_MIN_STRENGTH_BITS", 40.0)?,
+            breached_list_path: env::var("PASSWORD_BREACHED_LIST_PATH").ok().filter(|path| !path.is_empty()),
+            require_breached_list: env::var("PASSWORD_REQUIRE_BREACHED_LIST")
+                .unwrap_or("false".into())
+                .parse::<bool>()
+                .map_err(|_| "PASSWORD_REQUIRE_BREACHED_LIST must be true or false")?,
+        };
+        PasswordPolicy::new(&password_policy)?;
+        let trust_proxy_headers = env::var("TRUST_PROXY_HEADERS")
+            .unwrap_or("false".into())
+            .parse::<bool>()
+            .map_err(|_| "TRUST_PROXY_HEADERS must be true or false")?;
+        let token_purge_interval = std::time::Duration::from_secs(60 * parse_env("TOKEN_PURGE_INTERVAL_MINUTES", 60)?);
+        let mfa_issuer = env::var("MFA_ISSUER").unwrap_or("TicketBan".into());
+        let mfa_challenge_ttl = chrono::Duration::seconds(parse_env("MFA_CHALLENGE_TTL_SECONDS", 300)?);
+        let mfa_max_attempts = parse_env("MFA_MAX_ATTEMPTS", 5)?;
+        let oidc_providers = load_oidc_providers()?;
+        let oidc_login_ttl = chrono::Duration::minutes(parse_env("OIDC_LOGIN_TTL_MINUTES", 10)?);
         let host = env::var("USER_SERVICE_HOST")
             .map_err(|_| "USER_SERVICE_HOST must be set")?;
         let port = env::var("USER_SERVICE_PORT")
             .map_err(|_| "USER_SERVICE_PORT must be set")?
             .parse::<u16>()
             .map_err(|_| "USER_SERVICE_PORT must be a valid port number")?;
+        let public_base_url = env::var("PUBLIC_BASE_URL")
+            .unwrap_or(format!("http://{}:{}", host, port))
+            .trim_end_matches('/')
+            .to_string();
+        let webauthn = WebauthnConfig {
+            rp_id: match env::var("WEBAUTHN_RP_ID") {
+                Ok(rp_id) => rp_id,
+                Err(_) => reqwest::Url::parse(&public_base_url)
+                    .ok()
+                    .and_then(|url| url.host_str().map(String::from))
+                    .ok_or("WEBAUTHN_RP_ID must be set when PUBLIC_BASE_URL has no host")?,
+            },
+            rp_name: env::var("WEBAUTHN_RP_NAME").unwrap_or(mfa_issuer.clone()),
+            origin: env::var("WEBAUTHN_ORIGIN").unwrap_or(public_base_url.clone()),
+        };
+        let webauthn_challenge_ttl = chrono::Duration::seconds(parse_env("WEBAUTHN_CHALLENGE_TTL_SECONDS", 300)?);
+        let password_reset_url = env::var("PASSWORD_RESET_URL")
+            .unwrap_or(format!("{}/reset-password", public_base_url));
         let grpc_port = env::var("USER_SERVICE_GRPC_PORT")
             .unwrap_or("50052".into())
             .parse::<u16>()
             .map_err(|_| "USER_SERVICE_GRPC_PORT must be a valid port number")?;
+        let grpc_service_keys: Vec<String> = env::var("GRPC_SERVICE_KEYS")
+            .map_err(|_| "GRPC_SERVICE_KEYS must be set")?
+            .split(',')
+            .map(|key| key.trim().to_string())
+            .filter(|key| !key.is_empty())
+            .collect();
+        if grpc_service_keys.is_empty() {
+            return Err("GRPC_SERVICE_KEYS must contain at least one key".into());
+        }
+        for key in &grpc_service_keys {
+            validate_service_key(key).map_err(|e| format!("Invalid GRPC_SERVICE_KEYS: {}", e))?;
+        }
+        let grpc_tls = GrpcTlsConfig {
+            cert_path: env::var("GRPC_TLS_CERT_PATH").ok(),
+            key_path: env::var("GRPC_TLS_KEY_PATH").ok(),
+            ca_path: env::var("GRPC_TLS_CLIENT_CA_PATH").ok(),
+            domain: None,
+        };
+        if grpc_tls.is_enabled() {
+            grpc_tls.server_config().map_err(|e| format!("Invalid gRPC TLS config: {}", e))?;
+        }
+        let auth_rules = AuthRules::from_config(
+            &env::var("AUTH_PUBLIC_ROUTES").unwrap_or(DEFAULT_PUBLIC_ROUTES.into()),
+            &env::var("AUTH_OPTIONAL_ROUTES").unwrap_or_default(),
+        )?;
         let log_level = env::var("LOG_LEVEL")
             .unwrap_or("info".into())
             .parse::<log::LevelFilter>()
@@ -35,9 +211,34 @@ impl Config {
 
         Ok(Self {
             database_url,
-            jwt_secret: Arc::new(jwt_secret),
+            jwt_signing_key: Arc::new(signing_key),
+            jwt_keys: Arc::new(jwt_keys),
+            access_token_ttl: chrono::Duration::minutes(access_token_ttl),
+            refresh_token_ttl: chrono::Duration::days(refresh_token_ttl),
+            email_verification_ttl: chrono::Duration::hours(email_verification_ttl),
+            email_resend_cooldown,
+            require_email_verification,
+            password_reset_ttl: chrono::Duration::minutes(password_reset_ttl),
+            password_reset_url,
+            public_base_url,
+            mailer,
+            login_throttle,
+            argon2,
+            password_policy,
+            trust_proxy_headers,
+            token_purge_interval,
+            mfa_issuer,
+            mfa_challenge_ttl,
+            mfa_max_attempts,
+            oidc_providers,
+            oidc_login_ttl,
+            webauthn,
+            webauthn_challenge_ttl,
             http_addr: format!("{}:{}", host, port).parse().map_err(|e| format!("Invalid HTTP address: {}", e))?,
             grpc_addr: format!("[::1]:{}", grpc_port).parse().map_err(|e| format!("Invalid gRPC address: {}", e))?,
+            grpc_service_keys,
+            grpc_tls,
+            auth_rules,
             log_level,
         })
     }
@@ -47,10 +248,95 @@ impl fmt::Debug for Config {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Config")
             .field("database_url", &self.database_url)
+            .field("jwt_signing_key_id", &self.jwt_signing_key.kid)
+            .field("access_token_ttl", &self.access_token_ttl)
+            .field("refresh_token_ttl", &self.refresh_token_ttl)
+            .field("email_verification_ttl", &self.email_verification_ttl)
+            .field("email_resend_cooldown", &self.email_resend_cooldown)
+            .field("require_email_verification", &self.require_email_verification)
+            .field("password_reset_ttl", &self.password_reset_ttl)
+            .field("password_reset_url", &self.password_reset_url)
+            .field("public_base_url", &self.public_base_url)
+            .field("mailer", &self.mailer)
+            .field("login_throttle", &self.login_throttle)
+            .field("argon2", &self.argon2)
+            .field("password_policy", &self.password_policy)
+            .field("trust_proxy_headers", &self.trust_proxy_headers)
+            .field("token_purge_interval", &self.token_purge_interval)
+            .field("mfa_issuer", &self.mfa_issuer)
+            .field("mfa_challenge_ttl", &self.mfa_challenge_ttl)
+            .field("mfa_max_attempts", &self.mfa_max_attempts)
+            .field("oidc_providers", &self.oidc_providers)
+            .field("oidc_login_ttl", &self.oidc_login_ttl)
+            .field("webauthn", &self.webauthn)
+            .field("webauthn_challenge_ttl", &self.webauthn_challenge_ttl)
             .field("http_addr", &self.http_addr)
             .field("grpc_addr", &self.grpc_addr)
+            .field("grpc_service_keys", &format!("<{} keys>", self.grpc_service_keys.len()))
+            .field("grpc_tls", &self.grpc_tls)
+            .field("auth_rules", &self.auth_rules)
             .field("log_level", &self.log_level)
             .finish() 
     }
 }
 
+fn parse_env<T: std::str::FromStr>(name: &str, default: T) -> Result<T, String> {
+    match env::var(name) {
+        Ok(value) => value.parse::<T>().map_err(|_| format!("{} must be a number", name)),
+        Err(_) => Ok(default),
+    }
+}
+
+// OIDC_PROVIDERS=okta,google, then OIDC_OKTA_ISSUER, OIDC_OKTA_CLIENT_ID and so on for each name.
+fn load_oidc_providers() -> Result<Vec<OidcProviderConfig>, String> {
+    env::var("OIDC_PROVIDERS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|name| name.trim().to_lowercase())
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                return Err(format!("Invalid OIDC provider name: {}", name));
+            }
+            let prefix = format!("OIDC_{}", name.to_uppercase().replace('-', "_"));
+            let required = |suffix: &str| {
+                env::var(format!("{}_{}", prefix, suffix)).map_err(|_| format!("{}_{} must be set", prefix, suffix))
+            };
+            let auto_link_verified_email = env::var(format!("{}_AUTO_LINK_VERIFIED_EMAIL", prefix))
+                .unwrap_or("false".into())
+                .parse::<bool>()
+                .map_err(|_| format!("{}_AUTO_LINK_VERIFIED_EMAIL must be true or false", prefix))?;
+            Ok(OidcProviderConfig {
+                issuer: required("ISSUER")?,
+                client_id: required("CLIENT_ID")?,
+                client_secret: env::var(format!("{}_CLIENT_SECRET", prefix)).ok().filter(|secret| !secret.is_empty()),
+                scopes: env::var(format!("{}_SCOPES", prefix)).unwrap_or("openid email profile".into()),
+                auto_link_verified_email,
+                name,
+            })
+        })
+        .collect()
+}
+
+fn load_public_keys(dir: &Path, signing_key_id: &str) -> Result<Vec<jsonwebtoken::jwk::Jwk>, String> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read JWT_PUBLIC_KEYS_DIR {}: {}", dir.display(), e))?;
+
+    let mut keys = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(|e| format!("Failed to read JWT_PUBLIC_KEYS_DIR: {}", e))?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("pem") {
+            continue;
+        }
+        let Some(kid) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        if kid == signing_key_id {
+            continue;
+        }
+        let pem = fs::read(&path)
+            .map_err(|e| format!("Failed to read public key {}: {}", path.display(), e))?;
+        keys.push(public_jwk_from_ed_pem(kid, &pem).map_err(|e| format!("Invalid public key {}: {}", path.display(), e))?);
+    }
+    Ok(keys)
+}
@@ -1,3 +1,4 @@
 pub mod app;
 pub mod db;
+#[allow(clippy::module_inception)]
 pub mod config;
\ No newline at end of file
@@ -0,0 +1,32 @@
+use actix_web::{web, HttpResponse};
+use crate::models::role::RoleDTO;
+use crate::models::response::ResponseBody;
+use crate::services::user_service::UserService;
+use crate::repositories::user_repository::PgUserRepository;
+use crate::errors::service_error::ServiceError;
+
+pub async fn get_user_roles(
+    service: web::Data<UserService<PgUserRepository>>,
+    user_uid: web::Path<String>,
+) -> Result<HttpResponse, ServiceError> {
+    let roles = service.get_roles(&user_uid.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(ResponseBody::new("Roles retrieved successfully", Some(roles))))
+}
+
+pub async fn add_user_role(
+    service: web::Data<UserService<PgUserRepository>>,
+    user_uid: web::Path<String>,
+    role_dto: web::Json<RoleDTO>,
+) -> Result<HttpResponse, ServiceError> {
+    let roles = service.add_role(&user_uid.into_inner(), role_dto.0).await?;
+    Ok(HttpResponse::Ok().json(ResponseBody::new("Role granted successfully", Some(roles))))
+}
+
+pub async fn remove_user_role(
+    service: web::Data<UserService<PgUserRepository>>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, ServiceError> {
+    let (user_uid, role) = path.into_inner();
+    let roles = service.remove_role(&user_uid, &role).await?;
+    Ok(HttpResponse::Ok().json(ResponseBody::new("Role revoked successfully", Some(roles))))
+}
@@ -0,0 +1,34 @@
+use actix_web::{web, HttpResponse};
+use shared::middleware::auth_user::AuthUser;
+use crate::models::api_key::CreateApiKeyDTO;
+use crate::models::response::ResponseBody;
+use crate::services::api_key_service::ApiKeyService;
+use crate::repositories::user_repository::PgUserRepository;
+use crate::repositories::api_key_repository::PgApiKeyRepository;
+use crate::errors::service_error::ServiceError;
+
+pub async fn create_api_key(
+    service: web::Data<ApiKeyService<PgUserRepository, PgApiKeyRepository>>,
+    auth_user: AuthUser,
+    dto: web::Json<CreateApiKeyDTO>,
+) -> Result<HttpResponse, ServiceError> {
+    let api_key = service.create(&auth_user.token, dto.0).await?;
+    Ok(HttpResponse::Created().json(ResponseBody::new("API key created, store it now as it will not be shown again", Some(api_key))))
+}
+
+pub async fn get_api_keys(
+    service: web::Data<ApiKeyService<PgUserRepository, PgApiKeyRepository>>,
+    auth_user: AuthUser,
+) -> Result<HttpResponse, ServiceError> {
+    let api_keys = service.list(&auth_user.token).await?;
+    Ok(HttpResponse::Ok().json(ResponseBody::new("API keys retrieved successfully", Some(api_keys))))
+}
+
+pub async fn revoke_api_key(
+    service: web::Data<ApiKeyService<PgUserRepository, PgApiKeyRepository>>,
+    auth_user: AuthUser,
+    key_uid: web::Path<String>,
+) -> Result<HttpResponse, ServiceError> {
+    service.revoke(&auth_user.token, &key_uid.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(ResponseBody::new("API key revoked successfully", None::<()>)))
+}
@@ -0,0 +1,154 @@
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use shared::middleware::auth_user::AuthUser;
+use shared::models::keys::KeySet;
+use crate::models::user::LoginDTO;
+use crate::models::token::{RefreshTokenDTO, VerifyEmailQuery, ResendVerificationDTO, ForgotPasswordDTO, ResetPasswordDTO};
+use crate::models::mfa::{LoginOutcome, MfaVerifyDTO};
+use crate::models::session::ClientInfo;
+use crate::models::identity::OidcCallbackQuery;
+use crate::models::response::ResponseBody;
+use crate::services::auth_service::AuthService;
+use crate::services::oidc_service::OidcService;
+use crate::repositories::user_repository::PgUserRepository;
+use crate::repositories::token_repository::PgTokenRepository;
+use crate::repositories::identity_repository::PgIdentityRepository;
+use crate::config::config::ClientIpConfig;
+use crate::errors::service_error::ServiceError;
+
+pub async fn login(
+    req: HttpRequest,
+    service: web::Data<AuthService<PgUserRepository, PgTokenRepository>>,
+    ip_config: web::Data<ClientIpConfig>,
+    login_dto: web::Json<LoginDTO>,
+) -> Result<HttpResponse, ServiceError> {
+    let client = client_info(&req, &ip_config, login_dto.device_name.clone());
+    let outcome = service.login(login_dto.0, &client).await?;
+    let message = match outcome {
+        LoginOutcome::Authenticated(_) => "User logged in successfully",
+        LoginOutcome::MfaRequired(_) => "Two-factor authentication required",
+    };
+    Ok(HttpResponse::Ok().json(ResponseBody::new(message, Some(outcome))))
+}
+
+pub async fn verify_mfa(
+    req: HttpRequest,
+    service: web::Data<AuthService<PgUserRepository, PgTokenRepository>>,
+    ip_config: web::Data<ClientIpConfig>,
+    dto: web::Json<MfaVerifyDTO>,
+) -> Result<HttpResponse, ServiceError> {
+    let client = client_info(&req, &ip_config, dto.device_name.clone());
+    let response = service.verify_mfa(dto.0, &client).await?;
+    Ok(HttpResponse::Ok().json(ResponseBody::new("User logged in successfully", Some(response))))
+}
+
+pub async fn oidc_authorize(
+    service: web::Data<OidcService<PgUserRepository, PgIdentityRepository>>,
+    provider: web::Path<String>,
+) -> Result<HttpResponse, ServiceError> {
+    let url = service.start_login(&provider).await?;
+    Ok(HttpResponse::Found().insert_header((header::LOCATION, url)).finish())
+}
+
+pub async fn oidc_callback(
+    req: HttpRequest,
+    service: web::Data<OidcService<PgUserRepository, PgIdentityRepository>>,
+    ip_config: web::Data<ClientIpConfig>,
+    provider: web::Path<String>,
+    query: web::Query<OidcCallbackQuery>,
+) -> Result<HttpResponse, ServiceError> {
+    let client = client_info(&req, &ip_config, None);
+    let outcome = service.finish_login(&provider, query.into_inner(), &client).await?;
+    let message = match outcome {
+        LoginOutcome::Authenticated(_) => "User logged in successfully",
+        LoginOutcome::MfaRequired(_) => "Two-factor authentication required",
+    };
+    Ok(HttpResponse::Ok().json(ResponseBody::new(message, Some(outcome))))
+}
+
+pub async fn refresh(
+    req: HttpRequest,
+    service: web::Data<AuthService<PgUserRepository, PgTokenRepository>>,
+    ip_config: web::Data<ClientIpConfig>,
+    refresh_dto: web::Json<RefreshTokenDTO>,
+) -> Result<HttpResponse, ServiceError> {
+    let tokens = service.refresh(refresh_dto.0, &client_ip(&req, &ip_config)).await?;
+    Ok(HttpResponse::Ok().json(ResponseBody::new("Token refreshed successfully", Some(tokens))))
+}
+
+pub async fn logout(
+    service: web::Data<AuthService<PgUserRepository, PgTokenRepository>>,
+    auth_user: AuthUser,
+    refresh_dto: Option<web::Json<RefreshTokenDTO>>,
+) -> Result<HttpResponse, ServiceError> {
+    service.logout(&auth_user.token, refresh_dto.map(|dto| dto.0)).await?;
+    Ok(HttpResponse::Ok().json(ResponseBody::new("User logged out successfully", None::<()>)))
+}
+
+pub async fn logout_all(
+    service: web::Data<AuthService<PgUserRepository, PgTokenRepository>>,
+    auth_user: AuthUser,
+) -> Result<HttpResponse, ServiceError> {
+    service.logout_all(&auth_user.token).await?;
+    Ok(HttpResponse::Ok().json(ResponseBody::new("User logged out from all devices", None::<()>)))
+}
+
+pub async fn verify_email(
+    service: web::Data<AuthService<PgUserRepository, PgTokenRepository>>,
+    query: web::Query<VerifyEmailQuery>,
+) -> Result<HttpResponse, ServiceError> {
+    service.verify_email(&query.token).await?;
+    Ok(HttpResponse::Ok().json(ResponseBody::new("Email verified successfully", None::<()>)))
+}
+
+pub async fn resend_verification(
+    service: web::Data<AuthService<PgUserRepository, PgTokenRepository>>,
+    dto: web::Json<ResendVerificationDTO>,
+) -> Result<HttpResponse, ServiceError> {
+    service.resend_email_verification(dto.0).await?;
+    Ok(HttpResponse::Accepted().json(ResponseBody::new(
+        "If the address belongs to an unverified account, a verification email has been sent",
+        None::<()>,
+    )))
+}
+
+pub async fn forgot_password(
+    service: web::Data<AuthService<PgUserRepository, PgTokenRepository>>,
+    dto: web::Json<ForgotPasswordDTO>,
+) -> Result<HttpResponse, ServiceError> {
+    service.forgot_password(dto.0).await?;
+    Ok(HttpResponse::Accepted().json(ResponseBody::new(
+        "If the address belongs to an account, a password reset email has been sent",
+        None::<()>,
+    )))
+}
+
+pub async fn reset_password(
+    service: web::Data<AuthService<PgUserRepository, PgTokenRepository>>,
+    dto: web::Json<ResetPasswordDTO>,
+) -> Result<HttpResponse, ServiceError> {
+    service.reset_password(dto.0).await?;
+    Ok(HttpResponse::Ok().json(ResponseBody::new("Password reset successfully", None::<()>)))
+}
+
+pub async fn jwks(keys: web::Data<KeySet>) -> HttpResponse {
+    HttpResponse::Ok().json(keys.jwks())
+}
+
+pub(crate) fn client_info(req: &HttpRequest, ip_config: &ClientIpConfig, device_name: Option<String>) -> ClientInfo {
+    let user_agent = req.headers()
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.chars().take(512).collect());
+    let device_name = device_name.map(|name| name.chars().take(100).collect());
+    ClientInfo { ip_address: client_ip(req, ip_config), user_agent, device_name }
+}
+
+fn client_ip(req: &HttpRequest, ip_config: &ClientIpConfig) -> String {
+    let connection_info = req.connection_info();
+    let addr = if ip_config.trust_proxy_headers {
+        connection_info.realip_remote_addr()
+    } else {
+        connection_info.peer_addr()
+    };
+    addr.unwrap_or("unknown").to_string()
+}
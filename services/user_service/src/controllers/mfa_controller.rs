@@ -0,0 +1,37 @@
+use actix_web::{web, HttpResponse};
+use shared::middleware::auth_user::AuthUser;
+use crate::models::mfa::{MfaCodeDTO, ReauthDTO};
+use crate::models::response::ResponseBody;
+use crate::services::auth_service::AuthService;
+use crate::services::mfa_service::MfaService;
+use crate::repositories::user_repository::PgUserRepository;
+use crate::repositories::token_repository::PgTokenRepository;
+use crate::repositories::mfa_repository::PgMfaRepository;
+use crate::errors::service_error::ServiceError;
+
+pub async fn start_totp_enrollment(
+    service: web::Data<AuthService<PgUserRepository, PgTokenRepository>>,
+    auth_user: AuthUser,
+    dto: web::Json<ReauthDTO>,
+) -> Result<HttpResponse, ServiceError> {
+    let enrollment = service.start_totp_enrollment(&auth_user.uid, dto.0).await?;
+    Ok(HttpResponse::Ok().json(ResponseBody::new("Scan the code and confirm it to enable two-factor authentication", Some(enrollment))))
+}
+
+pub async fn confirm_totp(
+    service: web::Data<MfaService<PgUserRepository, PgMfaRepository>>,
+    auth_user: AuthUser,
+    dto: web::Json<MfaCodeDTO>,
+) -> Result<HttpResponse, ServiceError> {
+    let recovery_codes = service.confirm_totp(&auth_user.uid, &dto.code).await?;
+    Ok(HttpResponse::Ok().json(ResponseBody::new("Two-factor authentication enabled, store the recovery codes safely", Some(recovery_codes))))
+}
+
+pub async fn disable_totp(
+    service: web::Data<AuthService<PgUserRepository, PgTokenRepository>>,
+    auth_user: AuthUser,
+    dto: web::Json<ReauthDTO>,
+) -> Result<HttpResponse, ServiceError> {
+    service.disable_totp(&auth_user.uid, dto.0).await?;
+    Ok(HttpResponse::Ok().json(ResponseBody::new("Two-factor authentication disabled", None::<()>)))
+}
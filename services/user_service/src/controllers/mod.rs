@@ -1 +1,6 @@
-pub mod user_controller;
\ No newline at end of file
+pub mod user_controller;
+pub mod auth_controller;
+pub mod admin_controller;
+pub mod mfa_controller;
+pub mod api_key_controller;
+pub mod passkey_controller;
\ No newline at end of file
@@ -0,0 +1,84 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use shared::middleware::auth_user::AuthUser;
+use crate::models::passkey::{FinishPasskeyRegistrationDTO, PasskeyLoginDTO, PasskeyMfaOptionsDTO, PasskeyMfaDTO};
+use crate::models::mfa::ReauthDTO;
+use crate::models::response::ResponseBody;
+use crate::services::passkey_service::PasskeyService;
+use crate::repositories::user_repository::PgUserRepository;
+use crate::repositories::passkey_repository::PgPasskeyRepository;
+use crate::controllers::auth_controller::client_info;
+use crate::config::config::ClientIpConfig;
+use crate::errors::service_error::ServiceError;
+
+pub async fn start_registration(
+    service: web::Data<PasskeyService<PgUserRepository, PgPasskeyRepository>>,
+    auth_user: AuthUser,
+    dto: web::Json<ReauthDTO>,
+) -> Result<HttpResponse, ServiceError> {
+    let options = service.start_registration(&auth_user.uid, dto.0).await?;
+    Ok(HttpResponse::Ok().json(ResponseBody::new("Pass these options to navigator.credentials.create()", Some(options))))
+}
+
+pub async fn finish_registration(
+    service: web::Data<PasskeyService<PgUserRepository, PgPasskeyRepository>>,
+    auth_user: AuthUser,
+    dto: web::Json<FinishPasskeyRegistrationDTO>,
+) -> Result<HttpResponse, ServiceError> {
+    let passkey = service.finish_registration(&auth_user.uid, dto.0).await?;
+    Ok(HttpResponse::Created().json(ResponseBody::new("Passkey registered successfully", Some(passkey))))
+}
+
+pub async fn get_passkeys(
+    service: web::Data<PasskeyService<PgUserRepository, PgPasskeyRepository>>,
+    auth_user: AuthUser,
+) -> Result<HttpResponse, ServiceError> {
+    let passkeys = service.list(&auth_user.uid).await?;
+    Ok(HttpResponse::Ok().json(ResponseBody::new("Passkeys retrieved successfully", Some(passkeys))))
+}
+
+pub async fn delete_passkey(
+    service: web::Data<PasskeyService<PgUserRepository, PgPasskeyRepository>>,
+    auth_user: AuthUser,
+    passkey_uid: web::Path<String>,
+    dto: web::Json<ReauthDTO>,
+) -> Result<HttpResponse, ServiceError> {
+    service.delete(&auth_user.uid, &passkey_uid.into_inner(), dto.0).await?;
+    Ok(HttpResponse::Ok().json(ResponseBody::new("Passkey removed successfully", None::<()>)))
+}
+
+pub async fn start_login(
+    service: web::Data<PasskeyService<PgUserRepository, PgPasskeyRepository>>,
+) -> Result<HttpResponse, ServiceError> {
+    let options = service.start_login().await?;
+    Ok(HttpResponse::Ok().json(ResponseBody::new("Pass these options to navigator.credentials.get()", Some(options))))
+}
+
+pub async fn finish_login(
+    req: HttpRequest,
+    service: web::Data<PasskeyService<PgUserRepository, PgPasskeyRepository>>,
+    ip_config: web::Data<ClientIpConfig>,
+    dto: web::Json<PasskeyLoginDTO>,
+) -> Result<HttpResponse, ServiceError> {
+    let client = client_info(&req, &ip_config, dto.device_name.clone());
+    let response = service.finish_login(dto.0, &client).await?;
+    Ok(HttpResponse::Ok().json(ResponseBody::new("User logged in successfully", Some(response))))
+}
+
+pub async fn start_mfa(
+    service: web::Data<PasskeyService<PgUserRepository, PgPasskeyRepository>>,
+    dto: web::Json<PasskeyMfaOptionsDTO>,
+) -> Result<HttpResponse, ServiceError> {
+    let options = service.start_mfa(&dto.challenge_token).await?;
+    Ok(HttpResponse::Ok().json(ResponseBody::new("Pass these options to navigator.credentials.get()", Some(options))))
+}
+
+pub async fn finish_mfa(
+    req: HttpRequest,
+    service: web::Data<PasskeyService<PgUserRepository, PgPasskeyRepository>>,
+    ip_config: web::Data<ClientIpConfig>,
+    dto: web::Json<PasskeyMfaDTO>,
+) -> Result<HttpResponse, ServiceError> {
+    let client = client_info(&req, &ip_config, dto.device_name.clone());
+    let response = service.finish_mfa(dto.0, &client).await?;
+    Ok(HttpResponse::Ok().json(ResponseBody::new("User logged in successfully", Some(response))))
+}
@@ -1,14 +1,20 @@
 use actix_web::{web, HttpResponse};
-use crate::models::user::{LoginDTO, UserDTO};
+use shared::middleware::auth_user::AuthUser;
+use crate::models::user::{UserDTO, ChangePasswordDTO, ChangeEmailDTO};
+use crate::models::profile::UpdateProfileDTO;
+use crate::models::directory::UserSearchQuery;
 use crate::models::response::ResponseBody;
 use crate::services::user_service::UserService;
+use crate::services::auth_service::AuthService;
 use crate::repositories::user_repository::PgUserRepository;
+use crate::repositories::token_repository::PgTokenRepository;
 use crate::errors::service_error::ServiceError;
 
 pub async fn get_users(
     service: web::Data<UserService<PgUserRepository>>,
+    query: web::Query<UserSearchQuery>,
 ) -> Result<HttpResponse, ServiceError> {
-    let users = service.get_all().await?;
+    let users = service.get_all(query.into_inner()).await?;
     Ok(HttpResponse::Ok().json(ResponseBody::new("Users retrieved successfully", Some(users))))
 }
 
@@ -20,18 +26,59 @@ pub async fn get_user_by_id(
     Ok(HttpResponse::Ok().json(ResponseBody::new("User retrieved successfully", Some(user))))
 }
 
+pub async fn update_profile(
+    service: web::Data<UserService<PgUserRepository>>,
+    auth_user: AuthUser,
+    dto: web::Json<UpdateProfileDTO>,
+) -> Result<HttpResponse, ServiceError> {
+    let user = service.update_profile(&auth_user.uid, dto.0).await?;
+    Ok(HttpResponse::Ok().json(ResponseBody::new("Profile updated successfully", Some(user))))
+}
+
 pub async fn signup(
     service: web::Data<UserService<PgUserRepository>>, 
+    auth_service: web::Data<AuthService<PgUserRepository, PgTokenRepository>>,
     user_dto: web::Json<UserDTO>,
 ) -> Result<HttpResponse, ServiceError> {
     let user = service.signup(user_dto.0).await?;
+    // The account exists at this point; a failed email can be retried through /verify/resend.
+    if let Err(e) = auth_service.send_email_verification(&user).await {
+        log::error!("Failed to send verification email to {}: {}", user.email, e);
+    }
     Ok(HttpResponse::Ok().json(ResponseBody::new("User created successfully", Some(user))))
 }
 
-pub async fn login(
-    service: web::Data<UserService<PgUserRepository>>,
-    login_dto: web::Json<LoginDTO>,
+pub async fn get_sessions(
+    auth_service: web::Data<AuthService<PgUserRepository, PgTokenRepository>>,
+    auth_user: AuthUser,
+) -> Result<HttpResponse, ServiceError> {
+    let sessions = auth_service.get_sessions(&auth_user.token).await?;
+    Ok(HttpResponse::Ok().json(ResponseBody::new("Sessions retrieved successfully", Some(sessions))))
+}
+
+pub async fn revoke_session(
+    auth_service: web::Data<AuthService<PgUserRepository, PgTokenRepository>>,
+    auth_user: AuthUser,
+    session_uid: web::Path<String>,
 ) -> Result<HttpResponse, ServiceError> {
-    let response = service.login(login_dto.0).await?;
-    Ok(HttpResponse::Ok().json(ResponseBody::new("User logged in successfully", Some(response))))
-}
\ No newline at end of file
+    auth_service.revoke_session(&auth_user.uid, &session_uid.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(ResponseBody::new("Session revoked successfully", None::<()>)))
+}
+
+pub async fn change_password(
+    auth_service: web::Data<AuthService<PgUserRepository, PgTokenRepository>>,
+    auth_user: AuthUser,
+    dto: web::Json<ChangePasswordDTO>,
+) -> Result<HttpResponse, ServiceError> {
+    auth_service.change_password(&auth_user.uid, dto.0).await?;
+    Ok(HttpResponse::Ok().json(ResponseBody::new("Password changed successfully, please log in again", None::<()>)))
+}
+
+pub async fn change_email(
+    auth_service: web::Data<AuthService<PgUserRepository, PgTokenRepository>>,
+    auth_user: AuthUser,
+    dto: web::Json<ChangeEmailDTO>,
+) -> Result<HttpResponse, ServiceError> {
+    auth_service.change_email(&auth_user.uid, dto.0).await?;
+    Ok(HttpResponse::Accepted().json(ResponseBody::new("Confirmation email sent to the new address", None::<()>)))
+}
@@ -1,18 +1,35 @@
+use std::collections::BTreeMap;
 use actix_web::{HttpResponse, ResponseError};
+use actix_web::http::{header, StatusCode};
 use derive_more::Display;
 use serde::Serialize;
+use validator::ValidationErrors;
+use shared::errors::repository_error::RepositoryError;
+use log::{error, warn};
 
 #[derive(Debug, Display, Serialize)]
 #[display(fmt = "{}", message)]
 pub struct ServiceError {
     pub message: String,
     pub status_code: u16,
+    #[serde(skip)]
+    pub retry_after: Option<u64>,
+    // Messages per request field, for input a client can point the user at.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errors: Option<BTreeMap<String, Vec<String>>>,
 }
 
 impl ResponseError for ServiceError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.status_code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
     fn error_response(&self) -> HttpResponse {
-        HttpResponse::build(self.status_code().into())
-            .json(self)
+        let mut response = HttpResponse::build(self.status_code());
+        if let Some(retry_after) = self.retry_after {
+            response.insert_header((header::RETRY_AFTER, retry_after.to_string()));
+        }
+        response.json(self)
     }
 }
 
@@ -21,6 +38,8 @@ impl ServiceError {
         Self {
             message: message.to_string(),
             status_code,
+            retry_after: None,
+            errors: None,
         }
     }
     
@@ -28,11 +47,129 @@ impl ServiceError {
         Self::new(message, 400)
     }
     
+    pub fn invalid_fields(errors: BTreeMap<String, Vec<String>>) -> Self {
+        let message = errors
+            .iter()
+            .flat_map(|(field, messages)| messages.iter().map(move |message| format!("{}: {}", field, message)))
+            .collect::<Vec<_>>()
+            .join("; ");
+        Self {
+            errors: Some(errors),
+            ..Self::new(&message, 400)
+        }
+    }
+    
+    pub fn invalid_input(errors: &ValidationErrors) -> Self {
+        Self::invalid_fields(field_errors(errors))
+    }
+    
+    pub fn unauthorized(message: &str) -> Self {
+        Self::new(message, 401)
+    }
+    
+    pub fn forbidden(message: &str) -> Self {
+        Self::new(message, 403)
+    }
+    
     pub fn not_found(message: &str) -> Self {
         Self::new(message, 404)
     }
     
+    pub fn conflict(message: &str) -> Self {
+        Self::new(message, 409)
+    }
+    
+    pub fn too_many_requests(message: &str, retry_after_seconds: u64) -> Self {
+        Self {
+            retry_after: Some(retry_after_seconds),
+            ..Self::new(message, 429)
+        }
+    }
+    
     pub fn internal_error(message: &str) -> Self {
         Self::new(message, 500)
     }
-}
\ No newline at end of file
+    
+    pub fn unavailable(message: &str, retry_after_seconds: u64) -> Self {
+        Self {
+            retry_after: Some(retry_after_seconds),
+            ..Self::new(message, 503)
+        }
+    }
+}
+
+// Services override these messages where they know better, e.g. which passkey is a duplicate.
+impl From<RepositoryError> for ServiceError {
+    fn from(e: RepositoryError) -> Self {
+        match e {
+            RepositoryError::NotFound(message) => Self::not_found(&message),
+            RepositoryError::Conflict { field: Some(field), .. } => {
+                let message = format!("{} already exists", capitalize(&field.replace('_', " ")));
+                Self {
+                    errors: Some(BTreeMap::from([(field, vec![message.clone()])])),
+                    ..Self::conflict(&message)
+                }
+            }
+            RepositoryError::Conflict { field: None, .. } => Self::conflict("Resource already exists"),
+            RepositoryError::ForeignKey { .. } => Self::conflict("A related resource does not exist or is still in use"),
+            RepositoryError::Transient(message) => {
+                warn!("Transient database error: {}", message);
+                Self::unavailable("The database is temporarily unavailable, try again", 1)
+            }
+            RepositoryError::Other(message) => {
+                error!("Database error: {}", message);
+                Self::internal_error("Database error")
+            }
+        }
+    }
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    chars.next().map(|first| first.to_uppercase().chain(chars).collect()).unwrap_or_default()
+}
+
+pub fn field_errors(errors: &ValidationErrors) -> BTreeMap<String, Vec<String>> {
+    errors.field_errors()
+        .into_iter()
+        .map(|(field, errors)| {
+            let messages = errors.iter()
+                .map(|error| error.message.as_ref().map(|message| message.to_string()).unwrap_or(error.code.to_string()))
+                .collect();
+            (field.to_string(), messages)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repository_errors_map_to_statuses() {
+        let error = ServiceError::from(RepositoryError::not_found("User with uid 1 not found"));
+        assert_eq!((error.status_code, error.message.as_str()), (404, "User with uid 1 not found"));
+
+        let error = ServiceError::from(RepositoryError::Transient("connection reset".into()));
+        assert_eq!((error.status_code, error.retry_after), (503, Some(1)));
+        let response = error.error_response();
+        assert_eq!(response.headers().get(header::RETRY_AFTER).unwrap(), "1");
+
+        let error = ServiceError::from(RepositoryError::Other("syntax error".into()));
+        assert_eq!((error.status_code, error.message.as_str()), (500, "Database error"));
+    }
+
+    #[test]
+    fn conflicts_name_the_duplicate_field() {
+        let conflict = RepositoryError::Conflict { constraint: Some("users_username_key".into()), field: Some("username".into()) };
+        let error = ServiceError::from(conflict);
+        assert_eq!((error.status_code, error.message.as_str()), (409, "Username already exists"));
+        assert_eq!(error.errors.unwrap()["username"], vec!["Username already exists"]);
+
+        let conflict = RepositoryError::Conflict { constraint: Some("passkeys_credential_id_key".into()), field: Some("credential_id".into()) };
+        assert_eq!(ServiceError::from(conflict).message, "Credential id already exists");
+
+        let conflict = RepositoryError::Conflict { constraint: None, field: None };
+        assert_eq!(ServiceError::from(conflict).message, "Resource already exists");
+    }
+}
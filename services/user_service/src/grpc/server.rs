@@ -1,19 +1,33 @@
 use std::sync::Arc;
 use tonic::{transport::Server, Request, Response, Status};
 use shared::user_service_grpc::user_service_grpc_server::{UserServiceGrpc, UserServiceGrpcServer};
-use shared::user_service_grpc::{UserRequest, UserResponse};
+use shared::grpc::service_auth::RequireServiceKey;
+use shared::grpc::tls::GrpcTlsConfig;
+use shared::user_service_grpc::{UserRequest, UserResponse, TokenRevocationRequest, TokenRevocationResponse, ApiKeyRequest, ApiKeyResponse};
 use crate::services::user_service::UserService;
+use crate::services::auth_service::AuthService;
+use crate::services::api_key_service::ApiKeyService;
 use crate::repositories::user_repository::PgUserRepository;
+use crate::repositories::token_repository::PgTokenRepository;
+use crate::repositories::api_key_repository::PgApiKeyRepository;
 use log::{info, error};
 
 pub struct UserGrpcService {
     user_service: Arc<UserService<PgUserRepository>>,
+    auth_service: Arc<AuthService<PgUserRepository, PgTokenRepository>>,
+    api_key_service: Arc<ApiKeyService<PgUserRepository, PgApiKeyRepository>>,
 }
 
 impl UserGrpcService {
-    pub fn new(user_service: Arc<UserService<PgUserRepository>>) -> Self {
+    pub fn new(
+        user_service: Arc<UserService<PgUserRepository>>,
+        auth_service: Arc<AuthService<PgUserRepository, PgTokenRepository>>,
+        api_key_service: Arc<ApiKeyService<PgUserRepository, PgApiKeyRepository>>,
+    ) -> Self {
         Self {
-            user_service: user_service,
+            user_service,
+            auth_service,
+            api_key_service,
         }
     }
 }
@@ -30,24 +44,81 @@ impl UserServiceGrpc for UserGrpcService {
                 Status::not_found(format!("User not found: {}", e))
             })?;
 
+        let (status_text, status_expires_at) = user.profile.current_status()
+            .map(|(text, expires_at)| (text.to_string(), expires_at.map_or(0, |expires_at| expires_at.timestamp())))
+            .unwrap_or_default();
+        let profile = user.profile;
         let response = UserResponse {
             uid: user.uid.to_string(),
             username: user.username,
             email: user.email,
             created_at: user.created_at.timestamp(),
             updated_at: user.updated_at.timestamp(),
+            display_name: profile.display_name.unwrap_or_default(),
+            avatar_url: profile.avatar_url.unwrap_or_default(),
+            bio: profile.bio.unwrap_or_default(),
+            status_text,
+            status_expires_at,
+            locale: profile.locale.unwrap_or_default(),
+            time_zone: profile.time_zone.unwrap_or_default(),
         };
 
         Ok(Response::new(response))
     }
+
+    async fn is_token_revoked(&self, request: Request<TokenRevocationRequest>) -> Result<Response<TokenRevocationResponse>, Status> {
+        let request = request.into_inner();
+
+        let revoked = self.auth_service.is_token_revoked(&request.jti, &request.sub, request.iat, &request.sid).await
+            .map_err(|e| {
+                error!("gRPC error: {}", e);
+                Status::internal(format!("Revocation check failed: {}", e))
+            })?;
+
+        Ok(Response::new(TokenRevocationResponse { revoked }))
+    }
+
+    async fn verify_api_key(&self, request: Request<ApiKeyRequest>) -> Result<Response<ApiKeyResponse>, Status> {
+        let key = request.into_inner().key;
+
+        let user_token = self.api_key_service.verify(&key).await
+            .map_err(|e| {
+                error!("gRPC error: {}", e);
+                Status::internal(format!("API key check failed: {}", e))
+            })?;
+
+        let response = match user_token {
+            Some(user_token) => ApiKeyResponse {
+                valid: true,
+                uid: user_token.jti,
+                user_uid: user_token.sub,
+                scopes: user_token.scopes,
+                expires_at: if user_token.exp == i64::MAX { 0 } else { user_token.exp },
+            },
+            None => ApiKeyResponse::default(),
+        };
+        Ok(Response::new(response))
+    }
 }
 
-pub async fn start_grpc_server(addr: std::net::SocketAddr, user_service: Arc<UserService<PgUserRepository>>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    info!("Starting gRPC server on {}", addr);
-    let user_service = UserGrpcService::new(user_service);
+pub async fn start_grpc_server(
+    addr: std::net::SocketAddr,
+    user_service: Arc<UserService<PgUserRepository>>,
+    auth_service: Arc<AuthService<PgUserRepository, PgTokenRepository>>,
+    api_key_service: Arc<ApiKeyService<PgUserRepository, PgApiKeyRepository>>,
+    service_keys: Vec<String>,
+    tls: GrpcTlsConfig,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    info!("Starting gRPC server on {} (tls: {}, mutual tls: {})", addr, tls.is_enabled(), tls.ca_path.is_some());
+    let user_service = UserGrpcService::new(user_service, auth_service, api_key_service);
+
+    let mut server = Server::builder();
+    if tls.is_enabled() {
+        server = server.tls_config(tls.server_config()?)?;
+    }
 
-    Server::builder()
-        .add_service(UserServiceGrpcServer::new(user_service))
+    server
+        .add_service(UserServiceGrpcServer::with_interceptor(user_service, RequireServiceKey::new(&service_keys)))
         .serve(addr)
         .await?;
 
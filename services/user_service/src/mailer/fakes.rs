@@ -0,0 +1,31 @@
+use std::sync::Mutex;
+use crate::errors::service_error::ServiceError;
+use crate::mailer::{Email, Mailer};
+
+// Keeps every email it is asked to send; a failing one errors like an unreachable SMTP server.
+#[derive(Default)]
+pub struct RecordingMailer {
+    pub sent: Mutex<Vec<Email>>,
+    pub failing: bool,
+}
+
+impl RecordingMailer {
+    pub fn failing() -> Self {
+        Self { failing: true, ..Self::default() }
+    }
+
+    pub fn sent(&self) -> Vec<Email> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl Mailer for RecordingMailer {
+    async fn send(&self, email: Email) -> Result<(), ServiceError> {
+        if self.failing {
+            return Err(ServiceError::internal_error("Failed to send email"));
+        }
+        self.sent.lock().unwrap().push(email);
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
+use crate::mailer::{Email, Mailer};
+use crate::errors::service_error::ServiceError;
+use log::info;
+
+// Development mailer: logs who every email goes to and, when an outbox path is set, appends it to
+// that file as one JSON object per line so that tests and local tooling can pick up the links.
+// Bodies carry sign-in links, so they only ever go to the outbox and never to the log.
+pub struct LogMailer {
+    outbox: Option<PathBuf>,
+}
+
+impl LogMailer {
+    pub fn new(outbox: Option<PathBuf>) -> Self {
+        Self { outbox }
+    }
+}
+
+#[async_trait::async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, email: Email) -> Result<(), ServiceError> {
+        info!("Email to {}: {}", email.to, email.subject);
+
+        if let Some(outbox) = &self.outbox {
+            let mut line = serde_json::to_string(&email)
+                .map_err(|e| ServiceError::internal_error(&format!("Failed to serialize email: {}", e)))?;
+            line.push('\n');
+
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(outbox)
+                .await
+                .map_err(|e| ServiceError::internal_error(&format!("Failed to open mail outbox: {}", e)))?;
+            file.write_all(line.as_bytes())
+                .await
+                .map_err(|e| ServiceError::internal_error(&format!("Failed to write mail outbox: {}", e)))?;
+            file.flush()
+                .await
+                .map_err(|e| ServiceError::internal_error(&format!("Failed to write mail outbox: {}", e)))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn appends_each_email_to_the_outbox() {
+        let outbox = std::env::temp_dir().join(format!("outbox-{}.jsonl", uuid::Uuid::new_v4()));
+        let mailer = LogMailer::new(Some(outbox.clone()));
+        for to in ["a@example.com", "b@example.com"] {
+            mailer.send(Email { to: to.to_string(), subject: "Hi".to_string(), body: "Body".to_string() })
+                .await
+                .unwrap();
+        }
+
+        let contents = std::fs::read_to_string(&outbox).unwrap();
+        std::fs::remove_file(&outbox).unwrap();
+        let recipients: Vec<String> = contents
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["to"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(recipients, ["a@example.com", "b@example.com"]);
+    }
+}
@@ -0,0 +1,56 @@
+pub mod smtp;
+pub mod log_mailer;
+#[cfg(test)]
+pub mod fakes;
+
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use serde::Serialize;
+use crate::errors::service_error::ServiceError;
+use crate::mailer::log_mailer::LogMailer;
+use crate::mailer::smtp::SmtpMailer;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[async_trait::async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: Email) -> Result<(), ServiceError>;
+}
+
+#[derive(Clone)]
+pub enum MailerConfig {
+    Log { outbox: Option<PathBuf> },
+    Smtp { host: String, port: u16, credentials: Option<(String, String)>, from: String },
+}
+
+impl MailerConfig {
+    pub fn build(&self) -> Result<Arc<dyn Mailer>, String> {
+        Ok(match self {
+            MailerConfig::Log { outbox } => Arc::new(LogMailer::new(outbox.clone())),
+            MailerConfig::Smtp { host, port, credentials, from } => {
+                Arc::new(SmtpMailer::new(host, *port, credentials.clone(), from)?)
+            }
+        })
+    }
+}
+
+impl fmt::Debug for MailerConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MailerConfig::Log { outbox } => f.debug_struct("Log").field("outbox", outbox).finish(),
+            MailerConfig::Smtp { host, port, credentials, from } => f
+                .debug_struct("Smtp")
+                .field("host", host)
+                .field("port", port)
+                .field("username", &credentials.as_ref().map(|(username, _)| username))
+                .field("from", from)
+                .finish(),
+        }
+    }
+}
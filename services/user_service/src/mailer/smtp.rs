@@ -0,0 +1,48 @@
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use crate::mailer::{Email, Mailer};
+use crate::errors::service_error::ServiceError;
+use log::{info, error};
+
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn new(host: &str, port: u16, credentials: Option<(String, String)>, from: &str) -> Result<Self, String> {
+        let from = from.parse::<Mailbox>()
+            .map_err(|e| format!("Invalid sender address {}: {}", from, e))?;
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+            .map_err(|e| format!("Invalid SMTP host {}: {}", host, e))?
+            .port(port);
+        if let Some((username, password)) = credentials {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(Self { transport: builder.build(), from })
+    }
+}
+
+#[async_trait::async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: Email) -> Result<(), ServiceError> {
+        let to = email.to.parse::<Mailbox>()
+            .map_err(|_| ServiceError::bad_request("Invalid recipient address"))?;
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(&email.subject)
+            .body(email.body)
+            .map_err(|e| ServiceError::internal_error(&format!("Failed to build email: {}", e)))?;
+
+        self.transport.send(message).await.map_err(|e| {
+            error!("Failed to send email to {}: {}", email.to, e);
+            ServiceError::internal_error("Failed to send email")
+        })?;
+
+        info!("Sent \"{}\" email to {}", email.subject, email.to);
+        Ok(())
+    }
+}
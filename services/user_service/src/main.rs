@@ -5,19 +5,28 @@ mod models;
 mod repositories;
 mod services;
 mod grpc;
+mod mailer;
+mod oidc;
+mod webauthn;
 
 use actix_web::middleware::Logger;
 use actix_web::{web ,App, HttpServer};
-use config::config::Config;
+use config::config::{ClientIpConfig, Config};
 use config::app::config_services;
 use config::db::init_db_pool;
 use dotenvy::dotenv;
-use env_logger;
-use log::{info, error};
+use log::{info, error, warn};
 use std::sync::Arc;
 use shared::middleware::auth::Authentication;
 use crate::grpc::server::start_grpc_server;
 use services::user_service::UserService;
+use services::auth_service::{spawn_token_purge, AuthService, AuthSettings};
+use services::mfa_service::MfaService;
+use services::oidc_service::OidcService;
+use services::api_key_service::ApiKeyService;
+use services::passkey_service::PasskeyService;
+use services::password_hasher::Argon2Hasher;
+use services::password_policy::PasswordPolicy;
 use actix_cors::Cors;
 
 
@@ -37,12 +46,76 @@ async fn main() -> std::io::Result<()> {
 
     let pool = init_db_pool(&config.database_url).await.map_err(|e| {
         error!("Failed to create DB pool: {}", e);
-        std::io::Error::new(std::io::ErrorKind::Other, e)
+        std::io::Error::other(e)
     })?;
     
-    let service = Arc::new(UserService::new(pool.clone(), config.jwt_secret.clone()));
+    let password_hasher = Arc::new(Argon2Hasher::new(&config.argon2).map_err(|e| {
+        error!("Invalid password hashing config: {}", e);
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, e)
+    })?);
+    let password_policy = Arc::new(PasswordPolicy::new(&config.password_policy).map_err(|e| {
+        error!("Invalid password policy config: {}", e);
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, e)
+    })?);
+    if config.password_policy.breached_list_path.is_none() {
+        warn!("PASSWORD_BREACHED_LIST_PATH is not set, only the bundled list of the most common breached passwords is checked, set PASSWORD_REQUIRE_BREACHED_LIST=true in production");
+    }
+    let service = Arc::new(UserService::new(pool.clone(), password_hasher.clone(), password_policy.clone()));
+    let mailer = config.mailer.build().map_err(|e| {
+        error!("Failed to create mailer: {}", e);
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, e)
+    })?;
+    let mfa_service = Arc::new(MfaService::new(pool.clone(), config.mfa_issuer.clone()));
+    let auth_service = Arc::new(AuthService::new(
+        pool.clone(),
+        config.jwt_signing_key.clone(),
+        mailer,
+        mfa_service.clone(),
+        password_hasher,
+        password_policy,
+        AuthSettings {
+            access_token_ttl: config.access_token_ttl,
+            refresh_token_ttl: config.refresh_token_ttl,
+            email_verification_ttl: config.email_verification_ttl,
+            email_resend_cooldown: config.email_resend_cooldown,
+            require_email_verification: config.require_email_verification,
+            password_reset_ttl: config.password_reset_ttl,
+            password_reset_url: config.password_reset_url.clone(),
+            public_base_url: config.public_base_url.clone(),
+            login_throttle: config.login_throttle.clone(),
+            mfa_challenge_ttl: config.mfa_challenge_ttl,
+            mfa_max_attempts: config.mfa_max_attempts,
+        },
+    ));
+    spawn_token_purge(auth_service.clone(), config.token_purge_interval);
+
+    let api_key_service = Arc::new(ApiKeyService::new(pool.clone()));
+    let passkey_service = Arc::new(PasskeyService::new(
+        pool.clone(),
+        auth_service.clone(),
+        config.webauthn.clone(),
+        config.webauthn_challenge_ttl,
+    ));
+    let oidc_service = Arc::new(OidcService::new(
+        pool.clone(),
+        auth_service.clone(),
+        &config.oidc_providers,
+        &config.public_base_url,
+        config.oidc_login_ttl,
+    ).map_err(|e| {
+        error!("Invalid OIDC config: {}", e);
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, e)
+    })?);
 
-    let grpc_task = tokio::spawn(start_grpc_server(config.grpc_addr.clone(), service.clone()));
+    let grpc_task = tokio::spawn(start_grpc_server(
+        config.grpc_addr,
+        service.clone(),
+        auth_service.clone(),
+        api_key_service.clone(),
+        config.grpc_service_keys.clone(),
+        config.grpc_tls.clone(),
+    ));
+    let ip_config = web::Data::new(ClientIpConfig { trust_proxy_headers: config.trust_proxy_headers });
     let http_server = HttpServer::new({
         move || {
             let cors = Cors::default()
@@ -54,9 +127,19 @@ async fn main() -> std::io::Result<()> {
             App::new()
                 .wrap(cors)
                 .wrap(Logger::default())
-                .wrap(Authentication::new(config.jwt_secret.clone(), Some(["/api/auth/signup", "/api/auth/login"].into())))
+                .wrap(
+                    Authentication::new(config.jwt_keys.clone(), auth_service.clone(), config.auth_rules.clone())
+                        .with_api_keys(api_key_service.clone())
+                )
                 .configure(config_services)
                 .app_data(web::Data::from(service.clone()))
+                .app_data(web::Data::from(auth_service.clone()))
+                .app_data(web::Data::from(mfa_service.clone()))
+                .app_data(web::Data::from(oidc_service.clone()))
+                .app_data(web::Data::from(api_key_service.clone()))
+                .app_data(web::Data::from(passkey_service.clone()))
+                .app_data(web::Data::from(config.jwt_keys.clone()))
+                .app_data(ip_config.clone())
                 
         }
     })
@@ -68,8 +151,8 @@ async fn main() -> std::io::Result<()> {
 
     tokio::select! {
         res = grpc_task => {
-            let result = res.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?; 
-            result.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?; 
+            let result = res.map_err(std::io::Error::other)?; 
+            result.map_err(std::io::Error::other)?; 
         },
         res = http_server => res?,
     };
@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use validator::Validate;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use crate::models::profile::Profile;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 20;
+pub const MAX_PAGE_SIZE: i64 = 100;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum UserSort {
+    Username,
+    Newest,
+    Relevance,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct UserSearchQuery {
+    #[validate(length(max = 50, message = "Search must be at most 50 characters"))]
+    pub q: Option<String>,
+    pub sort: Option<UserSort>,
+    pub cursor: Option<String>,
+    #[validate(range(min = 1, message = "Limit must be at least 1"))]
+    pub limit: Option<i64>,
+}
+
+// What other users get to see: the public profile, never the email.
+#[derive(Serialize, FromRow)]
+pub struct DirectoryUser {
+    pub uid: Uuid,
+    pub username: String,
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub profile: Profile,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing)]
+    pub rank: f32,
+}
+
+#[derive(Serialize)]
+pub struct UserPage {
+    pub users: Vec<DirectoryUser>,
+    pub next_cursor: Option<String>,
+}
+
+// The sort key of the last user on a page; the next page starts right after it.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "sort", rename_all = "snake_case")]
+pub enum UserCursor {
+    Username { username: String },
+    Newest { created_at: DateTime<Utc>, uid: Uuid },
+    Relevance { rank: f32, username: String },
+}
+
+impl UserCursor {
+    pub fn after(user: &DirectoryUser, sort: UserSort) -> Self {
+        match sort {
+            UserSort::Username => Self::Username { username: user.username.to_lowercase() },
+            UserSort::Newest => Self::Newest { created_at: user.created_at, uid: user.uid },
+            UserSort::Relevance => Self::Relevance { rank: user.rank, username: user.username.to_lowercase() },
+        }
+    }
+
+    pub fn sort(&self) -> UserSort {
+        match self {
+            Self::Username { .. } => UserSort::Username,
+            Self::Newest { .. } => UserSort::Newest,
+            Self::Relevance { .. } => UserSort::Relevance,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let json = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+}
+
+// The resolved search the repository runs; `limit` is already capped.
+#[derive(Debug)]
+pub struct UserSearch {
+    pub query: Option<String>,
+    pub sort: UserSort,
+    pub after: Option<UserCursor>,
+    pub limit: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursors_round_trip() {
+        let cursors = [
+            UserCursor::Username { username: "alice".into() },
+            UserCursor::Newest { created_at: Utc::now(), uid: Uuid::new_v4() },
+            UserCursor::Relevance { rank: 1.3333334, username: "bob".into() },
+        ];
+        for cursor in cursors {
+            assert_eq!(UserCursor::decode(&cursor.encode()), Some(cursor));
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_cursors() {
+        assert_eq!(UserCursor::decode("not a cursor"), None);
+        assert_eq!(UserCursor::decode(&URL_SAFE_NO_PAD.encode(r#"{"sort":"oldest"}"#)), None);
+    }
+}
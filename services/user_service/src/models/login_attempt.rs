@@ -0,0 +1,34 @@
+use chrono::{DateTime, Duration, Utc};
+use sqlx::FromRow;
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, FromRow)]
+pub struct LoginAttempts {
+    pub key: String,
+    pub failures: i32,
+    pub last_failure_at: DateTime<Utc>,
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+// How failures on one key turn into a lockout. Failures older than `window` are forgotten; past
+// `max_attempts` the key is locked for `base_lockout`, doubling with every further failure up to
+// `max_lockout`.
+#[derive(Debug, Clone)]
+pub struct LockoutPolicy {
+    pub max_attempts: i32,
+    pub window: Duration,
+    pub base_lockout: Duration,
+    pub max_lockout: Duration,
+}
+
+impl LockoutPolicy {
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn lockout_for(&self, failures: i32) -> Option<Duration> {
+        let excess = failures - self.max_attempts;
+        if excess <= 0 {
+            return None;
+        }
+        let factor = 2i32.saturating_pow((excess - 1).min(30) as u32);
+        Some((self.base_lockout * factor).min(self.max_lockout))
+    }
+}
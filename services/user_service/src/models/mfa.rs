@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use crate::models::user::LoginResponse;
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, FromRow)]
+pub struct UserTotp {
+    pub user_uid: Uuid,
+    pub secret: String,
+    pub enabled_at: Option<DateTime<Utc>>,
+    pub last_used_step: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, FromRow)]
+pub struct MfaChallengeRecord {
+    pub uid: Uuid,
+    pub user_uid: Uuid,
+    pub token_hash: String,
+    pub failed_attempts: i32,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TotpEnrollment {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MfaCodeDTO {
+    pub code: String,
+}
+
+// Proof that the account holder is present for a security change, such as turning TOTP on or off
+// or adding a passkey: the current password, or a second factor code for accounts without one.
+#[derive(Serialize, Deserialize, Default)]
+pub struct ReauthDTO {
+    #[serde(default)]
+    pub current_password: Option<String>,
+    #[serde(default)]
+    pub code: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RecoveryCodes {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MfaChallenge {
+    pub mfa_required: bool,
+    pub challenge_token: String,
+    pub expires_at: DateTime<Utc>,
+    pub methods: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MfaVerifyDTO {
+    pub challenge_token: String,
+    pub code: String,
+    #[serde(default)]
+    pub device_name: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum LoginOutcome {
+    Authenticated(Box<LoginResponse>),
+    MfaRequired(MfaChallenge),
+}
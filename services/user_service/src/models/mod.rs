@@ -1,2 +1,12 @@
 pub mod user;
-pub mod response;
\ No newline at end of file
+pub mod profile;
+pub mod directory;
+pub mod response;
+pub mod token;
+pub mod role;
+pub mod login_attempt;
+pub mod mfa;
+pub mod session;
+pub mod identity;
+pub mod api_key;
+pub mod passkey;
\ No newline at end of file
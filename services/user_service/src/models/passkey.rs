@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use validator::Validate;
+use crate::webauthn::{AssertionCredential, RegistrationCredential};
+
+#[derive(Clone, Serialize, Deserialize, FromRow)]
+pub struct Passkey {
+    pub uid: Uuid,
+    #[serde(skip_serializing)]
+    pub user_uid: Uuid,
+    pub credential_id: String,
+    #[serde(skip_serializing)]
+    pub public_key: Vec<u8>,
+    pub algorithm: i32,
+    #[serde(skip_serializing)]
+    pub sign_count: i64,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Validate)]
+pub struct FinishPasskeyRegistrationDTO {
+    #[validate(length(min = 1, max = 100, message = "Name must be between 1 and 100 characters"))]
+    pub name: Option<String>,
+    pub credential: RegistrationCredential,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PasskeyLoginDTO {
+    pub credential: AssertionCredential,
+    #[serde(default)]
+    pub device_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PasskeyMfaOptionsDTO {
+    pub challenge_token: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PasskeyMfaDTO {
+    pub challenge_token: String,
+    pub credential: AssertionCredential,
+    #[serde(default)]
+    pub device_name: Option<String>,
+}
@@ -0,0 +1,175 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::ser::SerializeStruct;
+use sqlx::FromRow;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use validator::{Validate, ValidationError};
+
+#[derive(Clone, Deserialize, FromRow, Default)]
+pub struct Profile {
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub bio: Option<String>,
+    pub status_text: Option<String>,
+    pub status_expires_at: Option<DateTime<Utc>>,
+    pub locale: Option<String>,
+    pub time_zone: Option<String>,
+}
+
+impl Profile {
+    // A status past its expiry reads as unset; the stored value is simply overwritten next time.
+    pub fn current_status(&self) -> Option<(&str, Option<DateTime<Utc>>)> {
+        let status_text = self.status_text.as_deref()?;
+        match self.status_expires_at {
+            Some(expires_at) if expires_at <= Utc::now() => None,
+            expires_at => Some((status_text, expires_at)),
+        }
+    }
+}
+
+impl Serialize for Profile {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let status = self.current_status();
+        let mut profile = serializer.serialize_struct("Profile", 7)?;
+        profile.serialize_field("display_name", &self.display_name)?;
+        profile.serialize_field("avatar_url", &self.avatar_url)?;
+        profile.serialize_field("bio", &self.bio)?;
+        profile.serialize_field("status_text", &status.map(|(text, _)| text))?;
+        profile.serialize_field("status_expires_at", &status.and_then(|(_, expires_at)| expires_at))?;
+        profile.serialize_field("locale", &self.locale)?;
+        profile.serialize_field("time_zone", &self.time_zone)?;
+        profile.end()
+    }
+}
+
+// Fields left out are kept as they are, fields sent as null are cleared.
+#[derive(Deserialize, Validate, Default)]
+pub struct UpdateProfileDTO {
+    #[serde(default, deserialize_with = "nullable")]
+    #[validate(length(min = 1, max = 50, message = "Display name must be between 1 and 50 characters"))]
+    pub display_name: Option<Option<String>>,
+
+    #[serde(default, deserialize_with = "nullable")]
+    #[validate(
+        url(message = "Avatar URL must be a valid URL"),
+        length(max = 2048, message = "Avatar URL must be at most 2048 characters"),
+        custom(function = "validate_avatar_url", message = "Avatar URL must use https"),
+    )]
+    pub avatar_url: Option<Option<String>>,
+
+    #[serde(default, deserialize_with = "nullable")]
+    #[validate(length(max = 500, message = "Bio must be at most 500 characters"))]
+    pub bio: Option<Option<String>>,
+
+    #[serde(default, deserialize_with = "nullable")]
+    #[validate(length(min = 1, max = 100, message = "Status must be between 1 and 100 characters"))]
+    pub status_text: Option<Option<String>>,
+
+    #[serde(default, deserialize_with = "nullable")]
+    #[validate(custom(function = "validate_in_future", message = "Status expiry must be in the future"))]
+    pub status_expires_at: Option<Option<DateTime<Utc>>>,
+
+    #[serde(default, deserialize_with = "nullable")]
+    #[validate(custom(function = "validate_locale", message = "Locale must be a language tag such as en or pt-BR"))]
+    pub locale: Option<Option<String>>,
+
+    #[serde(default, deserialize_with = "nullable")]
+    #[validate(custom(function = "validate_time_zone", message = "Time zone must be an IANA name such as Europe/Berlin"))]
+    pub time_zone: Option<Option<String>>,
+}
+
+impl UpdateProfileDTO {
+    pub fn is_empty(&self) -> bool {
+        self.display_name.is_none()
+            && self.avatar_url.is_none()
+            && self.bio.is_none()
+            && self.status_text.is_none()
+            && self.status_expires_at.is_none()
+            && self.locale.is_none()
+            && self.time_zone.is_none()
+    }
+}
+
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+fn validate_avatar_url(url: &str) -> Result<(), ValidationError> {
+    if url.starts_with("https://") { Ok(()) } else { Err(ValidationError::new("avatar_url")) }
+}
+
+fn validate_in_future(expires_at: &DateTime<Utc>) -> Result<(), ValidationError> {
+    if *expires_at > Utc::now() { Ok(()) } else { Err(ValidationError::new("status_expires_at")) }
+}
+
+// A BCP 47 subset: language, then an optional script and region, e.g. `en`, `pt-BR`, `zh-Hant-TW`.
+fn validate_locale(locale: &str) -> Result<(), ValidationError> {
+    let mut parts = locale.split('-');
+    let language = parts.next().unwrap_or_default();
+    let mut valid = (2..=3).contains(&language.len()) && language.chars().all(|c| c.is_ascii_lowercase());
+    let mut rest: Vec<&str> = parts.collect();
+    if rest.first().is_some_and(|script| script.len() == 4 && script.chars().all(|c| c.is_ascii_alphabetic())) {
+        rest.remove(0);
+    }
+    match rest.as_slice() {
+        [] => {}
+        [region] => {
+            valid &= (region.len() == 2 && region.chars().all(|c| c.is_ascii_uppercase()))
+                || (region.len() == 3 && region.chars().all(|c| c.is_ascii_digit()));
+        }
+        _ => valid = false,
+    }
+    if valid { Ok(()) } else { Err(ValidationError::new("locale")) }
+}
+
+fn validate_time_zone(time_zone: &str) -> Result<(), ValidationError> {
+    time_zone.parse::<Tz>().map(|_| ()).map_err(|_| ValidationError::new("time_zone"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use serde_json::json;
+
+    #[test]
+    fn distinguishes_missing_fields_from_nulls() {
+        let dto: UpdateProfileDTO = serde_json::from_value(json!({ "bio": null, "locale": "pt-BR" })).unwrap();
+        assert!(dto.display_name.is_none());
+        assert_eq!(dto.bio, Some(None));
+        assert_eq!(dto.locale, Some(Some("pt-BR".to_string())));
+        assert!(dto.validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_fields() {
+        let dto: UpdateProfileDTO = serde_json::from_value(json!({
+            "display_name": "",
+            "avatar_url": "http://example.com/me.png",
+            "status_expires_at": Utc::now() - Duration::minutes(1),
+            "locale": "english",
+            "time_zone": "Mars/Olympus",
+        })).unwrap();
+        let errors = dto.validate().unwrap_err();
+        let mut fields: Vec<&str> = errors.field_errors().into_keys().collect();
+        fields.sort();
+        assert_eq!(fields, ["avatar_url", "display_name", "locale", "status_expires_at", "time_zone"]);
+    }
+
+    #[test]
+    fn hides_an_expired_status() {
+        let mut profile = Profile {
+            status_text: Some("In a meeting".into()),
+            status_expires_at: Some(Utc::now() + Duration::hours(1)),
+            ..Default::default()
+        };
+        assert_eq!(serde_json::to_value(&profile).unwrap()["status_text"], "In a meeting");
+        profile.status_expires_at = Some(Utc::now() - Duration::hours(1));
+        let value = serde_json::to_value(&profile).unwrap();
+        assert!(value["status_text"].is_null() && value["status_expires_at"].is_null());
+    }
+}
@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+pub const DEFAULT_ROLE: &str = "user";
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Role {
+    pub name: String,
+    pub scopes: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RoleDTO {
+    pub role: String,
+}
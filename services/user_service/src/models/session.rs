@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+#[allow(dead_code)]
+#[derive(Clone, Serialize, Deserialize, FromRow)]
+pub struct Session {
+    pub uid: Uuid,
+    #[serde(skip_serializing)]
+    pub user_uid: Uuid,
+    pub device_name: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    #[serde(skip_serializing)]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct SessionResponse {
+    #[serde(flatten)]
+    pub session: Session,
+    pub current: bool,
+}
+
+// Where a login comes from, as seen by the HTTP layer.
+pub struct ClientInfo {
+    pub ip_address: String,
+    pub user_agent: Option<String>,
+    pub device_name: Option<String>,
+}
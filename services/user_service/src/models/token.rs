@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use validator::Validate;
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, FromRow)]
+pub struct RefreshToken {
+    pub uid: Uuid,
+    pub user_uid: Uuid,
+    pub family_uid: Uuid,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RefreshTokenDTO {
+    pub refresh_token: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AuthTokens {
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+    pub refresh_token: String,
+    pub refresh_expires_at: DateTime<Utc>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, FromRow)]
+pub struct EmailVerificationToken {
+    pub uid: Uuid,
+    pub user_uid: Uuid,
+    pub email: String,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct VerifyEmailQuery {
+    pub token: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ResendVerificationDTO {
+    pub email: String,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, FromRow)]
+pub struct PasswordResetToken {
+    pub uid: Uuid,
+    pub user_uid: Uuid,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Validate)]
+pub struct ForgotPasswordDTO {
+    #[validate(email(message = "Invalid email format"))]
+    pub email: String,
+}
+
+#[derive(Serialize, Deserialize, Validate)]
+pub struct ResetPasswordDTO {
+    pub token: String,
+
+    pub new_password: String,
+}
@@ -2,15 +2,21 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
-use validator::Validate;
+use validator::{Validate, ValidationError};
+use crate::models::token::AuthTokens;
+use crate::models::profile::Profile;
 
-#[derive(Serialize, Deserialize, FromRow)]
+#[derive(Clone, Serialize, Deserialize, FromRow)]
 pub struct User {
     pub uid: Uuid,
     pub username: String,
     pub email: String,
     #[serde(skip_serializing)]
-    pub password_hash: String,
+    pub password_hash: Option<String>,
+    pub email_verified_at: Option<DateTime<Utc>>,
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub profile: Profile,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -18,28 +24,87 @@ pub struct User {
 
 #[derive(Serialize, Deserialize, Validate)]
 pub struct UserDTO {
-    #[validate(length(min = 3, max = 20, message = "Username must be between 3 and 20 characters"))]
+    #[validate(
+        length(min = 3, max = 20, message = "Username must be between 3 and 20 characters"),
+        custom(function = "validate_username", message = "Username must not contain @ or spaces"),
+    )]
     pub username: String,
 
     #[validate(email(message = "Invalid email format"))]
     pub email: String,
 
-    #[validate(length(min = 8, message = "Password must be at least 8 characters long"))]
     pub password: String,
 }
 
 #[derive(Serialize, Deserialize, Validate)]
 pub struct LoginDTO {
-    #[validate(email(message = "Invalid email format"))]
-    pub email: String,
+    // A username or an email address; `email` is still accepted from older clients.
+    #[serde(alias = "email")]
+    #[validate(length(min = 1, max = 255, message = "Username or email is required"))]
+    pub identifier: String,
 
-    #[validate(length(min = 8, message = "Password must be at least 8 characters long"))]
+    // Not held to the password policy, so accounts whose password predates it can still sign in.
     pub password: String,
+
+    #[serde(default)]
+    #[validate(length(max = 100, message = "Device name must be at most 100 characters"))]
+    pub device_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Validate)]
+pub struct ChangePasswordDTO {
+    pub current_password: String,
+
+    pub new_password: String,
+}
+
+#[derive(Serialize, Deserialize, Validate)]
+pub struct ChangeEmailDTO {
+    pub current_password: String,
+
+    #[validate(email(message = "Invalid email format"))]
+    pub new_email: String,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct LoginResponse {
     pub user: User,
-    pub token: String,
-    pub expires_at: DateTime<Utc>,
-}
\ No newline at end of file
+    #[serde(flatten)]
+    pub tokens: AuthTokens,
+}
+
+// Logins tell usernames and emails apart by the `@`, so usernames can't contain one.
+fn validate_username(username: &str) -> Result<(), ValidationError> {
+    if username.contains('@') || username.chars().any(char::is_whitespace) {
+        return Err(ValidationError::new("username"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn login_accepts_the_old_email_field() {
+        let dto: LoginDTO = serde_json::from_value(json!({ "email": "Bob@X.com", "password": "violet-Harbor-73" })).unwrap();
+        assert_eq!(dto.identifier, "Bob@X.com");
+        let dto: LoginDTO = serde_json::from_value(json!({ "identifier": "bob", "password": "violet-Harbor-73" })).unwrap();
+        assert_eq!(dto.identifier, "bob");
+    }
+
+    #[test]
+    fn login_accepts_passwords_shorter_than_the_policy() {
+        let dto: LoginDTO = serde_json::from_value(json!({ "identifier": "bob", "password": "hunter2" })).unwrap();
+        assert!(dto.validate().is_ok());
+    }
+
+    #[test]
+    fn usernames_cannot_look_like_emails() {
+        let user = |username: &str| UserDTO { username: username.into(), email: "bob@x.com".into(), password: String::new() };
+        assert!(user("bob_99").validate().is_ok());
+        assert!(user("bob@x").validate().is_err());
+        assert!(user("bob x").validate().is_err());
+    }
+}
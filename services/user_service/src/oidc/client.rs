@@ -0,0 +1,263 @@
+use std::sync::RwLock;
+use std::time::Duration;
+use jsonwebtoken::{decode, decode_header, Algorithm, Validation};
+use jsonwebtoken::jwk::JwkSet;
+use reqwest::Url;
+use serde::Deserialize;
+use shared::models::keys::KeySet;
+use crate::errors::service_error::ServiceError;
+use crate::oidc::OidcProviderConfig;
+use log::{info, warn, error};
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub nonce: Option<String>,
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: bool,
+    pub preferred_username: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+// One configured provider. Discovery metadata and signing keys are fetched on first use and the keys
+// are fetched again when a token is signed with a key we haven't seen, which covers key rotation.
+pub struct OidcClient {
+    config: OidcProviderConfig,
+    redirect_uri: String,
+    http: reqwest::Client,
+    metadata: RwLock<Option<ProviderMetadata>>,
+    keys: KeySet,
+}
+
+impl OidcClient {
+    pub fn new(config: OidcProviderConfig, redirect_uri: String) -> Result<Self, String> {
+        let http = reqwest::Client::builder()
+            .timeout(HTTP_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client for {}: {}", config.name, e))?;
+        Ok(Self {
+            config,
+            redirect_uri,
+            http,
+            metadata: RwLock::new(None),
+            keys: KeySet::default(),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    pub fn auto_link_verified_email(&self) -> bool {
+        self.config.auto_link_verified_email
+    }
+
+    pub async fn authorization_url(&self, state: &str, nonce: &str, code_challenge: &str) -> Result<String, ServiceError> {
+        let metadata = self.metadata().await?;
+        let url = Url::parse_with_params(&metadata.authorization_endpoint, &[
+            ("response_type", "code"),
+            ("client_id", self.config.client_id.as_str()),
+            ("redirect_uri", self.redirect_uri.as_str()),
+            ("scope", self.config.scopes.as_str()),
+            ("state", state),
+            ("nonce", nonce),
+            ("code_challenge", code_challenge),
+            ("code_challenge_method", "S256"),
+        ])
+        .map_err(|e| provider_error(&self.config.name, &format!("invalid authorization endpoint: {}", e)))?;
+        Ok(url.to_string())
+    }
+
+    pub async fn exchange_code(&self, code: &str, code_verifier: &str) -> Result<String, ServiceError> {
+        let metadata = self.metadata().await?;
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.redirect_uri.as_str()),
+            ("client_id", self.config.client_id.as_str()),
+            ("code_verifier", code_verifier),
+        ];
+        if let Some(client_secret) = &self.config.client_secret {
+            form.push(("client_secret", client_secret.as_str()));
+        }
+
+        let response = self.http.post(&metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| provider_error(&self.config.name, &format!("token request failed: {}", e)))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            warn!("OIDC provider {} rejected the authorization code: {} {}", self.config.name, status, body);
+            return Err(ServiceError::unauthorized("The identity provider rejected the authorization code"));
+        }
+        let tokens: TokenResponse = response.json()
+            .await
+            .map_err(|e| provider_error(&self.config.name, &format!("invalid token response: {}", e)))?;
+        Ok(tokens.id_token)
+    }
+
+    pub async fn validate_id_token(&self, id_token: &str, nonce: &str) -> Result<IdTokenClaims, ServiceError> {
+        let metadata = self.metadata().await?;
+        let header = decode_header(id_token).map_err(|_| invalid_token("malformed header"))?;
+        // Symmetric algorithms would let anyone holding the client secret mint tokens.
+        if matches!(header.alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) {
+            return Err(invalid_token("symmetric signatures are not accepted"));
+        }
+        let kid = header.kid.ok_or_else(|| invalid_token("missing kid"))?;
+        let decoding_key = match self.keys.decoding_key(&kid) {
+            Some(key) => key,
+            None => {
+                self.refresh_keys(&metadata).await?;
+                self.keys.decoding_key(&kid).ok_or_else(|| invalid_token("unknown signing key"))?
+            }
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_audience(&[&self.config.client_id]);
+        let claims = decode::<IdTokenClaims>(id_token, &decoding_key, &validation)
+            .map_err(|e| {
+                warn!("Rejected ID token from {}: {}", self.config.name, e);
+                invalid_token("signature or claims check failed")
+            })?
+            .claims;
+
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(invalid_token("nonce mismatch"));
+        }
+        Ok(claims)
+    }
+
+    async fn metadata(&self) -> Result<ProviderMetadata, ServiceError> {
+        if let Some(metadata) = self.metadata.read().unwrap().clone() {
+            return Ok(metadata);
+        }
+
+        let url = format!("{}/.well-known/openid-configuration", self.config.issuer.trim_end_matches('/'));
+        info!("Fetching OIDC discovery document for {} from {}", self.config.name, url);
+        let metadata: ProviderMetadata = self.get_json(&url).await?;
+        if metadata.issuer.trim_end_matches('/') != self.config.issuer.trim_end_matches('/') {
+            return Err(provider_error(&self.config.name, &format!("discovery document is for issuer {}", metadata.issuer)));
+        }
+
+        *self.metadata.write().unwrap() = Some(metadata.clone());
+        Ok(metadata)
+    }
+
+    async fn refresh_keys(&self, metadata: &ProviderMetadata) -> Result<(), ServiceError> {
+        let jwks: JwkSet = self.get_json(&metadata.jwks_uri).await?;
+        self.keys.replace(jwks)
+            .map_err(|e| provider_error(&self.config.name, &format!("invalid JWKS: {}", e)))
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, url: &str) -> Result<T, ServiceError> {
+        self.http.get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| provider_error(&self.config.name, &format!("request to {} failed: {}", url, e)))?
+            .json::<T>()
+            .await
+            .map_err(|e| provider_error(&self.config.name, &format!("invalid response from {}: {}", url, e)))
+    }
+}
+
+fn provider_error(provider: &str, message: &str) -> ServiceError {
+    error!("OIDC provider {}: {}", provider, message);
+    ServiceError::new("The identity provider is unavailable", 502)
+}
+
+fn invalid_token(reason: &str) -> ServiceError {
+    ServiceError::unauthorized(&format!("Invalid ID token: {}", reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oidc::mock_issuer::{MockIssuer, CLIENT_ID};
+    use crate::oidc::pkce_pair;
+
+    fn client(issuer: &MockIssuer) -> OidcClient {
+        let config = OidcProviderConfig {
+            name: "mock".to_string(),
+            issuer: issuer.issuer.clone(),
+            client_id: CLIENT_ID.to_string(),
+            client_secret: None,
+            scopes: "openid email profile".to_string(),
+            auto_link_verified_email: false,
+        };
+        OidcClient::new(config, "http://localhost/api/auth/oidc/mock/callback".to_string()).unwrap()
+    }
+
+    async fn sign_in(issuer: &MockIssuer, client: &OidcClient, nonce: &str) -> Result<IdTokenClaims, ServiceError> {
+        let (verifier, challenge) = pkce_pair();
+        let url = Url::parse(&client.authorization_url("state", nonce, &challenge).await.unwrap()).unwrap();
+        let params: std::collections::HashMap<_, _> = url.query_pairs().into_owned().collect();
+        assert_eq!(params["code_challenge"], challenge);
+        assert_eq!(params["code_challenge_method"], "S256");
+
+        let code = issuer.issue_code(&params["code_challenge"], &params["nonce"], "user-1", "jane@example.com");
+        let id_token = client.exchange_code(&code, &verifier).await?;
+        client.validate_id_token(&id_token, "nonce-1").await
+    }
+
+    #[actix_web::test]
+    async fn completes_the_code_flow() {
+        let issuer = MockIssuer::start().await;
+        let claims = sign_in(&issuer, &client(&issuer), "nonce-1").await.unwrap();
+        assert_eq!(claims.sub, "user-1");
+        assert_eq!(claims.email.as_deref(), Some("jane@example.com"));
+        assert!(claims.email_verified);
+    }
+
+    #[actix_web::test]
+    async fn rejects_a_wrong_nonce_or_audience() {
+        let issuer = MockIssuer::start().await;
+        let client = client(&issuer);
+        assert_eq!(sign_in(&issuer, &client, "replayed").await.unwrap_err().status_code, 401);
+
+        issuer.set_audience("another-client");
+        assert_eq!(sign_in(&issuer, &client, "nonce-1").await.unwrap_err().status_code, 401);
+    }
+
+    #[actix_web::test]
+    async fn the_code_is_bound_to_the_pkce_verifier() {
+        let issuer = MockIssuer::start().await;
+        let client = client(&issuer);
+        let (_, challenge) = pkce_pair();
+        let (other_verifier, _) = pkce_pair();
+        let code = issuer.issue_code(&challenge, "nonce-1", "user-1", "jane@example.com");
+        assert_eq!(client.exchange_code(&code, &other_verifier).await.unwrap_err().status_code, 401);
+    }
+
+    #[actix_web::test]
+    async fn refetches_keys_after_rotation() {
+        let issuer = MockIssuer::start().await;
+        let client = client(&issuer);
+        sign_in(&issuer, &client, "nonce-1").await.unwrap();
+        sign_in(&issuer, &client, "nonce-1").await.unwrap();
+        assert_eq!(issuer.jwks_requests(), 1);
+
+        issuer.rotate_key();
+        sign_in(&issuer, &client, "nonce-1").await.unwrap();
+        assert_eq!(issuer.jwks_requests(), 2);
+    }
+}
@@ -0,0 +1,150 @@
+// A minimal OpenID provider for tests: discovery, JWKS and a token endpoint that enforces PKCE.
+// Authorization codes are handed out directly by `issue_code`, standing in for the browser step.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use actix_web::{web, App, HttpResponse, HttpServer};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::Utc;
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use serde_json::{json, Value};
+use crate::oidc::pkce_challenge;
+
+pub const CLIENT_ID: &str = "ticketban-test";
+
+struct IssuedCode {
+    code_challenge: String,
+    nonce: String,
+    claims: Value,
+}
+
+struct MockState {
+    issuer: String,
+    audience: String,
+    signing_key: (String, Vec<u8>),
+    public_keys: Vec<(String, Vec<u8>)>,
+    codes: HashMap<String, IssuedCode>,
+    jwks_requests: usize,
+}
+
+pub struct MockIssuer {
+    pub issuer: String,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockIssuer {
+    pub async fn start() -> Self {
+        let state = Arc::new(Mutex::new(MockState {
+            issuer: String::new(),
+            audience: CLIENT_ID.to_string(),
+            signing_key: (String::new(), Vec::new()),
+            public_keys: Vec::new(),
+            codes: HashMap::new(),
+            jwks_requests: 0,
+        }));
+        let app_state = web::Data::from(state.clone());
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(app_state.clone())
+                .route("/.well-known/openid-configuration", web::get().to(discovery))
+                .route("/jwks", web::get().to(jwks))
+                .route("/token", web::post().to(token))
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let issuer = format!("http://{}", server.addrs()[0]);
+        tokio::spawn(server.run());
+
+        state.lock().unwrap().issuer = issuer.clone();
+        let mock = Self { issuer, state };
+        mock.rotate_key();
+        mock
+    }
+
+    // Publishes a new signing key alongside the old ones, as providers do during rotation.
+    pub fn rotate_key(&self) {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let mut state = self.state.lock().unwrap();
+        let kid = format!("key-{}", state.public_keys.len() + 1);
+        state.public_keys.push((kid.clone(), key_pair.public_key().as_ref().to_vec()));
+        state.signing_key = (kid, pkcs8.as_ref().to_vec());
+    }
+
+    pub fn set_audience(&self, audience: &str) {
+        self.state.lock().unwrap().audience = audience.to_string();
+    }
+
+    pub fn jwks_requests(&self) -> usize {
+        self.state.lock().unwrap().jwks_requests
+    }
+
+    pub fn issue_code(&self, code_challenge: &str, nonce: &str, sub: &str, email: &str) -> String {
+        let code = format!("code-{}", uuid::Uuid::new_v4());
+        let claims = json!({ "sub": sub, "email": email, "email_verified": true, "preferred_username": sub });
+        self.state.lock().unwrap().codes.insert(code.clone(), IssuedCode {
+            code_challenge: code_challenge.to_string(),
+            nonce: nonce.to_string(),
+            claims,
+        });
+        code
+    }
+}
+
+async fn discovery(state: web::Data<Mutex<MockState>>) -> HttpResponse {
+    let issuer = state.lock().unwrap().issuer.clone();
+    HttpResponse::Ok().json(json!({
+        "issuer": issuer,
+        "authorization_endpoint": format!("{}/authorize", issuer),
+        "token_endpoint": format!("{}/token", issuer),
+        "jwks_uri": format!("{}/jwks", issuer),
+    }))
+}
+
+async fn jwks(state: web::Data<Mutex<MockState>>) -> HttpResponse {
+    let mut state = state.lock().unwrap();
+    state.jwks_requests += 1;
+    let keys: Vec<Value> = state.public_keys
+        .iter()
+        .map(|(kid, public_key)| json!({
+            "kty": "OKP",
+            "crv": "Ed25519",
+            "use": "sig",
+            "alg": "EdDSA",
+            "kid": kid,
+            "x": URL_SAFE_NO_PAD.encode(public_key),
+        }))
+        .collect();
+    HttpResponse::Ok().json(json!({ "keys": keys }))
+}
+
+async fn token(state: web::Data<Mutex<MockState>>, form: web::Form<HashMap<String, String>>) -> HttpResponse {
+    let mut state = state.lock().unwrap();
+    let field = |name: &str| form.get(name).map(String::as_str).unwrap_or_default();
+    if field("grant_type") != "authorization_code" || field("client_id") != CLIENT_ID {
+        return HttpResponse::BadRequest().json(json!({ "error": "invalid_client" }));
+    }
+    let Some(issued) = state.codes.remove(field("code")) else {
+        return HttpResponse::BadRequest().json(json!({ "error": "invalid_grant" }));
+    };
+    if pkce_challenge(field("code_verifier")) != issued.code_challenge {
+        return HttpResponse::BadRequest().json(json!({ "error": "invalid_grant" }));
+    }
+
+    let now = Utc::now().timestamp();
+    let mut claims = issued.claims;
+    claims["iss"] = json!(state.issuer);
+    claims["aud"] = json!(state.audience);
+    claims["nonce"] = json!(issued.nonce);
+    claims["iat"] = json!(now);
+    claims["exp"] = json!(now + 300);
+
+    let (kid, pkcs8) = &state.signing_key;
+    let mut header = Header::new(Algorithm::EdDSA);
+    header.kid = Some(kid.clone());
+    let id_token = encode(&header, &claims, &EncodingKey::from_ed_der(pkcs8)).unwrap();
+    HttpResponse::Ok().json(json!({ "access_token": "unused", "token_type": "Bearer", "id_token": id_token }))
+}
@@ -0,0 +1,107 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::models::api_key::ApiKey;
+use shared::errors::repository_error::RepositoryError;
+use log::error;
+
+#[async_trait::async_trait]
+pub trait ApiKeyRepository {
+    async fn create(&self, api_key: &ApiKey) -> Result<(), RepositoryError>;
+    async fn get_active_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>, RepositoryError>;
+    async fn get_active_for_user(&self, user_uid: &Uuid) -> Result<Vec<ApiKey>, RepositoryError>;
+    async fn revoke(&self, user_uid: &Uuid, uid: &Uuid) -> Result<bool, RepositoryError>;
+    async fn touch(&self, uid: &Uuid) -> Result<(), RepositoryError>;
+}
+
+pub struct PgApiKeyRepository {
+    pub pool: PgPool,
+}
+
+impl PgApiKeyRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl ApiKeyRepository for PgApiKeyRepository {
+    async fn create(&self, api_key: &ApiKey) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO api_keys (uid, user_uid, name, prefix, key_hash, scopes, expires_at, created_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"
+        )
+        .bind(api_key.uid)
+        .bind(api_key.user_uid)
+        .bind(&api_key.name)
+        .bind(&api_key.prefix)
+        .bind(&api_key.key_hash)
+        .bind(&api_key.scopes)
+        .bind(api_key.expires_at)
+        .bind(api_key.created_at)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+        .map_err(|e| {
+            error!("Database error in create: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    async fn get_active_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>, RepositoryError> {
+        sqlx::query_as::<_, ApiKey>(
+            "SELECT * FROM api_keys
+             WHERE key_hash = $1 AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > NOW())"
+        )
+        .bind(key_hash)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            error!("Database error in get_active_by_hash: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    async fn get_active_for_user(&self, user_uid: &Uuid) -> Result<Vec<ApiKey>, RepositoryError> {
+        sqlx::query_as::<_, ApiKey>(
+            "SELECT * FROM api_keys
+             WHERE user_uid = $1 AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > NOW())
+             ORDER BY created_at DESC"
+        )
+        .bind(user_uid)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            error!("Database error in get_active_for_user: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    async fn revoke(&self, user_uid: &Uuid, uid: &Uuid) -> Result<bool, RepositoryError> {
+        sqlx::query("UPDATE api_keys SET revoked_at = NOW() WHERE uid = $1 AND user_uid = $2 AND revoked_at IS NULL")
+            .bind(uid)
+            .bind(user_uid)
+            .execute(&self.pool)
+            .await
+            .map(|result| result.rows_affected() > 0)
+            .map_err(|e| {
+                error!("Database error in revoke: {}", e);
+                RepositoryError::from(e)
+            })
+    }
+
+    // Written at most once a minute per key so busy scripts don't turn every request into a write.
+    async fn touch(&self, uid: &Uuid) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "UPDATE api_keys SET last_used_at = NOW()
+             WHERE uid = $1 AND (last_used_at IS NULL OR last_used_at < NOW() - INTERVAL '1 minute')"
+        )
+        .bind(uid)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+        .map_err(|e| {
+            error!("Database error in touch: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+}
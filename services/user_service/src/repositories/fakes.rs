@@ -0,0 +1,745 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use crate::models::identity::{ExternalIdentity, OidcLoginState};
+use crate::models::directory::{DirectoryUser, UserCursor, UserSearch, UserSort};
+use crate::models::login_attempt::{LockoutPolicy, LoginAttempts};
+use crate::models::mfa::{MfaChallengeRecord, UserTotp};
+use crate::models::passkey::Passkey;
+use crate::models::profile::{Profile, UpdateProfileDTO};
+use crate::models::role::{Role, DEFAULT_ROLE};
+use crate::models::session::Session;
+use crate::models::token::{EmailVerificationToken, PasswordResetToken, RefreshToken};
+use crate::models::user::{User, UserDTO};
+use crate::repositories::identity_repository::IdentityRepository;
+use crate::repositories::login_attempt_repository::LoginAttemptRepository;
+use crate::repositories::mfa_repository::MfaRepository;
+use crate::repositories::passkey_repository::PasskeyRepository;
+use crate::repositories::token_repository::{revocation_cutoff, TokenRepository};
+use crate::repositories::user_repository::UserRepository;
+use shared::errors::repository_error::RepositoryError;
+
+#[derive(Default)]
+pub struct InMemoryLoginAttemptRepository {
+    attempts: Mutex<HashMap<String, LoginAttempts>>,
+}
+
+#[async_trait::async_trait]
+impl LoginAttemptRepository for InMemoryLoginAttemptRepository {
+    async fn get(&self, key: &str) -> Result<Option<LoginAttempts>, RepositoryError> {
+        Ok(self.attempts.lock().unwrap().get(key).cloned())
+    }
+
+    async fn record_failure(&self, key: &str, policy: &LockoutPolicy) -> Result<LoginAttempts, RepositoryError> {
+        let now = Utc::now();
+        let mut attempts = self.attempts.lock().unwrap();
+        let entry = attempts.entry(key.to_string()).or_insert_with(|| LoginAttempts {
+            key: key.to_string(),
+            failures: 0,
+            last_failure_at: now,
+            locked_until: None,
+        });
+        if entry.last_failure_at < now - policy.window {
+            entry.failures = 0;
+        }
+        entry.failures += 1;
+        entry.last_failure_at = now;
+        if let Some(lockout) = policy.lockout_for(entry.failures) {
+            entry.locked_until = Some(now + lockout);
+        }
+        Ok(entry.clone())
+    }
+
+    async fn reset(&self, key: &str) -> Result<(), RepositoryError> {
+        self.attempts.lock().unwrap().remove(key);
+        Ok(())
+    }
+
+    async fn purge(&self, failed_before: DateTime<Utc>) -> Result<u64, RepositoryError> {
+        let now = Utc::now();
+        let mut attempts = self.attempts.lock().unwrap();
+        let before = attempts.len();
+        attempts.retain(|_, entry| entry.last_failure_at >= failed_before || entry.locked_until.is_some_and(|until| until >= now));
+        Ok((before - attempts.len()) as u64)
+    }
+}
+
+// The fakes below are cheap to clone and share their state, so a test can keep a handle to look
+// at what a service wrote.
+#[derive(Clone, Default)]
+pub struct FakeUserRepository {
+    users: Arc<Mutex<Vec<User>>>,
+    roles: Arc<Mutex<HashMap<Uuid, Vec<String>>>>,
+}
+
+impl FakeUserRepository {
+    pub fn add(&self, username: &str, email: &str, password_hash: Option<String>) -> User {
+        let now = Utc::now();
+        let user = User {
+            uid: Uuid::new_v4(),
+            username: username.to_string(),
+            email: email.to_string(),
+            password_hash,
+            email_verified_at: Some(now),
+            profile: Profile::default(),
+            created_at: now,
+            updated_at: now,
+        };
+        self.users.lock().unwrap().push(user.clone());
+        self.roles.lock().unwrap().insert(user.uid, vec![DEFAULT_ROLE.to_string()]);
+        user
+    }
+
+    fn find(&self, matches: impl Fn(&User) -> bool) -> Option<User> {
+        self.users.lock().unwrap().iter().find(|user| matches(user)).cloned()
+    }
+
+    fn update<R>(&self, uid: &Uuid, change: impl FnOnce(&mut User) -> R) -> Result<R, RepositoryError> {
+        let mut users = self.users.lock().unwrap();
+        let user = users.iter_mut().find(|user| user.uid == *uid)
+            .ok_or_else(|| RepositoryError::NotFound(format!("User with uid {} not found", uid)))?;
+        Ok(change(user))
+    }
+}
+
+#[async_trait::async_trait]
+impl UserRepository for FakeUserRepository {
+    // Stands in for the trigram search with substring matches, ranking prefix matches above the
+    // rest; sorting and cursors behave as in Postgres.
+    async fn get_all(&self, search: &UserSearch) -> Result<Vec<DirectoryUser>, RepositoryError> {
+        let query = search.query.as_deref().map(str::to_lowercase);
+        let mut users: Vec<DirectoryUser> = self.users.lock().unwrap().iter()
+            .filter_map(|user| {
+                let rank = match &query {
+                    Some(query) => {
+                        let names = [Some(user.username.to_lowercase()), user.profile.display_name.as_deref().map(str::to_lowercase)];
+                        let names: Vec<&String> = names.iter().flatten().collect();
+                        if names.iter().any(|name| name.starts_with(query.as_str())) {
+                            2.0
+                        } else if names.iter().any(|name| name.contains(query.as_str())) {
+                            1.0
+                        } else {
+                            return None;
+                        }
+                    }
+                    None => 0.0,
+                };
+                Some(DirectoryUser {
+                    uid: user.uid,
+                    username: user.username.clone(),
+                    profile: user.profile.clone(),
+                    created_at: user.created_at,
+                    rank,
+                })
+            })
+            .filter(|user| match &search.after {
+                Some(UserCursor::Username { username }) => user.username.to_lowercase() > *username,
+                Some(UserCursor::Newest { created_at, uid }) => (user.created_at, user.uid) < (*created_at, *uid),
+                Some(UserCursor::Relevance { rank, username }) => {
+                    user.rank < *rank || (user.rank == *rank && user.username.to_lowercase() > *username)
+                }
+                None => true,
+            })
+            .collect();
+        users.sort_by(|a, b| match search.sort {
+            UserSort::Username => a.username.to_lowercase().cmp(&b.username.to_lowercase()),
+            UserSort::Newest => (b.created_at, b.uid).cmp(&(a.created_at, a.uid)),
+            UserSort::Relevance => b.rank.total_cmp(&a.rank)
+                .then_with(|| a.username.to_lowercase().cmp(&b.username.to_lowercase())),
+        });
+        users.truncate(search.limit as usize);
+        Ok(users)
+    }
+
+    async fn get_by_id(&self, uid: &Uuid) -> Result<User, RepositoryError> {
+        self.find(|user| user.uid == *uid)
+            .ok_or_else(|| RepositoryError::NotFound(format!("User with uid {} not found", uid)))
+    }
+
+    async fn get_by_email(&self, email: &str) -> Result<User, RepositoryError> {
+        self.find(|user| user.email.eq_ignore_ascii_case(email.trim()))
+            .ok_or_else(|| RepositoryError::NotFound(format!("User with email {} not found", email)))
+    }
+
+    async fn get_by_login(&self, identifier: &str) -> Result<User, RepositoryError> {
+        let identifier = identifier.trim();
+        if identifier.contains('@') {
+            return self.get_by_email(identifier).await;
+        }
+        self.find(|user| user.username.eq_ignore_ascii_case(identifier))
+            .ok_or_else(|| RepositoryError::NotFound(format!("User with username {} not found", identifier)))
+    }
+
+    async fn create(&self, user_dto: &UserDTO) -> Result<User, RepositoryError> {
+        for (field, taken) in [
+            ("username", self.get_by_login(&user_dto.username).await.is_ok()),
+            ("email", self.get_by_email(&user_dto.email).await.is_ok()),
+        ] {
+            if taken {
+                return Err(RepositoryError::Conflict {
+                    constraint: Some(format!("users_{}_lower_key", field)),
+                    field: Some(field.to_string()),
+                });
+            }
+        }
+        let user = self.add(&user_dto.username, &user_dto.email, Some(user_dto.password.clone()));
+        self.update(&user.uid, |user| {
+            user.email_verified_at = None;
+            user.clone()
+        })
+    }
+
+    async fn update_password(&self, uid: &Uuid, password_hash: &str) -> Result<(), RepositoryError> {
+        self.update(uid, |user| user.password_hash = Some(password_hash.to_string()))
+    }
+
+    async fn update_email(&self, uid: &Uuid, email: &str) -> Result<User, RepositoryError> {
+        self.update(uid, |user| {
+            user.email = email.to_string();
+            user.email_verified_at = Some(Utc::now());
+            user.clone()
+        })
+    }
+
+    async fn update_profile(&self, uid: &Uuid, profile: &UpdateProfileDTO) -> Result<User, RepositoryError> {
+        self.update(uid, |user| {
+            let current = &mut user.profile;
+            let fields = [
+                (&profile.display_name, &mut current.display_name),
+                (&profile.avatar_url, &mut current.avatar_url),
+                (&profile.bio, &mut current.bio),
+                (&profile.status_text, &mut current.status_text),
+                (&profile.locale, &mut current.locale),
+                (&profile.time_zone, &mut current.time_zone),
+            ];
+            for (sent, value) in fields {
+                if let Some(sent) = sent {
+                    *value = sent.clone();
+                }
+            }
+            if let Some(status_expires_at) = profile.status_expires_at {
+                current.status_expires_at = status_expires_at;
+            }
+            user.updated_at = Utc::now();
+            user.clone()
+        })
+    }
+
+    async fn mark_email_verified(&self, uid: &Uuid, email: &str) -> Result<bool, RepositoryError> {
+        let mut users = self.users.lock().unwrap();
+        let Some(user) = users.iter_mut().find(|user| user.uid == *uid && user.email == email) else {
+            return Ok(false);
+        };
+        user.email_verified_at.get_or_insert_with(Utc::now);
+        Ok(true)
+    }
+
+    async fn get_roles(&self, user_uid: &Uuid) -> Result<Vec<Role>, RepositoryError> {
+        let mut roles: Vec<Role> = self.roles.lock().unwrap()
+            .get(user_uid)
+            .into_iter()
+            .flatten()
+            .map(|name| Role { name: name.clone(), scopes: vec![format!("{}:read", name)] })
+            .collect();
+        roles.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(roles)
+    }
+
+    async fn role_exists(&self, role: &str) -> Result<bool, RepositoryError> {
+        Ok(["user", "admin"].contains(&role))
+    }
+
+    async fn add_role(&self, user_uid: &Uuid, role: &str) -> Result<(), RepositoryError> {
+        let mut roles = self.roles.lock().unwrap();
+        let roles = roles.entry(*user_uid).or_default();
+        if !roles.iter().any(|name| name == role) {
+            roles.push(role.to_string());
+        }
+        Ok(())
+    }
+
+    async fn remove_role(&self, user_uid: &Uuid, role: &str) -> Result<bool, RepositoryError> {
+        let mut roles = self.roles.lock().unwrap();
+        let roles = roles.entry(*user_uid).or_default();
+        let before = roles.len();
+        roles.retain(|name| name != role);
+        Ok(roles.len() < before)
+    }
+}
+
+#[derive(Default)]
+pub struct TokenStore {
+    pub refresh_tokens: Vec<RefreshToken>,
+    pub revoked_tokens: HashMap<Uuid, DateTime<Utc>>,
+    pub revoked_before: HashMap<Uuid, DateTime<Utc>>,
+    pub email_verification_tokens: Vec<EmailVerificationToken>,
+    pub password_reset_tokens: Vec<PasswordResetToken>,
+    pub mfa_challenges: Vec<MfaChallengeRecord>,
+    pub sessions: Vec<Session>,
+}
+
+#[derive(Clone, Default)]
+pub struct FakeTokenRepository {
+    pub store: Arc<Mutex<TokenStore>>,
+}
+
+impl FakeTokenRepository {
+    pub fn with<R>(&self, read: impl FnOnce(&mut TokenStore) -> R) -> R {
+        read(&mut self.store.lock().unwrap())
+    }
+}
+
+#[async_trait::async_trait]
+impl TokenRepository for FakeTokenRepository {
+    async fn create_refresh_token(&self, user_uid: &Uuid, family_uid: &Uuid, token_hash: &str, expires_at: DateTime<Utc>) -> Result<RefreshToken, RepositoryError> {
+        let refresh_token = RefreshToken {
+            uid: Uuid::new_v4(),
+            user_uid: *user_uid,
+            family_uid: *family_uid,
+            token_hash: token_hash.to_string(),
+            expires_at,
+            revoked_at: None,
+            created_at: Utc::now(),
+        };
+        self.with(|store| store.refresh_tokens.push(refresh_token.clone()));
+        Ok(refresh_token)
+    }
+
+    async fn get_refresh_token_by_hash(&self, token_hash: &str) -> Result<Option<RefreshToken>, RepositoryError> {
+        Ok(self.with(|store| store.refresh_tokens.iter().find(|token| token.token_hash == token_hash).cloned()))
+    }
+
+    async fn consume_refresh_token(&self, token_hash: &str) -> Result<Option<RefreshToken>, RepositoryError> {
+        Ok(self.with(|store| {
+            let token = store.refresh_tokens.iter_mut()
+                .find(|token| token.token_hash == token_hash && token.revoked_at.is_none())?;
+            token.revoked_at = Some(Utc::now());
+            Some(token.clone())
+        }))
+    }
+
+    async fn revoke_refresh_token_family(&self, family_uid: &Uuid) -> Result<(), RepositoryError> {
+        self.with(|store| {
+            for token in store.refresh_tokens.iter_mut().filter(|token| token.family_uid == *family_uid) {
+                token.revoked_at.get_or_insert_with(Utc::now);
+            }
+        });
+        Ok(())
+    }
+
+    async fn revoke_token(&self, jti: &Uuid, _user_uid: &Uuid, expires_at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        self.with(|store| {
+            store.revoked_tokens.entry(*jti).or_insert(expires_at);
+        });
+        Ok(())
+    }
+
+    async fn revoke_all_user_tokens(&self, user_uid: &Uuid) -> Result<(), RepositoryError> {
+        let now = Utc::now();
+        self.with(|store| {
+            store.revoked_before.insert(*user_uid, revocation_cutoff(now));
+            for token in store.refresh_tokens.iter_mut().filter(|token| token.user_uid == *user_uid) {
+                token.revoked_at.get_or_insert(now);
+            }
+            for session in store.sessions.iter_mut().filter(|session| session.user_uid == *user_uid) {
+                session.revoked_at.get_or_insert(now);
+            }
+        });
+        Ok(())
+    }
+
+    async fn is_token_revoked(&self, jti: &Uuid, user_uid: &Uuid, issued_at: DateTime<Utc>, session_uid: Option<Uuid>) -> Result<bool, RepositoryError> {
+        Ok(self.with(|store| {
+            store.revoked_tokens.contains_key(jti)
+                || store.revoked_before.get(user_uid).is_some_and(|before| *before > issued_at)
+                || store.sessions.iter().any(|session| Some(session.uid) == session_uid && session.revoked_at.is_some())
+        }))
+    }
+
+    async fn create_email_verification_token(&self, user_uid: &Uuid, email: &str, token_hash: &str, expires_at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        self.with(|store| store.email_verification_tokens.push(EmailVerificationToken {
+            uid: Uuid::new_v4(),
+            user_uid: *user_uid,
+            email: email.to_string(),
+            token_hash: token_hash.to_string(),
+            expires_at,
+            used_at: None,
+            created_at: Utc::now(),
+        }));
+        Ok(())
+    }
+
+    async fn consume_email_verification_token(&self, token_hash: &str) -> Result<Option<EmailVerificationToken>, RepositoryError> {
+        let now = Utc::now();
+        Ok(self.with(|store| {
+            let token = store.email_verification_tokens.iter_mut()
+                .find(|token| token.token_hash == token_hash && token.used_at.is_none() && token.expires_at > now)?;
+            token.used_at = Some(now);
+            Some(token.clone())
+        }))
+    }
+
+    async fn last_email_verification_sent_at(&self, user_uid: &Uuid) -> Result<Option<DateTime<Utc>>, RepositoryError> {
+        Ok(self.with(|store| {
+            store.email_verification_tokens.iter()
+                .filter(|token| token.user_uid == *user_uid)
+                .map(|token| token.created_at)
+                .max()
+        }))
+    }
+
+    async fn invalidate_email_change_tokens(&self, user_uid: &Uuid, current_email: &str) -> Result<(), RepositoryError> {
+        self.with(|store| {
+            for token in store.email_verification_tokens.iter_mut()
+                .filter(|token| token.user_uid == *user_uid && !token.email.eq_ignore_ascii_case(current_email))
+            {
+                token.used_at.get_or_insert_with(Utc::now);
+            }
+        });
+        Ok(())
+    }
+
+    async fn create_password_reset_token(&self, user_uid: &Uuid, token_hash: &str, expires_at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        self.with(|store| store.password_reset_tokens.push(PasswordResetToken {
+            uid: Uuid::new_v4(),
+            user_uid: *user_uid,
+            token_hash: token_hash.to_string(),
+            expires_at,
+            used_at: None,
+            created_at: Utc::now(),
+        }));
+        Ok(())
+    }
+
+    async fn get_password_reset_token(&self, token_hash: &str) -> Result<Option<PasswordResetToken>, RepositoryError> {
+        let now = Utc::now();
+        Ok(self.with(|store| {
+            store.password_reset_tokens.iter()
+                .find(|token| token.token_hash == token_hash && token.used_at.is_none() && token.expires_at > now)
+                .cloned()
+        }))
+    }
+
+    async fn consume_password_reset_token(&self, token_hash: &str) -> Result<Option<PasswordResetToken>, RepositoryError> {
+        let now = Utc::now();
+        Ok(self.with(|store| {
+            let token = store.password_reset_tokens.iter_mut()
+                .find(|token| token.token_hash == token_hash && token.used_at.is_none() && token.expires_at > now)?;
+            token.used_at = Some(now);
+            Some(token.clone())
+        }))
+    }
+
+    async fn invalidate_password_reset_tokens(&self, user_uid: &Uuid) -> Result<(), RepositoryError> {
+        self.with(|store| {
+            for token in store.password_reset_tokens.iter_mut().filter(|token| token.user_uid == *user_uid) {
+                token.used_at.get_or_insert_with(Utc::now);
+            }
+        });
+        Ok(())
+    }
+
+    async fn create_mfa_challenge(&self, user_uid: &Uuid, token_hash: &str, expires_at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        self.with(|store| store.mfa_challenges.push(MfaChallengeRecord {
+            uid: Uuid::new_v4(),
+            user_uid: *user_uid,
+            token_hash: token_hash.to_string(),
+            failed_attempts: 0,
+            expires_at,
+            used_at: None,
+            created_at: Utc::now(),
+        }));
+        Ok(())
+    }
+
+    async fn get_mfa_challenge(&self, token_hash: &str) -> Result<Option<MfaChallengeRecord>, RepositoryError> {
+        let now = Utc::now();
+        Ok(self.with(|store| {
+            store.mfa_challenges.iter()
+                .find(|challenge| challenge.token_hash == token_hash && challenge.used_at.is_none() && challenge.expires_at > now)
+                .cloned()
+        }))
+    }
+
+    async fn record_mfa_challenge_failure(&self, token_hash: &str, max_attempts: i32) -> Result<(), RepositoryError> {
+        self.with(|store| {
+            if let Some(challenge) = store.mfa_challenges.iter_mut()
+                .find(|challenge| challenge.token_hash == token_hash && challenge.used_at.is_none())
+            {
+                challenge.failed_attempts += 1;
+                if challenge.failed_attempts >= max_attempts {
+                    challenge.used_at = Some(Utc::now());
+                }
+            }
+        });
+        Ok(())
+    }
+
+    async fn consume_mfa_challenge(&self, token_hash: &str) -> Result<Option<MfaChallengeRecord>, RepositoryError> {
+        let now = Utc::now();
+        Ok(self.with(|store| {
+            let challenge = store.mfa_challenges.iter_mut()
+                .find(|challenge| challenge.token_hash == token_hash && challenge.used_at.is_none() && challenge.expires_at > now)?;
+            challenge.used_at = Some(now);
+            Some(challenge.clone())
+        }))
+    }
+
+    async fn create_session(&self, session: &Session) -> Result<(), RepositoryError> {
+        self.with(|store| store.sessions.push(session.clone()));
+        Ok(())
+    }
+
+    async fn touch_session(&self, session_uid: &Uuid, ip_address: &str, expires_at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        self.with(|store| {
+            if let Some(session) = store.sessions.iter_mut()
+                .find(|session| session.uid == *session_uid && session.revoked_at.is_none())
+            {
+                session.last_used_at = Utc::now();
+                session.ip_address = Some(ip_address.to_string());
+                session.expires_at = expires_at;
+            }
+        });
+        Ok(())
+    }
+
+    async fn get_active_sessions(&self, user_uid: &Uuid) -> Result<Vec<Session>, RepositoryError> {
+        let now = Utc::now();
+        let mut sessions: Vec<Session> = self.with(|store| {
+            store.sessions.iter()
+                .filter(|session| session.user_uid == *user_uid && session.revoked_at.is_none() && session.expires_at > now)
+                .cloned()
+                .collect()
+        });
+        sessions.sort_by_key(|session| Reverse(session.last_used_at));
+        Ok(sessions)
+    }
+
+    async fn revoke_session(&self, user_uid: &Uuid, session_uid: &Uuid) -> Result<bool, RepositoryError> {
+        let now = Utc::now();
+        Ok(self.with(|store| {
+            for token in store.refresh_tokens.iter_mut()
+                .filter(|token| token.family_uid == *session_uid && token.user_uid == *user_uid)
+            {
+                token.revoked_at.get_or_insert(now);
+            }
+            let Some(session) = store.sessions.iter_mut()
+                .find(|session| session.uid == *session_uid && session.user_uid == *user_uid && session.revoked_at.is_none())
+            else {
+                return false;
+            };
+            session.revoked_at = Some(now);
+            true
+        }))
+    }
+
+    async fn purge_expired(&self, revocations_before: DateTime<Utc>) -> Result<u64, RepositoryError> {
+        let now = Utc::now();
+        Ok(self.with(|store| {
+            let count = |store: &TokenStore| {
+                store.revoked_tokens.len() + store.revoked_before.len() + store.sessions.len() + store.refresh_tokens.len()
+            };
+            let before = count(store);
+            store.revoked_tokens.retain(|_, expires_at| *expires_at >= now);
+            store.revoked_before.retain(|_, revoked_before| *revoked_before >= revocations_before);
+            let (kept, purged): (Vec<Session>, Vec<Session>) = store.sessions.drain(..).partition(|session| {
+                session.expires_at >= revocations_before && session.revoked_at.is_none_or(|revoked_at| revoked_at >= revocations_before)
+            });
+            store.sessions = kept;
+            store.refresh_tokens.retain(|token| {
+                token.expires_at >= now && !purged.iter().any(|session| session.uid == token.family_uid)
+            });
+            (before - count(store)) as u64
+        }))
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct FakeMfaRepository {
+    totp: Arc<Mutex<HashMap<Uuid, UserTotp>>>,
+    recovery_codes: Arc<Mutex<HashMap<Uuid, HashSet<String>>>>,
+    passkey_users: Arc<Mutex<HashSet<Uuid>>>,
+}
+
+impl FakeMfaRepository {
+    // Stands in for the passkeys table, which has_passkeys reads in Postgres.
+    pub fn add_passkey_user(&self, user_uid: &Uuid) {
+        self.passkey_users.lock().unwrap().insert(*user_uid);
+    }
+}
+
+#[async_trait::async_trait]
+impl MfaRepository for FakeMfaRepository {
+    async fn get_totp(&self, user_uid: &Uuid) -> Result<Option<UserTotp>, RepositoryError> {
+        Ok(self.totp.lock().unwrap().get(user_uid).cloned())
+    }
+
+    async fn save_pending_totp(&self, user_uid: &Uuid, secret: &str) -> Result<(), RepositoryError> {
+        self.totp.lock().unwrap().insert(*user_uid, UserTotp {
+            user_uid: *user_uid,
+            secret: secret.to_string(),
+            enabled_at: None,
+            last_used_step: None,
+            created_at: Utc::now(),
+        });
+        Ok(())
+    }
+
+    async fn enable_totp(&self, user_uid: &Uuid, step: i64, recovery_code_hashes: &[String]) -> Result<(), RepositoryError> {
+        let mut totp = self.totp.lock().unwrap();
+        let totp = totp.get_mut(user_uid)
+            .filter(|totp| totp.enabled_at.is_none())
+            .ok_or_else(|| RepositoryError::not_found("No pending two-factor enrollment"))?;
+        totp.enabled_at = Some(Utc::now());
+        totp.last_used_step = Some(step);
+        self.recovery_codes.lock().unwrap().insert(*user_uid, recovery_code_hashes.iter().cloned().collect());
+        Ok(())
+    }
+
+    async fn use_totp_step(&self, user_uid: &Uuid, step: i64) -> Result<bool, RepositoryError> {
+        let mut totp = self.totp.lock().unwrap();
+        let Some(totp) = totp.get_mut(user_uid).filter(|totp| totp.enabled_at.is_some()) else {
+            return Ok(false);
+        };
+        if totp.last_used_step.is_some_and(|last| last >= step) {
+            return Ok(false);
+        }
+        totp.last_used_step = Some(step);
+        Ok(true)
+    }
+
+    async fn use_recovery_code(&self, user_uid: &Uuid, code_hash: &str) -> Result<bool, RepositoryError> {
+        Ok(self.recovery_codes.lock().unwrap()
+            .get_mut(user_uid)
+            .is_some_and(|codes| codes.remove(code_hash)))
+    }
+
+    async fn delete_totp(&self, user_uid: &Uuid) -> Result<(), RepositoryError> {
+        self.totp.lock().unwrap().remove(user_uid);
+        self.recovery_codes.lock().unwrap().remove(user_uid);
+        Ok(())
+    }
+
+    async fn has_passkeys(&self, user_uid: &Uuid) -> Result<bool, RepositoryError> {
+        Ok(self.passkey_users.lock().unwrap().contains(user_uid))
+    }
+}
+
+/// Pending challenges by hash: the user they were issued to, their purpose and expiry.
+type PasskeyChallenges = HashMap<String, (Option<Uuid>, String, DateTime<Utc>)>;
+
+#[derive(Clone, Default)]
+pub struct FakePasskeyRepository {
+    challenges: Arc<Mutex<PasskeyChallenges>>,
+    passkeys: Arc<Mutex<Vec<Passkey>>>,
+}
+
+#[async_trait::async_trait]
+impl PasskeyRepository for FakePasskeyRepository {
+    async fn save_challenge(&self, challenge_hash: &str, user_uid: Option<&Uuid>, purpose: &str, expires_at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        self.challenges.lock().unwrap().insert(challenge_hash.to_string(), (user_uid.copied(), purpose.to_string(), expires_at));
+        Ok(())
+    }
+
+    async fn consume_challenge(&self, challenge_hash: &str, purpose: &str) -> Result<Option<Option<Uuid>>, RepositoryError> {
+        let mut challenges = self.challenges.lock().unwrap();
+        match challenges.get(challenge_hash) {
+            Some((_, stored_purpose, expires_at)) if stored_purpose == purpose && *expires_at > Utc::now() => {
+                Ok(challenges.remove(challenge_hash).map(|(user_uid, _, _)| user_uid))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    async fn create(&self, passkey: &Passkey) -> Result<(), RepositoryError> {
+        let mut passkeys = self.passkeys.lock().unwrap();
+        if passkeys.iter().any(|existing| existing.credential_id == passkey.credential_id) {
+            return Err(RepositoryError::Conflict {
+                constraint: Some("passkeys_credential_id_key".to_string()),
+                field: Some("credential_id".to_string()),
+            });
+        }
+        passkeys.push(passkey.clone());
+        Ok(())
+    }
+
+    async fn get_by_credential_id(&self, credential_id: &str) -> Result<Option<Passkey>, RepositoryError> {
+        Ok(self.passkeys.lock().unwrap().iter().find(|passkey| passkey.credential_id == credential_id).cloned())
+    }
+
+    async fn get_for_user(&self, user_uid: &Uuid) -> Result<Vec<Passkey>, RepositoryError> {
+        Ok(self.passkeys.lock().unwrap().iter().filter(|passkey| passkey.user_uid == *user_uid).cloned().collect())
+    }
+
+    async fn record_use(&self, uid: &Uuid, sign_count: i64) -> Result<(), RepositoryError> {
+        if let Some(passkey) = self.passkeys.lock().unwrap().iter_mut().find(|passkey| passkey.uid == *uid) {
+            passkey.sign_count = sign_count;
+            passkey.last_used_at = Some(Utc::now());
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, user_uid: &Uuid, uid: &Uuid) -> Result<bool, RepositoryError> {
+        let mut passkeys = self.passkeys.lock().unwrap();
+        let before = passkeys.len();
+        passkeys.retain(|passkey| !(passkey.uid == *uid && passkey.user_uid == *user_uid));
+        Ok(passkeys.len() < before)
+    }
+}
+
+// Users created from an identity go into the shared user fake, so services see them there.
+#[derive(Clone)]
+pub struct FakeIdentityRepository {
+    users: FakeUserRepository,
+    identities: Arc<Mutex<Vec<(String, String, Uuid)>>>,
+    login_states: Arc<Mutex<HashMap<String, OidcLoginState>>>,
+}
+
+impl FakeIdentityRepository {
+    pub fn new(users: FakeUserRepository) -> Self {
+        Self { users, identities: Arc::default(), login_states: Arc::default() }
+    }
+}
+
+#[async_trait::async_trait]
+impl IdentityRepository for FakeIdentityRepository {
+    async fn find_user_uid(&self, provider: &str, subject: &str) -> Result<Option<Uuid>, RepositoryError> {
+        Ok(self.identities.lock().unwrap().iter()
+            .find(|(p, s, _)| p == provider && s == subject)
+            .map(|(_, _, user_uid)| *user_uid))
+    }
+
+    async fn link_identity(&self, user_uid: &Uuid, identity: &ExternalIdentity) -> Result<(), RepositoryError> {
+        self.identities.lock().unwrap().push((identity.provider.clone(), identity.subject.clone(), *user_uid));
+        Ok(())
+    }
+
+    async fn touch_identity(&self, _provider: &str, _subject: &str, _email: &str) -> Result<(), RepositoryError> {
+        Ok(())
+    }
+
+    async fn create_user_with_identity(&self, identity: &ExternalIdentity) -> Result<User, RepositoryError> {
+        let user = self.users.add(&identity.username, &identity.email, None);
+        self.link_identity(&user.uid, identity).await?;
+        Ok(user)
+    }
+
+    async fn save_login_state(&self, state_hash: &str, provider: &str, code_verifier: &str, nonce: &str, expires_at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        self.login_states.lock().unwrap().insert(state_hash.to_string(), OidcLoginState {
+            state_hash: state_hash.to_string(),
+            provider: provider.to_string(),
+            code_verifier: code_verifier.to_string(),
+            nonce: nonce.to_string(),
+            expires_at,
+            used_at: None,
+            created_at: Utc::now(),
+        });
+        Ok(())
+    }
+
+    async fn consume_login_state(&self, state_hash: &str) -> Result<Option<OidcLoginState>, RepositoryError> {
+        Ok(self.login_states.lock().unwrap().remove(state_hash).filter(|state| state.expires_at > Utc::now()))
+    }
+}
@@ -0,0 +1,163 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use crate::models::user::User;
+use crate::models::role::DEFAULT_ROLE;
+use crate::models::identity::{OidcLoginState, ExternalIdentity};
+use shared::errors::repository_error::RepositoryError;
+use log::{info, error};
+
+const USERNAME_ATTEMPTS: usize = 5;
+
+#[async_trait::async_trait]
+pub trait IdentityRepository {
+    async fn find_user_uid(&self, provider: &str, subject: &str) -> Result<Option<Uuid>, RepositoryError>;
+    async fn link_identity(&self, user_uid: &Uuid, identity: &ExternalIdentity) -> Result<(), RepositoryError>;
+    async fn touch_identity(&self, provider: &str, subject: &str, email: &str) -> Result<(), RepositoryError>;
+    async fn create_user_with_identity(&self, identity: &ExternalIdentity) -> Result<User, RepositoryError>;
+    async fn save_login_state(&self, state_hash: &str, provider: &str, code_verifier: &str, nonce: &str, expires_at: DateTime<Utc>) -> Result<(), RepositoryError>;
+    async fn consume_login_state(&self, state_hash: &str) -> Result<Option<OidcLoginState>, RepositoryError>;
+}
+
+pub struct PgIdentityRepository {
+    pub pool: PgPool,
+}
+
+impl PgIdentityRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl IdentityRepository for PgIdentityRepository {
+    async fn find_user_uid(&self, provider: &str, subject: &str) -> Result<Option<Uuid>, RepositoryError> {
+        sqlx::query_scalar::<_, Uuid>("SELECT user_uid FROM user_identities WHERE provider = $1 AND subject = $2")
+            .bind(provider)
+            .bind(subject)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| {
+                error!("Database error in find_user_uid: {}", e);
+                RepositoryError::from(e)
+            })
+    }
+
+    async fn link_identity(&self, user_uid: &Uuid, identity: &ExternalIdentity) -> Result<(), RepositoryError> {
+        info!("Linking {} identity {} to user {}", identity.provider, identity.subject, user_uid);
+        sqlx::query("INSERT INTO user_identities (user_uid, provider, subject, email) VALUES ($1, $2, $3, $4)")
+            .bind(user_uid)
+            .bind(&identity.provider)
+            .bind(&identity.subject)
+            .bind(&identity.email)
+            .execute(&self.pool)
+            .await
+            .map(|_| ())
+            .map_err(|e| {
+                error!("Database error in link_identity: {}", e);
+                RepositoryError::from(e)
+            })
+    }
+
+    async fn touch_identity(&self, provider: &str, subject: &str, email: &str) -> Result<(), RepositoryError> {
+        sqlx::query("UPDATE user_identities SET last_login_at = NOW(), email = $3 WHERE provider = $1 AND subject = $2")
+            .bind(provider)
+            .bind(subject)
+            .bind(email)
+            .execute(&self.pool)
+            .await
+            .map(|_| ())
+            .map_err(|e| {
+                error!("Database error in touch_identity: {}", e);
+                RepositoryError::from(e)
+            })
+    }
+
+    // The provider's username is only a suggestion; a numeric suffix is added when it is taken.
+    async fn create_user_with_identity(&self, identity: &ExternalIdentity) -> Result<User, RepositoryError> {
+        info!("Creating user {} from {} identity {}", identity.email, identity.provider, identity.subject);
+        let mut tx = self.pool.begin().await
+            .map_err(RepositoryError::from)?;
+
+        let mut user = None;
+        for attempt in 0..USERNAME_ATTEMPTS {
+            let username = match attempt {
+                0 => identity.username.clone(),
+                _ => {
+                    let base: String = identity.username.chars().take(15).collect();
+                    format!("{}{:04}", base, rand::thread_rng().gen_range(0..10_000))
+                }
+            };
+            user = sqlx::query_as::<_, User>(
+                "INSERT INTO users (username, email, password_hash, email_verified_at, created_at, updated_at)
+                 VALUES ($1, $2, NULL, NOW(), NOW(), NOW())
+                 ON CONFLICT (LOWER(username)) DO NOTHING
+                 RETURNING *"
+            )
+            .bind(&username)
+            .bind(&identity.email)
+            .fetch_optional(&mut tx)
+            .await
+            .map_err(RepositoryError::from)?;
+            if user.is_some() {
+                break;
+            }
+        }
+        let user = user.ok_or_else(|| RepositoryError::other("Failed to find a free username"))?;
+
+        sqlx::query("INSERT INTO user_roles (user_uid, role) VALUES ($1, $2)")
+            .bind(user.uid)
+            .bind(DEFAULT_ROLE)
+            .execute(&mut tx)
+            .await
+            .map_err(RepositoryError::from)?;
+
+        sqlx::query("INSERT INTO user_identities (user_uid, provider, subject, email) VALUES ($1, $2, $3, $4)")
+            .bind(user.uid)
+            .bind(&identity.provider)
+            .bind(&identity.subject)
+            .bind(&identity.email)
+            .execute(&mut tx)
+            .await
+            .map_err(RepositoryError::from)?;
+
+        tx.commit().await
+            .map_err(RepositoryError::from)?;
+        Ok(user)
+    }
+
+    async fn save_login_state(&self, state_hash: &str, provider: &str, code_verifier: &str, nonce: &str, expires_at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO oidc_login_states (state_hash, provider, code_verifier, nonce, expires_at)
+             VALUES ($1, $2, $3, $4, $5)"
+        )
+        .bind(state_hash)
+        .bind(provider)
+        .bind(code_verifier)
+        .bind(nonce)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+        .map_err(|e| {
+            error!("Database error in save_login_state: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    async fn consume_login_state(&self, state_hash: &str) -> Result<Option<OidcLoginState>, RepositoryError> {
+        sqlx::query_as::<_, OidcLoginState>(
+            "UPDATE oidc_login_states SET used_at = NOW()
+             WHERE state_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+             RETURNING *"
+        )
+        .bind(state_hash)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            error!("Database error in consume_login_state: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+}
@@ -0,0 +1,107 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use crate::models::login_attempt::{LockoutPolicy, LoginAttempts};
+use shared::errors::repository_error::RepositoryError;
+use log::error;
+
+#[async_trait::async_trait]
+pub trait LoginAttemptRepository: Send + Sync {
+    async fn get(&self, key: &str) -> Result<Option<LoginAttempts>, RepositoryError>;
+    // Counts a failure, starting over when the previous one is older than the policy window, and
+    // locks the key in the same write once it is over its allowance.
+    async fn record_failure(&self, key: &str, policy: &LockoutPolicy) -> Result<LoginAttempts, RepositoryError>;
+    async fn reset(&self, key: &str) -> Result<(), RepositoryError>;
+    // Drops counters whose last failure is older than `failed_before` and that are no longer locked.
+    async fn purge(&self, failed_before: DateTime<Utc>) -> Result<u64, RepositoryError>;
+}
+
+pub struct PgLoginAttemptRepository {
+    pub pool: PgPool,
+}
+
+impl PgLoginAttemptRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+// ON CONFLICT can't read back the count it has just set, so the lockout is worked out from the
+// count expression itself.
+fn lockout_until(failures: &str) -> String {
+    format!(
+        "CASE WHEN {failures} > $3
+             THEN NOW() + LEAST($4 * POWER(2, LEAST({failures} - $3 - 1, 30)), $5) * INTERVAL '1 second'
+         END"
+    )
+}
+
+#[async_trait::async_trait]
+impl LoginAttemptRepository for PgLoginAttemptRepository {
+    async fn get(&self, key: &str) -> Result<Option<LoginAttempts>, RepositoryError> {
+        sqlx::query_as::<_, LoginAttempts>("SELECT * FROM login_attempts WHERE key = $1")
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| {
+                error!("Database error in get login attempts: {}", e);
+                RepositoryError::from(e)
+            })
+    }
+
+    async fn record_failure(&self, key: &str, policy: &LockoutPolicy) -> Result<LoginAttempts, RepositoryError> {
+        let failures = "CASE
+                WHEN login_attempts.last_failure_at < NOW() - $2 * INTERVAL '1 second' THEN 1
+                ELSE login_attempts.failures + 1
+            END";
+        let query = format!(
+            "INSERT INTO login_attempts (key, failures, last_failure_at, locked_until)
+             VALUES ($1, 1, NOW(), {})
+             ON CONFLICT (key) DO UPDATE SET
+                 failures = {failures},
+                 last_failure_at = NOW(),
+                 locked_until = COALESCE({}, login_attempts.locked_until)
+             RETURNING *",
+            lockout_until("1"),
+            lockout_until(failures),
+        );
+        sqlx::query_as::<_, LoginAttempts>(&query)
+            .bind(key)
+            .bind(policy.window.num_seconds() as f64)
+            .bind(policy.max_attempts)
+            .bind(policy.base_lockout.num_seconds() as f64)
+            .bind(policy.max_lockout.num_seconds() as f64)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| {
+                error!("Database error in record_failure: {}", e);
+                RepositoryError::from(e)
+            })
+    }
+
+    async fn reset(&self, key: &str) -> Result<(), RepositoryError> {
+        sqlx::query("DELETE FROM login_attempts WHERE key = $1")
+            .bind(key)
+            .execute(&self.pool)
+            .await
+            .map(|_| ())
+            .map_err(|e| {
+                error!("Database error in reset: {}", e);
+                RepositoryError::from(e)
+            })
+    }
+
+    async fn purge(&self, failed_before: DateTime<Utc>) -> Result<u64, RepositoryError> {
+        sqlx::query(
+            "DELETE FROM login_attempts
+             WHERE last_failure_at < $1 AND (locked_until IS NULL OR locked_until < NOW())"
+        )
+        .bind(failed_before)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(|e| {
+            error!("Database error in purge login attempts: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+}
@@ -0,0 +1,167 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::models::mfa::UserTotp;
+use shared::errors::repository_error::RepositoryError;
+use log::{info, error};
+
+#[async_trait::async_trait]
+pub trait MfaRepository {
+    async fn get_totp(&self, user_uid: &Uuid) -> Result<Option<UserTotp>, RepositoryError>;
+    async fn save_pending_totp(&self, user_uid: &Uuid, secret: &str) -> Result<(), RepositoryError>;
+    async fn enable_totp(&self, user_uid: &Uuid, step: i64, recovery_code_hashes: &[String]) -> Result<(), RepositoryError>;
+    async fn use_totp_step(&self, user_uid: &Uuid, step: i64) -> Result<bool, RepositoryError>;
+    async fn use_recovery_code(&self, user_uid: &Uuid, code_hash: &str) -> Result<bool, RepositoryError>;
+    async fn delete_totp(&self, user_uid: &Uuid) -> Result<(), RepositoryError>;
+    async fn has_passkeys(&self, user_uid: &Uuid) -> Result<bool, RepositoryError>;
+}
+
+pub struct PgMfaRepository {
+    pub pool: PgPool,
+}
+
+impl PgMfaRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl MfaRepository for PgMfaRepository {
+    async fn get_totp(&self, user_uid: &Uuid) -> Result<Option<UserTotp>, RepositoryError> {
+        sqlx::query_as::<_, UserTotp>("SELECT * FROM user_totp WHERE user_uid = $1")
+            .bind(user_uid)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| {
+                error!("Database error in get_totp: {}", e);
+                RepositoryError::from(e)
+            })
+    }
+
+    async fn save_pending_totp(&self, user_uid: &Uuid, secret: &str) -> Result<(), RepositoryError> {
+        info!("Starting TOTP enrollment for user {}", user_uid);
+        sqlx::query(
+            "INSERT INTO user_totp (user_uid, secret) VALUES ($1, $2)
+             ON CONFLICT (user_uid) DO UPDATE SET secret = EXCLUDED.secret, last_used_step = NULL, created_at = NOW()
+             WHERE user_totp.enabled_at IS NULL"
+        )
+        .bind(user_uid)
+        .bind(secret)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+        .map_err(|e| {
+            error!("Database error in save_pending_totp: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    async fn enable_totp(&self, user_uid: &Uuid, step: i64, recovery_code_hashes: &[String]) -> Result<(), RepositoryError> {
+        info!("Enabling TOTP for user {}", user_uid);
+        let mut tx = self.pool.begin().await
+            .map_err(RepositoryError::from)?;
+
+        sqlx::query("UPDATE user_totp SET enabled_at = NOW(), last_used_step = $2 WHERE user_uid = $1")
+            .bind(user_uid)
+            .bind(step)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                error!("Database error in enable_totp: {}", e);
+                RepositoryError::from(e)
+            })?;
+
+        sqlx::query("DELETE FROM mfa_recovery_codes WHERE user_uid = $1")
+            .bind(user_uid)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                error!("Database error in enable_totp: {}", e);
+                RepositoryError::from(e)
+            })?;
+        for code_hash in recovery_code_hashes {
+            sqlx::query("INSERT INTO mfa_recovery_codes (user_uid, code_hash) VALUES ($1, $2)")
+                .bind(user_uid)
+                .bind(code_hash)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| {
+                    error!("Database error in enable_totp: {}", e);
+                    RepositoryError::from(e)
+                })?;
+        }
+
+        tx.commit().await
+            .map_err(RepositoryError::from)
+    }
+
+    // Only moves forward, so a code can't be replayed within its validity window.
+    async fn use_totp_step(&self, user_uid: &Uuid, step: i64) -> Result<bool, RepositoryError> {
+        sqlx::query(
+            "UPDATE user_totp SET last_used_step = $2
+             WHERE user_uid = $1 AND (last_used_step IS NULL OR last_used_step < $2)"
+        )
+        .bind(user_uid)
+        .bind(step)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected() > 0)
+        .map_err(|e| {
+            error!("Database error in use_totp_step: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    async fn use_recovery_code(&self, user_uid: &Uuid, code_hash: &str) -> Result<bool, RepositoryError> {
+        sqlx::query(
+            "UPDATE mfa_recovery_codes SET used_at = NOW()
+             WHERE user_uid = $1 AND code_hash = $2 AND used_at IS NULL"
+        )
+        .bind(user_uid)
+        .bind(code_hash)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected() > 0)
+        .map_err(|e| {
+            error!("Database error in use_recovery_code: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    async fn delete_totp(&self, user_uid: &Uuid) -> Result<(), RepositoryError> {
+        info!("Disabling TOTP for user {}", user_uid);
+        let mut tx = self.pool.begin().await
+            .map_err(RepositoryError::from)?;
+
+        sqlx::query("DELETE FROM user_totp WHERE user_uid = $1")
+            .bind(user_uid)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                error!("Database error in delete_totp: {}", e);
+                RepositoryError::from(e)
+            })?;
+        sqlx::query("DELETE FROM mfa_recovery_codes WHERE user_uid = $1")
+            .bind(user_uid)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                error!("Database error in delete_totp: {}", e);
+                RepositoryError::from(e)
+            })?;
+
+        tx.commit().await
+            .map_err(RepositoryError::from)
+    }
+
+    async fn has_passkeys(&self, user_uid: &Uuid) -> Result<bool, RepositoryError> {
+        sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM passkeys WHERE user_uid = $1)")
+            .bind(user_uid)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| {
+                error!("Database error in has_passkeys: {}", e);
+                RepositoryError::from(e)
+            })
+    }
+}
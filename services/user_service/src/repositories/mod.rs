@@ -1 +1,9 @@
-pub mod user_repository;
\ No newline at end of file
+pub mod user_repository;
+pub mod token_repository;
+pub mod login_attempt_repository;
+pub mod mfa_repository;
+pub mod identity_repository;
+pub mod api_key_repository;
+pub mod passkey_repository;
+#[cfg(test)]
+pub mod fakes;
\ No newline at end of file
@@ -0,0 +1,134 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use crate::models::passkey::Passkey;
+use shared::errors::repository_error::RepositoryError;
+use log::error;
+
+#[async_trait::async_trait]
+pub trait PasskeyRepository {
+    async fn save_challenge(&self, challenge_hash: &str, user_uid: Option<&Uuid>, purpose: &str, expires_at: DateTime<Utc>) -> Result<(), RepositoryError>;
+    async fn consume_challenge(&self, challenge_hash: &str, purpose: &str) -> Result<Option<Option<Uuid>>, RepositoryError>;
+    async fn create(&self, passkey: &Passkey) -> Result<(), RepositoryError>;
+    async fn get_by_credential_id(&self, credential_id: &str) -> Result<Option<Passkey>, RepositoryError>;
+    async fn get_for_user(&self, user_uid: &Uuid) -> Result<Vec<Passkey>, RepositoryError>;
+    async fn record_use(&self, uid: &Uuid, sign_count: i64) -> Result<(), RepositoryError>;
+    async fn delete(&self, user_uid: &Uuid, uid: &Uuid) -> Result<bool, RepositoryError>;
+}
+
+pub struct PgPasskeyRepository {
+    pub pool: PgPool,
+}
+
+impl PgPasskeyRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl PasskeyRepository for PgPasskeyRepository {
+    async fn save_challenge(&self, challenge_hash: &str, user_uid: Option<&Uuid>, purpose: &str, expires_at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO webauthn_challenges (challenge_hash, user_uid, purpose, expires_at) VALUES ($1, $2, $3, $4)"
+        )
+        .bind(challenge_hash)
+        .bind(user_uid)
+        .bind(purpose)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+        .map_err(|e| {
+            error!("Database error in save_challenge: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    // Returns the user the challenge was issued for, if any, or None when it is unknown, used or expired.
+    async fn consume_challenge(&self, challenge_hash: &str, purpose: &str) -> Result<Option<Option<Uuid>>, RepositoryError> {
+        sqlx::query_scalar::<_, Option<Uuid>>(
+            "UPDATE webauthn_challenges SET used_at = NOW()
+             WHERE challenge_hash = $1 AND purpose = $2 AND used_at IS NULL AND expires_at > NOW()
+             RETURNING user_uid"
+        )
+        .bind(challenge_hash)
+        .bind(purpose)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            error!("Database error in consume_challenge: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    async fn create(&self, passkey: &Passkey) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO passkeys (uid, user_uid, credential_id, public_key, algorithm, sign_count, name, created_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"
+        )
+        .bind(passkey.uid)
+        .bind(passkey.user_uid)
+        .bind(&passkey.credential_id)
+        .bind(&passkey.public_key)
+        .bind(passkey.algorithm)
+        .bind(passkey.sign_count)
+        .bind(&passkey.name)
+        .bind(passkey.created_at)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+        .map_err(|e| {
+            error!("Database error in create: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    async fn get_by_credential_id(&self, credential_id: &str) -> Result<Option<Passkey>, RepositoryError> {
+        sqlx::query_as::<_, Passkey>("SELECT * FROM passkeys WHERE credential_id = $1")
+            .bind(credential_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| {
+                error!("Database error in get_by_credential_id: {}", e);
+                RepositoryError::from(e)
+            })
+    }
+
+    async fn get_for_user(&self, user_uid: &Uuid) -> Result<Vec<Passkey>, RepositoryError> {
+        sqlx::query_as::<_, Passkey>("SELECT * FROM passkeys WHERE user_uid = $1 ORDER BY created_at")
+            .bind(user_uid)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| {
+                error!("Database error in get_for_user: {}", e);
+                RepositoryError::from(e)
+            })
+    }
+
+    async fn record_use(&self, uid: &Uuid, sign_count: i64) -> Result<(), RepositoryError> {
+        sqlx::query("UPDATE passkeys SET sign_count = $2, last_used_at = NOW() WHERE uid = $1")
+            .bind(uid)
+            .bind(sign_count)
+            .execute(&self.pool)
+            .await
+            .map(|_| ())
+            .map_err(|e| {
+                error!("Database error in record_use: {}", e);
+                RepositoryError::from(e)
+            })
+    }
+
+    async fn delete(&self, user_uid: &Uuid, uid: &Uuid) -> Result<bool, RepositoryError> {
+        sqlx::query("DELETE FROM passkeys WHERE uid = $1 AND user_uid = $2")
+            .bind(uid)
+            .bind(user_uid)
+            .execute(&self.pool)
+            .await
+            .map(|result| result.rows_affected() > 0)
+            .map_err(|e| {
+                error!("Database error in delete: {}", e);
+                RepositoryError::from(e)
+            })
+    }
+}
@@ -0,0 +1,540 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+use chrono::{DateTime, SubsecRound, Utc};
+use crate::models::token::{RefreshToken, EmailVerificationToken, PasswordResetToken};
+use crate::models::mfa::MfaChallengeRecord;
+use crate::models::session::Session;
+use shared::errors::repository_error::RepositoryError;
+use log::{info, error};
+
+#[async_trait::async_trait]
+pub trait TokenRepository {
+    async fn create_refresh_token(&self, user_uid: &Uuid, family_uid: &Uuid, token_hash: &str, expires_at: DateTime<Utc>) -> Result<RefreshToken, RepositoryError>;
+    async fn get_refresh_token_by_hash(&self, token_hash: &str) -> Result<Option<RefreshToken>, RepositoryError>;
+    async fn consume_refresh_token(&self, token_hash: &str) -> Result<Option<RefreshToken>, RepositoryError>;
+    async fn revoke_refresh_token_family(&self, family_uid: &Uuid) -> Result<(), RepositoryError>;
+    async fn revoke_token(&self, jti: &Uuid, user_uid: &Uuid, expires_at: DateTime<Utc>) -> Result<(), RepositoryError>;
+    async fn revoke_all_user_tokens(&self, user_uid: &Uuid) -> Result<(), RepositoryError>;
+    async fn is_token_revoked(&self, jti: &Uuid, user_uid: &Uuid, issued_at: DateTime<Utc>, session_uid: Option<Uuid>) -> Result<bool, RepositoryError>;
+    async fn create_email_verification_token(&self, user_uid: &Uuid, email: &str, token_hash: &str, expires_at: DateTime<Utc>) -> Result<(), RepositoryError>;
+    async fn consume_email_verification_token(&self, token_hash: &str) -> Result<Option<EmailVerificationToken>, RepositoryError>;
+    async fn last_email_verification_sent_at(&self, user_uid: &Uuid) -> Result<Option<DateTime<Utc>>, RepositoryError>;
+    async fn invalidate_email_change_tokens(&self, user_uid: &Uuid, current_email: &str) -> Result<(), RepositoryError>;
+    async fn create_password_reset_token(&self, user_uid: &Uuid, token_hash: &str, expires_at: DateTime<Utc>) -> Result<(), RepositoryError>;
+    async fn get_password_reset_token(&self, token_hash: &str) -> Result<Option<PasswordResetToken>, RepositoryError>;
+    async fn consume_password_reset_token(&self, token_hash: &str) -> Result<Option<PasswordResetToken>, RepositoryError>;
+    async fn invalidate_password_reset_tokens(&self, user_uid: &Uuid) -> Result<(), RepositoryError>;
+    async fn create_mfa_challenge(&self, user_uid: &Uuid, token_hash: &str, expires_at: DateTime<Utc>) -> Result<(), RepositoryError>;
+    async fn get_mfa_challenge(&self, token_hash: &str) -> Result<Option<MfaChallengeRecord>, RepositoryError>;
+    async fn record_mfa_challenge_failure(&self, token_hash: &str, max_attempts: i32) -> Result<(), RepositoryError>;
+    async fn consume_mfa_challenge(&self, token_hash: &str) -> Result<Option<MfaChallengeRecord>, RepositoryError>;
+    async fn create_session(&self, session: &Session) -> Result<(), RepositoryError>;
+    async fn touch_session(&self, session_uid: &Uuid, ip_address: &str, expires_at: DateTime<Utc>) -> Result<(), RepositoryError>;
+    async fn get_active_sessions(&self, user_uid: &Uuid) -> Result<Vec<Session>, RepositoryError>;
+    async fn revoke_session(&self, user_uid: &Uuid, session_uid: &Uuid) -> Result<bool, RepositoryError>;
+    async fn purge_expired(&self, revocations_before: DateTime<Utc>) -> Result<u64, RepositoryError>;
+}
+
+// Access tokens carry `iat` in whole seconds, so the cutoff is truncated to match: a token issued
+// later in the same second as the revocation stays valid. Ones issued earlier in that second are
+// still rejected, since revoking everything also revokes their sessions.
+pub fn revocation_cutoff(now: DateTime<Utc>) -> DateTime<Utc> {
+    now.trunc_subsecs(0)
+}
+
+pub struct PgTokenRepository {
+    pub pool: PgPool,
+}
+
+impl PgTokenRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl TokenRepository for PgTokenRepository {
+    async fn create_refresh_token(&self, user_uid: &Uuid, family_uid: &Uuid, token_hash: &str, expires_at: DateTime<Utc>) -> Result<RefreshToken, RepositoryError> {
+        info!("Creating refresh token for user {} in family {}", user_uid, family_uid);
+        sqlx::query_as::<_, RefreshToken>(
+            "INSERT INTO refresh_tokens (user_uid, family_uid, token_hash, expires_at)
+             VALUES ($1, $2, $3, $4)
+             RETURNING *"
+        )
+        .bind(user_uid)
+        .bind(family_uid)
+        .bind(token_hash)
+        .bind(expires_at)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| {
+            error!("Database error in create_refresh_token: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    async fn get_refresh_token_by_hash(&self, token_hash: &str) -> Result<Option<RefreshToken>, RepositoryError> {
+        sqlx::query_as::<_, RefreshToken>("SELECT * FROM refresh_tokens WHERE token_hash = $1")
+            .bind(token_hash)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| {
+                error!("Database error in get_refresh_token_by_hash: {}", e);
+                RepositoryError::from(e)
+            })
+    }
+
+    async fn consume_refresh_token(&self, token_hash: &str) -> Result<Option<RefreshToken>, RepositoryError> {
+        sqlx::query_as::<_, RefreshToken>(
+            "UPDATE refresh_tokens SET revoked_at = NOW()
+             WHERE token_hash = $1 AND revoked_at IS NULL
+             RETURNING *"
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            error!("Database error in consume_refresh_token: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    async fn revoke_refresh_token_family(&self, family_uid: &Uuid) -> Result<(), RepositoryError> {
+        info!("Revoking refresh token family {}", family_uid);
+        sqlx::query(
+            "UPDATE refresh_tokens SET revoked_at = NOW()
+             WHERE family_uid = $1 AND revoked_at IS NULL"
+        )
+        .bind(family_uid)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            error!("Database error in revoke_refresh_token_family: {}", e);
+            RepositoryError::from(e)
+        })?;
+
+        Ok(())
+    }
+
+    async fn revoke_token(&self, jti: &Uuid, user_uid: &Uuid, expires_at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        info!("Revoking token {} of user {}", jti, user_uid);
+        sqlx::query(
+            "INSERT INTO revoked_tokens (jti, user_uid, expires_at)
+             VALUES ($1, $2, $3)
+             ON CONFLICT (jti) DO NOTHING"
+        )
+        .bind(jti)
+        .bind(user_uid)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            error!("Database error in revoke_token: {}", e);
+            RepositoryError::from(e)
+        })?;
+
+        Ok(())
+    }
+
+    async fn revoke_all_user_tokens(&self, user_uid: &Uuid) -> Result<(), RepositoryError> {
+        info!("Revoking all tokens of user {}", user_uid);
+        let mut tx = self.pool.begin().await
+            .map_err(RepositoryError::from)?;
+
+        sqlx::query(
+            "INSERT INTO user_token_revocations (user_uid, revoked_before)
+             VALUES ($1, $2)
+             ON CONFLICT (user_uid) DO UPDATE SET revoked_before = EXCLUDED.revoked_before"
+        )
+        .bind(user_uid)
+        .bind(revocation_cutoff(Utc::now()))
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            error!("Database error in revoke_all_user_tokens: {}", e);
+            RepositoryError::from(e)
+        })?;
+
+        sqlx::query(
+            "UPDATE refresh_tokens SET revoked_at = NOW()
+             WHERE user_uid = $1 AND revoked_at IS NULL"
+        )
+        .bind(user_uid)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            error!("Database error in revoke_all_user_tokens: {}", e);
+            RepositoryError::from(e)
+        })?;
+
+        sqlx::query(
+            "UPDATE sessions SET revoked_at = NOW()
+             WHERE user_uid = $1 AND revoked_at IS NULL"
+        )
+        .bind(user_uid)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            error!("Database error in revoke_all_user_tokens: {}", e);
+            RepositoryError::from(e)
+        })?;
+
+        tx.commit().await
+            .map_err(RepositoryError::from)
+    }
+
+    async fn is_token_revoked(&self, jti: &Uuid, user_uid: &Uuid, issued_at: DateTime<Utc>, session_uid: Option<Uuid>) -> Result<bool, RepositoryError> {
+        sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS(SELECT 1 FROM revoked_tokens WHERE jti = $1)
+                 OR EXISTS(
+                     SELECT 1 FROM user_token_revocations
+                     WHERE user_uid = $2 AND revoked_before > $3
+                 )
+                 OR EXISTS(SELECT 1 FROM sessions WHERE uid = $4 AND revoked_at IS NOT NULL)"
+        )
+        .bind(jti)
+        .bind(user_uid)
+        .bind(issued_at)
+        .bind(session_uid)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| {
+            error!("Database error in is_token_revoked: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    async fn create_email_verification_token(&self, user_uid: &Uuid, email: &str, token_hash: &str, expires_at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        info!("Creating email verification token for user {}", user_uid);
+        sqlx::query(
+            "INSERT INTO email_verification_tokens (user_uid, email, token_hash, expires_at)
+             VALUES ($1, $2, $3, $4)"
+        )
+        .bind(user_uid)
+        .bind(email)
+        .bind(token_hash)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+        .map_err(|e| {
+            error!("Database error in create_email_verification_token: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    async fn consume_email_verification_token(&self, token_hash: &str) -> Result<Option<EmailVerificationToken>, RepositoryError> {
+        sqlx::query_as::<_, EmailVerificationToken>(
+            "UPDATE email_verification_tokens SET used_at = NOW()
+             WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+             RETURNING *"
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            error!("Database error in consume_email_verification_token: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    async fn last_email_verification_sent_at(&self, user_uid: &Uuid) -> Result<Option<DateTime<Utc>>, RepositoryError> {
+        sqlx::query_scalar::<_, Option<DateTime<Utc>>>(
+            "SELECT MAX(created_at) FROM email_verification_tokens WHERE user_uid = $1"
+        )
+        .bind(user_uid)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| {
+            error!("Database error in last_email_verification_sent_at: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    // Links for any address other than the current one are pending email changes.
+    async fn invalidate_email_change_tokens(&self, user_uid: &Uuid, current_email: &str) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "UPDATE email_verification_tokens SET used_at = NOW()
+             WHERE user_uid = $1 AND used_at IS NULL AND LOWER(email) <> LOWER($2)"
+        )
+        .bind(user_uid)
+        .bind(current_email)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+        .map_err(|e| {
+            error!("Database error in invalidate_email_change_tokens: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    async fn create_password_reset_token(&self, user_uid: &Uuid, token_hash: &str, expires_at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        info!("Creating password reset token for user {}", user_uid);
+        sqlx::query(
+            "INSERT INTO password_reset_tokens (user_uid, token_hash, expires_at)
+             VALUES ($1, $2, $3)"
+        )
+        .bind(user_uid)
+        .bind(token_hash)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+        .map_err(|e| {
+            error!("Database error in create_password_reset_token: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    async fn get_password_reset_token(&self, token_hash: &str) -> Result<Option<PasswordResetToken>, RepositoryError> {
+        sqlx::query_as::<_, PasswordResetToken>(
+            "SELECT * FROM password_reset_tokens
+             WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()"
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            error!("Database error in get_password_reset_token: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    async fn consume_password_reset_token(&self, token_hash: &str) -> Result<Option<PasswordResetToken>, RepositoryError> {
+        sqlx::query_as::<_, PasswordResetToken>(
+            "UPDATE password_reset_tokens SET used_at = NOW()
+             WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+             RETURNING *"
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            error!("Database error in consume_password_reset_token: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    async fn invalidate_password_reset_tokens(&self, user_uid: &Uuid) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "UPDATE password_reset_tokens SET used_at = NOW()
+             WHERE user_uid = $1 AND used_at IS NULL"
+        )
+        .bind(user_uid)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+        .map_err(|e| {
+            error!("Database error in invalidate_password_reset_tokens: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    async fn create_mfa_challenge(&self, user_uid: &Uuid, token_hash: &str, expires_at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        info!("Creating MFA challenge for user {}", user_uid);
+        sqlx::query(
+            "INSERT INTO mfa_challenges (user_uid, token_hash, expires_at)
+             VALUES ($1, $2, $3)"
+        )
+        .bind(user_uid)
+        .bind(token_hash)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+        .map_err(|e| {
+            error!("Database error in create_mfa_challenge: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    async fn get_mfa_challenge(&self, token_hash: &str) -> Result<Option<MfaChallengeRecord>, RepositoryError> {
+        sqlx::query_as::<_, MfaChallengeRecord>(
+            "SELECT * FROM mfa_challenges
+             WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()"
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            error!("Database error in get_mfa_challenge: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    // The challenge is burned once it reaches `max_attempts` wrong codes, forcing a new password login.
+    async fn record_mfa_challenge_failure(&self, token_hash: &str, max_attempts: i32) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "UPDATE mfa_challenges
+             SET failed_attempts = failed_attempts + 1,
+                 used_at = CASE WHEN failed_attempts + 1 >= $2 THEN NOW() ELSE used_at END
+             WHERE token_hash = $1 AND used_at IS NULL"
+        )
+        .bind(token_hash)
+        .bind(max_attempts)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+        .map_err(|e| {
+            error!("Database error in record_mfa_challenge_failure: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    async fn consume_mfa_challenge(&self, token_hash: &str) -> Result<Option<MfaChallengeRecord>, RepositoryError> {
+        sqlx::query_as::<_, MfaChallengeRecord>(
+            "UPDATE mfa_challenges SET used_at = NOW()
+             WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+             RETURNING *"
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            error!("Database error in consume_mfa_challenge: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    async fn create_session(&self, session: &Session) -> Result<(), RepositoryError> {
+        info!("Creating session {} for user {}", session.uid, session.user_uid);
+        sqlx::query(
+            "INSERT INTO sessions (uid, user_uid, device_name, user_agent, ip_address, expires_at)
+             VALUES ($1, $2, $3, $4, $5, $6)"
+        )
+        .bind(session.uid)
+        .bind(session.user_uid)
+        .bind(&session.device_name)
+        .bind(&session.user_agent)
+        .bind(&session.ip_address)
+        .bind(session.expires_at)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+        .map_err(|e| {
+            error!("Database error in create_session: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    async fn touch_session(&self, session_uid: &Uuid, ip_address: &str, expires_at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "UPDATE sessions SET last_used_at = NOW(), ip_address = $2, expires_at = $3
+             WHERE uid = $1 AND revoked_at IS NULL"
+        )
+        .bind(session_uid)
+        .bind(ip_address)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+        .map_err(|e| {
+            error!("Database error in touch_session: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    async fn get_active_sessions(&self, user_uid: &Uuid) -> Result<Vec<Session>, RepositoryError> {
+        sqlx::query_as::<_, Session>(
+            "SELECT * FROM sessions
+             WHERE user_uid = $1 AND revoked_at IS NULL AND expires_at > NOW()
+             ORDER BY last_used_at DESC"
+        )
+        .bind(user_uid)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            error!("Database error in get_active_sessions: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    async fn revoke_session(&self, user_uid: &Uuid, session_uid: &Uuid) -> Result<bool, RepositoryError> {
+        info!("Revoking session {} of user {}", session_uid, user_uid);
+        let mut tx = self.pool.begin().await
+            .map_err(RepositoryError::from)?;
+
+        let revoked = sqlx::query(
+            "UPDATE sessions SET revoked_at = NOW()
+             WHERE uid = $1 AND user_uid = $2 AND revoked_at IS NULL"
+        )
+        .bind(session_uid)
+        .bind(user_uid)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            error!("Database error in revoke_session: {}", e);
+            RepositoryError::from(e)
+        })?
+        .rows_affected() > 0;
+
+        sqlx::query(
+            "UPDATE refresh_tokens SET revoked_at = NOW()
+             WHERE family_uid = $1 AND user_uid = $2 AND revoked_at IS NULL"
+        )
+        .bind(session_uid)
+        .bind(user_uid)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            error!("Database error in revoke_session: {}", e);
+            RepositoryError::from(e)
+        })?;
+
+        tx.commit().await
+            .map_err(RepositoryError::from)?;
+        Ok(revoked)
+    }
+
+    // Revoked tokens only need remembering until they expire, and a user-wide cutoff only until
+    // every access token issued before it has.
+    async fn purge_expired(&self, revocations_before: DateTime<Utc>) -> Result<u64, RepositoryError> {
+        let mut tx = self.pool.begin().await
+            .map_err(RepositoryError::from)?;
+
+        let revoked_tokens = sqlx::query("DELETE FROM revoked_tokens WHERE expires_at < NOW()")
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                error!("Database error in purge_expired: {}", e);
+                RepositoryError::from(e)
+            })?
+            .rows_affected();
+
+        let revocations = sqlx::query("DELETE FROM user_token_revocations WHERE revoked_before < $1")
+            .bind(revocations_before)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                error!("Database error in purge_expired: {}", e);
+                RepositoryError::from(e)
+            })?
+            .rows_affected();
+
+        // A revoked session is kept for as long as access tokens issued in it can still be presented.
+        let sessions = sqlx::query_scalar::<_, Uuid>(
+            "DELETE FROM sessions WHERE expires_at < $1 OR revoked_at < $1 RETURNING uid"
+        )
+        .bind(revocations_before)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| {
+            error!("Database error in purge_expired: {}", e);
+            RepositoryError::from(e)
+        })?;
+
+        let refresh_tokens = sqlx::query("DELETE FROM refresh_tokens WHERE expires_at < NOW() OR family_uid = ANY($1)")
+            .bind(&sessions)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                error!("Database error in purge_expired: {}", e);
+                RepositoryError::from(e)
+            })?
+            .rows_affected();
+
+        tx.commit().await
+            .map_err(RepositoryError::from)?;
+        Ok(revoked_tokens + revocations + sessions.len() as u64 + refresh_tokens)
+    }
+}
@@ -1,15 +1,27 @@
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, QueryBuilder};
 use uuid::Uuid;
 use crate::models::user::{User, UserDTO};
-use crate::errors::service_error::ServiceError;
+use crate::models::profile::UpdateProfileDTO;
+use crate::models::directory::{DirectoryUser, UserCursor, UserSearch, UserSort};
+use crate::models::role::{Role, DEFAULT_ROLE};
+use shared::errors::repository_error::RepositoryError;
 use log::{info, error};
 
 #[async_trait::async_trait]
 pub trait UserRepository {
-    async fn get_all(&self) -> Result<Vec<User>, ServiceError>;
-    async fn get_by_id(&self, uid: &Uuid) -> Result<User, ServiceError>;
-    async fn get_by_email(&self, email: &str) -> Result<User, ServiceError>;
-    async fn create(&self, user: &UserDTO) -> Result<User, ServiceError>;
+    async fn get_all(&self, search: &UserSearch) -> Result<Vec<DirectoryUser>, RepositoryError>;
+    async fn get_by_id(&self, uid: &Uuid) -> Result<User, RepositoryError>;
+    async fn get_by_email(&self, email: &str) -> Result<User, RepositoryError>;
+    async fn get_by_login(&self, identifier: &str) -> Result<User, RepositoryError>;
+    async fn create(&self, user: &UserDTO) -> Result<User, RepositoryError>;
+    async fn update_password(&self, uid: &Uuid, password_hash: &str) -> Result<(), RepositoryError>;
+    async fn update_email(&self, uid: &Uuid, email: &str) -> Result<User, RepositoryError>;
+    async fn update_profile(&self, uid: &Uuid, profile: &UpdateProfileDTO) -> Result<User, RepositoryError>;
+    async fn mark_email_verified(&self, uid: &Uuid, email: &str) -> Result<bool, RepositoryError>;
+    async fn get_roles(&self, user_uid: &Uuid) -> Result<Vec<Role>, RepositoryError>;
+    async fn role_exists(&self, role: &str) -> Result<bool, RepositoryError>;
+    async fn add_role(&self, user_uid: &Uuid, role: &str) -> Result<(), RepositoryError>;
+    async fn remove_role(&self, user_uid: &Uuid, role: &str) -> Result<bool, RepositoryError>;
 }
 
 pub struct PgUserRepository {
@@ -24,18 +36,68 @@ impl PgUserRepository {
 
 #[async_trait::async_trait]
 impl UserRepository for PgUserRepository {
-    async fn get_all(&self) -> Result<Vec<User>, ServiceError> {
-        info!("Executing get_all query");
-        sqlx::query_as::<_, User>("SELECT * FROM users")
+    // Prefix matches on username or display name rank above fuzzy ones. Fuzzy means pg_trgm's
+    // similarity (`%`) or word similarity (`<%`), so typos and single words of a display name
+    // both match; the trigram indexes serve all of these.
+    async fn get_all(&self, search: &UserSearch) -> Result<Vec<DirectoryUser>, RepositoryError> {
+        info!("Executing get_all query: {:?}", search);
+        let mut builder = QueryBuilder::<Postgres>::new(
+            "SELECT * FROM (
+                SELECT uid, username, display_name, avatar_url, bio, status_text, status_expires_at,
+                       locale, time_zone, created_at, "
+        );
+        match &search.query {
+            Some(query) => {
+                let query = query.to_lowercase();
+                let prefix = format!("{}%", escape_like(&query));
+                builder.push("(CASE WHEN LOWER(username) LIKE ").push_bind(prefix.clone())
+                    .push(" OR LOWER(display_name) LIKE ").push_bind(prefix.clone())
+                    .push(" THEN 1 ELSE 0 END + GREATEST(word_similarity(").push_bind(query.clone())
+                    .push(", LOWER(username)), word_similarity(").push_bind(query.clone())
+                    .push(", LOWER(display_name))))::real AS rank FROM users WHERE LOWER(username) LIKE ").push_bind(prefix.clone())
+                    .push(" OR LOWER(display_name) LIKE ").push_bind(prefix)
+                    .push(" OR LOWER(username) % ").push_bind(query.clone())
+                    .push(" OR LOWER(display_name) % ").push_bind(query.clone())
+                    .push(" OR ").push_bind(query.clone()).push(" <% LOWER(username)")
+                    .push(" OR ").push_bind(query).push(" <% LOWER(display_name)");
+            }
+            None => {
+                builder.push("0::real AS rank FROM users");
+            }
+        }
+        builder.push(") AS matches");
+
+        match &search.after {
+            Some(UserCursor::Username { username }) => {
+                builder.push(" WHERE LOWER(username) > ").push_bind(username.clone());
+            }
+            Some(UserCursor::Newest { created_at, uid }) => {
+                builder.push(" WHERE (created_at, uid) < (").push_bind(*created_at).push(", ").push_bind(*uid).push(")");
+            }
+            Some(UserCursor::Relevance { rank, username }) => {
+                builder.push(" WHERE rank < ").push_bind(*rank)
+                    .push(" OR (rank = ").push_bind(*rank)
+                    .push(" AND LOWER(username) > ").push_bind(username.clone()).push(")");
+            }
+            None => {}
+        }
+        builder.push(match search.sort {
+            UserSort::Username => " ORDER BY LOWER(username)",
+            UserSort::Newest => " ORDER BY created_at DESC, uid DESC",
+            UserSort::Relevance => " ORDER BY rank DESC, LOWER(username)",
+        });
+        builder.push(" LIMIT ").push_bind(search.limit);
+
+        builder.build_query_as::<DirectoryUser>()
             .fetch_all(&self.pool)
             .await
             .map_err(|e| {
                 error!("Database error in get_all: {}", e);
-                ServiceError::internal_error(&format!("Database error: {}", e))
+                RepositoryError::from(e)
             })
     }
     
-    async fn get_by_id(&self, uid: &Uuid) -> Result<User, ServiceError> {
+    async fn get_by_id(&self, uid: &Uuid) -> Result<User, RepositoryError> {
         info!("Fetching user by ID: {}", uid);
         sqlx::query_as::<_, User>("SELECT * FROM users WHERE uid = $1")
             .bind(uid)
@@ -43,27 +105,49 @@ impl UserRepository for PgUserRepository {
             .await
             .map_err(|e| {
                 error!("Database error in get_by_id: {}", e);
-                ServiceError::internal_error(&format!("Database error: {}", e))
+                RepositoryError::from(e)
             })?
-            .ok_or_else(|| ServiceError::not_found(&format!("User with uid {} not found", uid)))
+            .ok_or_else(|| RepositoryError::NotFound(format!("User with uid {} not found", uid)))
     }
     
-    async fn get_by_email(&self, email: &str) -> Result<User, ServiceError> {
+    // Emails and usernames are compared case-insensitively, matching the unique LOWER() indexes.
+    async fn get_by_email(&self, email: &str) -> Result<User, RepositoryError> {
         info!("Fetching user by email: {}", email);
-        sqlx::query_as::<_, User>("SELECT * FROM users WHERE email = $1")
-            .bind(email)
+        sqlx::query_as::<_, User>("SELECT * FROM users WHERE LOWER(email) = LOWER($1)")
+            .bind(email.trim())
             .fetch_optional(&self.pool)
             .await
             .map_err(|e| {
                 error!("Database error in get_by_email: {}", e);
-                ServiceError::internal_error(&format!("Database error: {}", e))
+                RepositoryError::from(e)
+            })?
+            .ok_or_else(|| RepositoryError::NotFound(format!("User with email {} not found", email)))
+    }
+
+    // Usernames can't contain `@`, so anything with one is looked up as an email.
+    async fn get_by_login(&self, identifier: &str) -> Result<User, RepositoryError> {
+        let identifier = identifier.trim();
+        if identifier.contains('@') {
+            return self.get_by_email(identifier).await;
+        }
+        info!("Fetching user by username: {}", identifier);
+        sqlx::query_as::<_, User>("SELECT * FROM users WHERE LOWER(username) = LOWER($1)")
+            .bind(identifier)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| {
+                error!("Database error in get_by_login: {}", e);
+                RepositoryError::from(e)
             })?
-            .ok_or_else(|| ServiceError::not_found(&format!("User with email {} not found", email)))
+            .ok_or_else(|| RepositoryError::NotFound(format!("User with username {} not found", identifier)))
     }
 
-    async fn create(&self, user_dto: &UserDTO) -> Result<User, ServiceError> {      
+    async fn create(&self, user_dto: &UserDTO) -> Result<User, RepositoryError> {      
         info!("Creating user with email: {}", user_dto.email);  
-        sqlx::query_as::<_, User>(
+        let mut tx = self.pool.begin().await
+            .map_err(RepositoryError::from)?;
+
+        let user = sqlx::query_as::<_, User>(
             "INSERT INTO users (username, email, password_hash, created_at, updated_at) 
              VALUES ($1, $2, $3, NOW(), NOW()) 
              RETURNING *"
@@ -71,9 +155,173 @@ impl UserRepository for PgUserRepository {
         .bind(&user_dto.username)
         .bind(&user_dto.email)
         .bind(&user_dto.password)
-        .fetch_one(&self.pool)
+        .fetch_one(&mut tx)
+        .await
+        .map_err(RepositoryError::from)?;
+
+        sqlx::query("INSERT INTO user_roles (user_uid, role) VALUES ($1, $2)")
+            .bind(user.uid)
+            .bind(DEFAULT_ROLE)
+            .execute(&mut tx)
+            .await
+            .map_err(RepositoryError::from)?;
+
+        tx.commit().await
+            .map_err(RepositoryError::from)?;
+        Ok(user)
+    }
+
+    async fn update_password(&self, uid: &Uuid, password_hash: &str) -> Result<(), RepositoryError> {
+        info!("Updating password of user {}", uid);
+        let result = sqlx::query("UPDATE users SET password_hash = $2 WHERE uid = $1")
+            .bind(uid)
+            .bind(password_hash)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| {
+                error!("Database error in update_password: {}", e);
+                RepositoryError::from(e)
+            })?;
+
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(format!("User with uid {} not found", uid)));
+        }
+        Ok(())
+    }
+
+    async fn update_email(&self, uid: &Uuid, email: &str) -> Result<User, RepositoryError> {
+        info!("Updating email of user {} to {}", uid, email);
+        sqlx::query_as::<_, User>(
+            "UPDATE users SET email = $2, email_verified_at = NOW()
+             WHERE uid = $1
+             RETURNING *"
+        )
+        .bind(uid)
+        .bind(email)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            error!("Database error in update_email: {}", e);
+            RepositoryError::from(e)
+        })?
+        .ok_or_else(|| RepositoryError::NotFound(format!("User with uid {} not found", uid)))
+    }
+
+    // Each field comes with a flag saying whether it was sent, so omitted fields keep their value
+    // and fields sent as null are cleared.
+    async fn update_profile(&self, uid: &Uuid, profile: &UpdateProfileDTO) -> Result<User, RepositoryError> {
+        info!("Updating profile of user {}", uid);
+        sqlx::query_as::<_, User>(
+            "UPDATE users SET
+                 display_name = CASE WHEN $2 THEN $3 ELSE display_name END,
+                 avatar_url = CASE WHEN $4 THEN $5 ELSE avatar_url END,
+                 bio = CASE WHEN $6 THEN $7 ELSE bio END,
+                 status_text = CASE WHEN $8 THEN $9 ELSE status_text END,
+                 status_expires_at = CASE WHEN $10 THEN $11 ELSE status_expires_at END,
+                 locale = CASE WHEN $12 THEN $13 ELSE locale END,
+                 time_zone = CASE WHEN $14 THEN $15 ELSE time_zone END,
+                 updated_at = NOW()
+             WHERE uid = $1
+             RETURNING *"
+        )
+        .bind(uid)
+        .bind(profile.display_name.is_some())
+        .bind(profile.display_name.clone().flatten())
+        .bind(profile.avatar_url.is_some())
+        .bind(profile.avatar_url.clone().flatten())
+        .bind(profile.bio.is_some())
+        .bind(profile.bio.clone().flatten())
+        .bind(profile.status_text.is_some())
+        .bind(profile.status_text.clone().flatten())
+        .bind(profile.status_expires_at.is_some())
+        .bind(profile.status_expires_at.flatten())
+        .bind(profile.locale.is_some())
+        .bind(profile.locale.clone().flatten())
+        .bind(profile.time_zone.is_some())
+        .bind(profile.time_zone.clone().flatten())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            error!("Database error in update_profile: {}", e);
+            RepositoryError::from(e)
+        })?
+        .ok_or_else(|| RepositoryError::NotFound(format!("User with uid {} not found", uid)))
+    }
+
+    async fn mark_email_verified(&self, uid: &Uuid, email: &str) -> Result<bool, RepositoryError> {
+        info!("Marking email {} of user {} as verified", email, uid);
+        sqlx::query(
+            "UPDATE users SET email_verified_at = COALESCE(email_verified_at, NOW())
+             WHERE uid = $1 AND email = $2"
+        )
+        .bind(uid)
+        .bind(email)
+        .execute(&self.pool)
         .await
-        .map_err(|e| ServiceError::internal_error(&format!("Failed to create user: {}", e)))
+        .map(|result| result.rows_affected() > 0)
+        .map_err(|e| {
+            error!("Database error in mark_email_verified: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    async fn get_roles(&self, user_uid: &Uuid) -> Result<Vec<Role>, RepositoryError> {
+        sqlx::query_as::<_, Role>(
+            "SELECT r.name, r.scopes FROM roles r
+             JOIN user_roles ur ON ur.role = r.name
+             WHERE ur.user_uid = $1
+             ORDER BY r.name"
+        )
+        .bind(user_uid)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            error!("Database error in get_roles: {}", e);
+            RepositoryError::from(e)
+        })
+    }
+
+    async fn role_exists(&self, role: &str) -> Result<bool, RepositoryError> {
+        sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM roles WHERE name = $1)")
+            .bind(role)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| {
+                error!("Database error in role_exists: {}", e);
+                RepositoryError::from(e)
+            })
+    }
+
+    async fn add_role(&self, user_uid: &Uuid, role: &str) -> Result<(), RepositoryError> {
+        info!("Granting role {} to user {}", role, user_uid);
+        sqlx::query("INSERT INTO user_roles (user_uid, role) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+            .bind(user_uid)
+            .bind(role)
+            .execute(&self.pool)
+            .await
+            .map(|_| ())
+            .map_err(|e| {
+                error!("Database error in add_role: {}", e);
+                RepositoryError::from(e)
+            })
     }
 
-}
\ No newline at end of file
+    async fn remove_role(&self, user_uid: &Uuid, role: &str) -> Result<bool, RepositoryError> {
+        info!("Revoking role {} from user {}", role, user_uid);
+        sqlx::query("DELETE FROM user_roles WHERE user_uid = $1 AND role = $2")
+            .bind(user_uid)
+            .bind(role)
+            .execute(&self.pool)
+            .await
+            .map(|result| result.rows_affected() > 0)
+            .map_err(|e| {
+                error!("Database error in remove_role: {}", e);
+                RepositoryError::from(e)
+            })
+    }
+}
+
+// Search terms are matched literally, so LIKE wildcards in them are escaped.
+fn escape_like(term: &str) -> String {
+    term.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
@@ -0,0 +1,153 @@
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+use rand::RngCore;
+use rand::rngs::OsRng;
+use validator::Validate;
+use shared::middleware::auth::{ApiKeyVerifier, API_KEY_PREFIX};
+use shared::models::user_token::UserToken;
+use crate::models::api_key::{ApiKey, CreateApiKeyDTO, CreatedApiKey};
+use crate::repositories::user_repository::{UserRepository, PgUserRepository};
+use crate::repositories::api_key_repository::{ApiKeyRepository, PgApiKeyRepository};
+use crate::services::auth_service::{generate_token, hash_token};
+use crate::errors::service_error::ServiceError;
+use log::{info, warn};
+
+pub struct ApiKeyService<U: UserRepository, K: ApiKeyRepository> {
+    user_repository: U,
+    api_key_repository: K,
+}
+
+impl ApiKeyService<PgUserRepository, PgApiKeyRepository> {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            user_repository: PgUserRepository::new(pool.clone()),
+            api_key_repository: PgApiKeyRepository::new(pool),
+        }
+    }
+}
+
+impl<U: UserRepository, K: ApiKeyRepository> ApiKeyService<U, K> {
+    // A key can only be given scopes its owner currently has through their roles.
+    pub async fn create(&self, user_token: &UserToken, dto: CreateApiKeyDTO) -> Result<CreatedApiKey, ServiceError> {
+        let user_uid = owner(user_token)?;
+        dto.validate().map_err(|e| ServiceError::bad_request(&e.to_string()))?;
+
+        let allowed = self.role_scopes(&user_uid).await?;
+        if let Some(scope) = dto.scopes.iter().find(|scope| !allowed.contains(scope)) {
+            return Err(ServiceError::forbidden(&format!("Scope {} is not available to you", scope)));
+        }
+        let mut scopes = dto.scopes;
+        scopes.sort();
+        scopes.dedup();
+
+        let (prefix, key) = generate_api_key();
+        let now = Utc::now();
+        let api_key = ApiKey {
+            uid: Uuid::new_v4(),
+            user_uid,
+            name: dto.name,
+            prefix,
+            key_hash: hash_token(&key),
+            scopes,
+            expires_at: dto.expires_in_days.map(|days| now + Duration::days(days)),
+            last_used_at: None,
+            created_at: now,
+            revoked_at: None,
+        };
+        self.api_key_repository.create(&api_key).await?;
+
+        info!("User {} created API key {}", user_uid, api_key.uid);
+        Ok(CreatedApiKey { api_key, key })
+    }
+
+    pub async fn list(&self, user_token: &UserToken) -> Result<Vec<ApiKey>, ServiceError> {
+        let user_uid = owner(user_token)?;
+        Ok(self.api_key_repository.get_active_for_user(&user_uid).await?)
+    }
+
+    pub async fn revoke(&self, user_token: &UserToken, key_uid: &str) -> Result<(), ServiceError> {
+        let user_uid = owner(user_token)?;
+        let key_uid = Uuid::parse_str(key_uid).map_err(|_| ServiceError::bad_request("Invalid UUID"))?;
+        if !self.api_key_repository.revoke(&user_uid, &key_uid).await? {
+            return Err(ServiceError::not_found(&format!("API key {} not found", key_uid)));
+        }
+
+        info!("User {} revoked API key {}", user_uid, key_uid);
+        Ok(())
+    }
+
+    // Scopes are narrowed to what the owner's roles still grant, so removing a role also limits their keys.
+    pub async fn verify(&self, key: &str) -> Result<Option<UserToken>, ServiceError> {
+        let Some(api_key) = self.api_key_repository.get_active_by_hash(&hash_token(key)).await? else {
+            warn!("Rejected unknown or revoked API key {}", display_prefix(key));
+            return Ok(None);
+        };
+        self.api_key_repository.touch(&api_key.uid).await?;
+
+        let allowed = self.role_scopes(&api_key.user_uid).await?;
+        let scopes = api_key.scopes.into_iter().filter(|scope| allowed.contains(scope)).collect();
+        let expires_at = api_key.expires_at.map(|expires_at| expires_at.timestamp());
+        Ok(Some(UserToken::for_api_key(api_key.user_uid, api_key.uid, scopes, expires_at)))
+    }
+
+    async fn role_scopes(&self, user_uid: &Uuid) -> Result<Vec<String>, ServiceError> {
+        let roles = self.user_repository.get_roles(user_uid).await?;
+        Ok(roles.into_iter().flat_map(|role| role.scopes).collect())
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<U, K> ApiKeyVerifier for ApiKeyService<U, K>
+where
+    U: UserRepository + Send + Sync,
+    K: ApiKeyRepository + Send + Sync,
+{
+    async fn verify(&self, api_key: &str) -> Result<Option<UserToken>, actix_web::Error> {
+        Ok(ApiKeyService::verify(self, api_key).await?)
+    }
+}
+
+// Managing keys needs a real login, otherwise a leaked key could mint more keys.
+fn owner(user_token: &UserToken) -> Result<Uuid, ServiceError> {
+    if user_token.api_key_id.is_some() {
+        return Err(ServiceError::forbidden("API keys cannot be used to manage API keys"));
+    }
+    user_token.get_user_id().map_err(|_| ServiceError::unauthorized("Invalid token"))
+}
+
+// Keys look like `tb_<12 hex>_<64 hex>`; the part before the secret is kept as the display prefix.
+fn generate_api_key() -> (String, String) {
+    let mut id = [0u8; 6];
+    OsRng.fill_bytes(&mut id);
+    let prefix = format!("{}{}", API_KEY_PREFIX, hex::encode(id));
+    let key = format!("{}_{}", prefix, generate_token());
+    (prefix, key)
+}
+
+fn display_prefix(key: &str) -> &str {
+    key.rsplit_once('_').map(|(prefix, _)| prefix).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_keys_start_with_their_prefix() {
+        let (prefix, key) = generate_api_key();
+        assert!(key.starts_with(API_KEY_PREFIX));
+        assert_eq!(display_prefix(&key), prefix);
+        assert_eq!(key.len(), API_KEY_PREFIX.len() + 12 + 1 + 64);
+        assert_ne!(generate_api_key().1, key);
+    }
+
+    #[test]
+    fn key_tokens_cannot_manage_keys() {
+        let user_uid = Uuid::new_v4();
+        let token = UserToken::for_api_key(user_uid, Uuid::new_v4(), vec![], None);
+        assert_eq!(owner(&token).unwrap_err().status_code, 403);
+        let token = UserToken::new(user_uid, vec![], vec![], Duration::minutes(5));
+        assert_eq!(owner(&token).unwrap(), user_uid);
+    }
+}
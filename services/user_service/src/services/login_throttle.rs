@@ -0,0 +1,185 @@
+use std::sync::Arc;
+use chrono::{Duration, Utc};
+use crate::models::login_attempt::LockoutPolicy;
+use crate::repositories::login_attempt_repository::LoginAttemptRepository;
+use crate::errors::service_error::ServiceError;
+use log::warn;
+
+#[derive(Debug, Clone)]
+pub struct LoginThrottleSettings {
+    pub max_attempts_per_email: i32,
+    pub max_attempts_per_ip: i32,
+    pub base_lockout: Duration,
+    pub max_lockout: Duration,
+    pub window: Duration,
+}
+
+// Failed logins are counted per email and per client IP. Once a key goes over its allowance it is
+// locked out, and every further failure doubles the lockout up to `max_lockout`.
+pub struct LoginThrottle {
+    store: Arc<dyn LoginAttemptRepository>,
+    settings: LoginThrottleSettings,
+}
+
+impl LoginThrottle {
+    pub fn new(store: Arc<dyn LoginAttemptRepository>, settings: LoginThrottleSettings) -> Self {
+        Self { store, settings }
+    }
+
+    pub async fn check(&self, email: &str, ip: &str) -> Result<(), ServiceError> {
+        self.check_keys([email_key(email), ip_key(ip)]).await
+    }
+
+    // For re-authentication within a session, where only the account's counter applies.
+    pub async fn check_account(&self, email: &str) -> Result<(), ServiceError> {
+        self.check_keys([email_key(email)]).await
+    }
+
+    async fn check_keys<const N: usize>(&self, keys: [String; N]) -> Result<(), ServiceError> {
+        let now = Utc::now();
+        for key in keys {
+            let locked_until = self.store.get(&key).await?.and_then(|attempts| attempts.locked_until);
+            if let Some(locked_until) = locked_until.filter(|until| *until > now) {
+                let retry_after = ((locked_until - now).num_milliseconds() + 999) / 1000;
+                let retry_after = retry_after.max(1) as u64;
+                return Err(ServiceError::too_many_requests(
+                    &format!("Too many failed login attempts, try again in {} seconds", retry_after),
+                    retry_after,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn record_failure(&self, email: &str, ip: &str) -> Result<(), ServiceError> {
+        self.record_failures([
+            (email_key(email), self.settings.max_attempts_per_email),
+            (ip_key(ip), self.settings.max_attempts_per_ip),
+        ]).await
+    }
+
+    pub async fn record_account_failure(&self, email: &str) -> Result<(), ServiceError> {
+        self.record_failures([(email_key(email), self.settings.max_attempts_per_email)]).await
+    }
+
+    async fn record_failures<const N: usize>(&self, limits: [(String, i32); N]) -> Result<(), ServiceError> {
+        for (key, max_attempts) in limits {
+            let attempts = self.store.record_failure(&key, &self.policy(max_attempts)).await?;
+            if attempts.failures > max_attempts {
+                warn!("Locked out {} until {:?} after {} failed login attempts", key, attempts.locked_until, attempts.failures);
+            }
+        }
+        Ok(())
+    }
+
+    // The IP counter is left alone on success, otherwise an attacker could clear it with their own account.
+    pub async fn record_success(&self, email: &str) -> Result<(), ServiceError> {
+        Ok(self.store.reset(&email_key(email)).await?)
+    }
+
+    // Counters past the window would start over on the next failure anyway.
+    pub async fn purge_stale(&self) -> Result<u64, ServiceError> {
+        Ok(self.store.purge(Utc::now() - self.settings.window).await?)
+    }
+
+    fn policy(&self, max_attempts: i32) -> LockoutPolicy {
+        LockoutPolicy {
+            max_attempts,
+            window: self.settings.window,
+            base_lockout: self.settings.base_lockout,
+            max_lockout: self.settings.max_lockout,
+        }
+    }
+}
+
+fn email_key(email: &str) -> String {
+    format!("email:{}", email.trim().to_lowercase())
+}
+
+fn ip_key(ip: &str) -> String {
+    format!("ip:{}", ip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::fakes::InMemoryLoginAttemptRepository;
+
+    fn throttle() -> LoginThrottle {
+        LoginThrottle::new(
+            Arc::new(InMemoryLoginAttemptRepository::default()),
+            LoginThrottleSettings {
+                max_attempts_per_email: 3,
+                max_attempts_per_ip: 5,
+                base_lockout: Duration::seconds(30),
+                max_lockout: Duration::seconds(100),
+                window: Duration::minutes(15),
+            },
+        )
+    }
+
+    async fn fail(throttle: &LoginThrottle, email: &str, ip: &str, times: usize) {
+        for _ in 0..times {
+            throttle.record_failure(email, ip).await.unwrap();
+        }
+    }
+
+    fn retry_after(result: Result<(), ServiceError>) -> u64 {
+        let error = result.unwrap_err();
+        assert_eq!(error.status_code, 429);
+        error.retry_after.unwrap()
+    }
+
+    #[tokio::test]
+    async fn locks_an_email_after_too_many_failures() {
+        let throttle = throttle();
+        fail(&throttle, "a@example.com", "10.0.0.1", 3).await;
+        assert!(throttle.check("a@example.com", "10.0.0.2").await.is_ok());
+
+        fail(&throttle, "A@example.com", "10.0.0.1", 1).await;
+        let retry = retry_after(throttle.check("a@example.com", "10.0.0.2").await);
+        assert_eq!(retry, 30);
+        assert!(throttle.check("b@example.com", "10.0.0.2").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn lockouts_grow_up_to_the_maximum() {
+        let throttle = throttle();
+        fail(&throttle, "a@example.com", "10.0.0.1", 5).await;
+        assert!(retry_after(throttle.check("a@example.com", "10.0.0.2").await) > 30);
+
+        fail(&throttle, "a@example.com", "10.0.0.3", 5).await;
+        assert!(retry_after(throttle.check("a@example.com", "10.0.0.2").await) <= 100);
+    }
+
+    #[tokio::test]
+    async fn counts_failures_per_ip_across_emails() {
+        let throttle = throttle();
+        for i in 0..6 {
+            fail(&throttle, &format!("user{}@example.com", i), "10.0.0.1", 1).await;
+        }
+        retry_after(throttle.check("new@example.com", "10.0.0.1").await);
+        assert!(throttle.check("new@example.com", "10.0.0.2").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn success_clears_the_email_counter() {
+        let throttle = throttle();
+        fail(&throttle, "a@example.com", "10.0.0.1", 3).await;
+        throttle.record_success("a@example.com").await.unwrap();
+        fail(&throttle, "a@example.com", "10.0.0.1", 1).await;
+        assert!(throttle.check("a@example.com", "10.0.0.2").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn purging_keeps_only_current_lockouts() {
+        let throttle = throttle();
+        fail(&throttle, "a@example.com", "10.0.0.1", 1).await;
+        fail(&throttle, "b@example.com", "10.0.0.2", 4).await;
+        assert_eq!(throttle.purge_stale().await.unwrap(), 0);
+
+        assert_eq!(throttle.store.purge(Utc::now() + Duration::seconds(1)).await.unwrap(), 3);
+        assert!(throttle.store.get(&email_key("a@example.com")).await.unwrap().is_none());
+        retry_after(throttle.check("b@example.com", "10.0.0.3").await);
+    }
+}
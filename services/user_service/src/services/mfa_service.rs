@@ -0,0 +1,272 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use sqlx::PgPool;
+use uuid::Uuid;
+use rand::RngCore;
+use rand::rngs::OsRng;
+use totp_rs::{Algorithm, Secret, TOTP};
+use crate::models::mfa::{TotpEnrollment, RecoveryCodes};
+use crate::repositories::user_repository::{UserRepository, PgUserRepository};
+use crate::repositories::mfa_repository::{MfaRepository, PgMfaRepository};
+use crate::services::auth_service::hash_token;
+use crate::errors::service_error::ServiceError;
+use log::{info, warn};
+
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP_SECONDS: u64 = 30;
+const RECOVERY_CODE_COUNT: usize = 10;
+
+pub struct MfaService<U: UserRepository, M: MfaRepository> {
+    user_repository: U,
+    mfa_repository: M,
+    issuer: String,
+}
+
+impl MfaService<PgUserRepository, PgMfaRepository> {
+    pub fn new(pool: PgPool, issuer: String) -> Self {
+        Self::with_repositories(PgUserRepository::new(pool.clone()), PgMfaRepository::new(pool), issuer)
+    }
+}
+
+impl<U: UserRepository, M: MfaRepository> MfaService<U, M> {
+    pub fn with_repositories(user_repository: U, mfa_repository: M, issuer: String) -> Self {
+        Self { user_repository, mfa_repository, issuer }
+    }
+
+    // Starting again before confirming replaces the pending secret; an enabled one has to be disabled
+    // first. Callers confirm the account holder first, see AuthService::start_totp_enrollment.
+    pub async fn start_totp_enrollment(&self, user_uid: &Uuid) -> Result<TotpEnrollment, ServiceError> {
+        if self.is_enabled(user_uid).await? {
+            return Err(ServiceError::bad_request("Two-factor authentication is already enabled"));
+        }
+        let user = self.user_repository.get_by_id(user_uid).await?;
+
+        let mut secret = vec![0u8; 20];
+        OsRng.fill_bytes(&mut secret);
+        let secret = Secret::Raw(secret).to_encoded().to_string();
+        let totp = self.totp(&secret, Some(user.email))?;
+        self.mfa_repository.save_pending_totp(user_uid, &secret).await?;
+
+        Ok(TotpEnrollment { otpauth_uri: totp.get_url(), secret })
+    }
+
+    pub async fn confirm_totp(&self, user_uid: &Uuid, code: &str) -> Result<RecoveryCodes, ServiceError> {
+        let pending = self.mfa_repository.get_totp(user_uid).await?
+            .filter(|totp| totp.enabled_at.is_none())
+            .ok_or_else(|| ServiceError::bad_request("No pending two-factor enrollment"))?;
+
+        let step = matching_step(&self.totp(&pending.secret, None)?, code, unix_time())
+            .ok_or_else(|| ServiceError::bad_request("Invalid authentication code"))?;
+
+        let recovery_codes: Vec<String> = (0..RECOVERY_CODE_COUNT).map(|_| generate_recovery_code()).collect();
+        let hashes: Vec<String> = recovery_codes.iter().map(|code| hash_recovery_code(code)).collect();
+        self.mfa_repository.enable_totp(user_uid, step, &hashes).await?;
+
+        info!("User {} enabled two-factor authentication", user_uid);
+        Ok(RecoveryCodes { recovery_codes })
+    }
+
+    // Callers confirm the account holder first, see AuthService::disable_totp.
+    pub async fn disable_totp(&self, user_uid: &Uuid) -> Result<(), ServiceError> {
+        self.mfa_repository.delete_totp(user_uid).await?;
+        info!("User {} disabled two-factor authentication", user_uid);
+        Ok(())
+    }
+
+    pub async fn is_enabled(&self, user_uid: &Uuid) -> Result<bool, ServiceError> {
+        Ok(self.mfa_repository.get_totp(user_uid).await?
+            .is_some_and(|totp| totp.enabled_at.is_some()))
+    }
+
+    // The second factors a password login has to be followed by; empty when none are set up.
+    pub async fn second_factors(&self, user_uid: &Uuid) -> Result<Vec<String>, ServiceError> {
+        let mut methods = Vec::new();
+        if self.is_enabled(user_uid).await? {
+            methods.push("totp".to_string());
+        }
+        if self.mfa_repository.has_passkeys(user_uid).await? {
+            methods.push("passkey".to_string());
+        }
+        Ok(methods)
+    }
+
+    // Accepts either a current TOTP code or one of the unused recovery codes; both are single use.
+    pub async fn verify_code(&self, user_uid: &Uuid, code: &str) -> Result<bool, ServiceError> {
+        let Some(enrolled) = self.mfa_repository.get_totp(user_uid).await?.filter(|totp| totp.enabled_at.is_some()) else {
+            return Ok(false);
+        };
+
+        let code = code.trim();
+        if code.len() == TOTP_DIGITS && code.bytes().all(|b| b.is_ascii_digit()) {
+            return match matching_step(&self.totp(&enrolled.secret, None)?, code, unix_time()) {
+                Some(step) => Ok(self.mfa_repository.use_totp_step(user_uid, step).await?),
+                None => Ok(false),
+            };
+        }
+
+        let used = self.mfa_repository.use_recovery_code(user_uid, &hash_recovery_code(code)).await?;
+        if used {
+            warn!("User {} signed in with a recovery code", user_uid);
+        }
+        Ok(used)
+    }
+
+    fn totp(&self, secret: &str, account_name: Option<String>) -> Result<TOTP, ServiceError> {
+        let secret = Secret::Encoded(secret.to_string())
+            .to_bytes()
+            .map_err(|e| ServiceError::internal_error(&format!("Invalid TOTP secret: {:?}", e)))?;
+        TOTP::new(
+            Algorithm::SHA1,
+            TOTP_DIGITS,
+            0,
+            TOTP_STEP_SECONDS,
+            secret,
+            Some(self.issuer.clone()),
+            account_name.unwrap_or_default(),
+        )
+        .map_err(|e| ServiceError::internal_error(&format!("Invalid TOTP parameters: {}", e)))
+    }
+}
+
+#[cfg(test)]
+impl<U: UserRepository, M: MfaRepository> MfaService<U, M> {
+    pub(crate) fn current_code(&self, secret: &str) -> String {
+        self.totp(secret, None).unwrap().generate(unix_time())
+    }
+}
+
+// Returns the time step the code belongs to, allowing one step of clock drift either way.
+fn matching_step(totp: &TOTP, code: &str, now: u64) -> Option<i64> {
+    let current = now / TOTP_STEP_SECONDS;
+    [current.saturating_sub(1), current, current + 1]
+        .into_iter()
+        .find(|step| totp.check(code, step * TOTP_STEP_SECONDS))
+        .map(|step| step as i64)
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+fn generate_recovery_code() -> String {
+    let mut bytes = [0u8; 5];
+    OsRng.fill_bytes(&mut bytes);
+    let code = hex::encode(bytes);
+    format!("{}-{}", &code[..5], &code[5..])
+}
+
+fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code.chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+    hash_token(&normalized.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::mfa::ReauthDTO;
+    use crate::services::auth_service::testing::{TestAuth, PASSWORD};
+
+    fn totp() -> TOTP {
+        TOTP::new(Algorithm::SHA1, TOTP_DIGITS, 0, TOTP_STEP_SECONDS, b"12345678901234567890".to_vec(), None, String::new()).unwrap()
+    }
+
+    #[test]
+    fn codes_match_their_step_within_one_step_of_drift() {
+        let totp = totp();
+        let now = 1_700_000_000;
+        let step = (now / TOTP_STEP_SECONDS) as i64;
+
+        assert_eq!(matching_step(&totp, &totp.generate(now), now), Some(step));
+        assert_eq!(matching_step(&totp, &totp.generate(now - 30), now), Some(step - 1));
+        assert_eq!(matching_step(&totp, &totp.generate(now + 30), now), Some(step + 1));
+        assert_eq!(matching_step(&totp, &totp.generate(now - 90), now), None);
+    }
+
+    #[test]
+    fn recovery_codes_are_hashed_ignoring_case_and_separators() {
+        let code = generate_recovery_code();
+        assert_eq!(code.len(), 11);
+        assert_eq!(hash_recovery_code(&code), hash_recovery_code(&code.to_uppercase().replace('-', " ")));
+        assert_ne!(hash_recovery_code(&code), hash_recovery_code(&generate_recovery_code()));
+    }
+
+    #[tokio::test]
+    async fn enrollment_is_confirmed_with_a_current_code() {
+        let auth = TestAuth::default();
+        let user = auth.add_user("alice");
+        let mfa = &auth.mfa_service;
+
+        let enrollment = mfa.start_totp_enrollment(&user.uid).await.unwrap();
+        assert!(enrollment.otpauth_uri.starts_with("otpauth://totp/Test:alice%40example.com?"));
+        assert!(!mfa.is_enabled(&user.uid).await.unwrap());
+        assert_eq!(mfa.confirm_totp(&user.uid, "000000").await.err().unwrap().status_code, 400);
+
+        let codes = mfa.confirm_totp(&user.uid, &mfa.current_code(&enrollment.secret)).await.unwrap();
+        assert_eq!(codes.recovery_codes.len(), RECOVERY_CODE_COUNT);
+        assert!(mfa.is_enabled(&user.uid).await.unwrap());
+        assert_eq!(mfa.second_factors(&user.uid).await.unwrap(), vec!["totp"]);
+        assert_eq!(mfa.start_totp_enrollment(&user.uid).await.err().unwrap().status_code, 400);
+    }
+
+    #[tokio::test]
+    async fn totp_steps_and_recovery_codes_are_single_use() {
+        let auth = TestAuth::default();
+        let user = auth.add_user("alice");
+        let mfa = &auth.mfa_service;
+        let enrollment = mfa.start_totp_enrollment(&user.uid).await.unwrap();
+        let code = mfa.current_code(&enrollment.secret);
+        let recovery_codes = mfa.confirm_totp(&user.uid, &code).await.unwrap().recovery_codes;
+
+        // The step the enrollment was confirmed with is already spent, the next one isn't.
+        assert!(!mfa.verify_code(&user.uid, &code).await.unwrap());
+        let totp = mfa.totp(&enrollment.secret, None).unwrap();
+        let next = totp.generate(unix_time() + TOTP_STEP_SECONDS);
+        assert!(mfa.verify_code(&user.uid, &next).await.unwrap());
+        assert!(!mfa.verify_code(&user.uid, &next).await.unwrap());
+
+        assert!(mfa.verify_code(&user.uid, &recovery_codes[0].to_uppercase()).await.unwrap());
+        assert!(!mfa.verify_code(&user.uid, &recovery_codes[0]).await.unwrap());
+        assert!(!mfa.verify_code(&user.uid, "not-a-code").await.unwrap());
+    }
+
+    fn reauth(current_password: Option<&str>, code: Option<&str>) -> ReauthDTO {
+        ReauthDTO { current_password: current_password.map(str::to_string), code: code.map(str::to_string) }
+    }
+
+    #[tokio::test]
+    async fn turning_totp_on_and_off_needs_the_password() {
+        let auth = TestAuth::default();
+        let user = auth.add_user("alice");
+
+        let error = auth.service.start_totp_enrollment(&user.uid, ReauthDTO::default()).await.err().unwrap();
+        assert_eq!(error.message, "Current password is required");
+        auth.service.start_totp_enrollment(&user.uid, reauth(Some(PASSWORD), None)).await.unwrap();
+        auth.enable_totp(&user).await;
+
+        let error = auth.service.disable_totp(&user.uid, reauth(Some("wrong-password"), None)).await.err().unwrap();
+        assert_eq!(error.message, "Current password is incorrect");
+        assert!(auth.mfa_service.is_enabled(&user.uid).await.unwrap());
+
+        auth.service.disable_totp(&user.uid, reauth(Some(PASSWORD), None)).await.unwrap();
+        assert!(!auth.mfa_service.is_enabled(&user.uid).await.unwrap());
+        let error = auth.service.disable_totp(&user.uid, reauth(Some(PASSWORD), None)).await.err().unwrap();
+        assert_eq!(error.message, "Two-factor authentication is not enabled");
+    }
+
+    #[tokio::test]
+    async fn accounts_without_a_password_turn_totp_off_with_a_code() {
+        let auth = TestAuth::default();
+        let user = auth.users.add("alice", "alice@example.com", None);
+
+        auth.service.start_totp_enrollment(&user.uid, ReauthDTO::default()).await.unwrap();
+        let recovery_codes = auth.enable_totp(&user).await;
+
+        let error = auth.service.disable_totp(&user.uid, ReauthDTO::default()).await.err().unwrap();
+        assert_eq!(error.message, "An authentication code is required");
+        let error = auth.service.disable_totp(&user.uid, reauth(None, Some("00000-00000"))).await.err().unwrap();
+        assert_eq!(error.message, "Invalid authentication code");
+
+        auth.service.disable_totp(&user.uid, reauth(None, Some(&recovery_codes[0]))).await.unwrap();
+        assert!(!auth.mfa_service.is_enabled(&user.uid).await.unwrap());
+        assert!(!auth.mfa_service.verify_code(&user.uid, &recovery_codes[1]).await.unwrap());
+    }
+}
@@ -1 +1,9 @@
-pub mod user_service;
\ No newline at end of file
+pub mod user_service;
+pub mod auth_service;
+pub mod login_throttle;
+pub mod mfa_service;
+pub mod password_hasher;
+pub mod password_policy;
+pub mod oidc_service;
+pub mod api_key_service;
+pub mod passkey_service;
\ No newline at end of file
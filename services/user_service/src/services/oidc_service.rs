@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use sqlx::PgPool;
+use chrono::{Duration, Utc};
+use crate::models::identity::{OidcCallbackQuery, ExternalIdentity};
+use crate::models::mfa::LoginOutcome;
+use crate::models::session::ClientInfo;
+use crate::oidc::{pkce_pair, OidcProviderConfig};
+use crate::oidc::client::{OidcClient, IdTokenClaims};
+use crate::repositories::user_repository::{UserRepository, PgUserRepository};
+use crate::repositories::token_repository::{TokenRepository, PgTokenRepository};
+use crate::repositories::mfa_repository::{MfaRepository, PgMfaRepository};
+use crate::repositories::identity_repository::{IdentityRepository, PgIdentityRepository};
+use crate::services::auth_service::{AuthService, generate_token, hash_token};
+use crate::errors::service_error::ServiceError;
+use log::{info, warn};
+
+pub struct OidcService<U: UserRepository, I: IdentityRepository, T: TokenRepository = PgTokenRepository, M: MfaRepository = PgMfaRepository> {
+    user_repository: U,
+    identity_repository: I,
+    auth_service: Arc<AuthService<U, T, M>>,
+    providers: HashMap<String, OidcClient>,
+    login_ttl: Duration,
+}
+
+impl OidcService<PgUserRepository, PgIdentityRepository> {
+    pub fn new(
+        pool: PgPool,
+        auth_service: Arc<AuthService<PgUserRepository, PgTokenRepository>>,
+        providers: &[OidcProviderConfig],
+        public_base_url: &str,
+        login_ttl: Duration,
+    ) -> Result<Self, String> {
+        let providers = providers
+            .iter()
+            .map(|provider| {
+                let redirect_uri = format!("{}/api/auth/oidc/{}/callback", public_base_url, provider.name);
+                Ok((provider.name.clone(), OidcClient::new(provider.clone(), redirect_uri)?))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self::with_repositories(PgUserRepository::new(pool.clone()), PgIdentityRepository::new(pool), auth_service, providers, login_ttl))
+    }
+}
+
+impl<U: UserRepository, I: IdentityRepository, T: TokenRepository, M: MfaRepository> OidcService<U, I, T, M> {
+    pub fn with_repositories(
+        user_repository: U,
+        identity_repository: I,
+        auth_service: Arc<AuthService<U, T, M>>,
+        providers: HashMap<String, OidcClient>,
+        login_ttl: Duration,
+    ) -> Self {
+        Self { user_repository, identity_repository, auth_service, providers, login_ttl }
+    }
+
+    pub async fn start_login(&self, provider: &str) -> Result<String, ServiceError> {
+        let client = self.client(provider)?;
+        let state = generate_token();
+        let nonce = generate_token();
+        let (code_verifier, code_challenge) = pkce_pair();
+
+        let url = client.authorization_url(&state, &nonce, &code_challenge).await?;
+        let expires_at = Utc::now() + self.login_ttl;
+        self.identity_repository
+            .save_login_state(&hash_token(&state), client.name(), &code_verifier, &nonce, expires_at)
+            .await?;
+        Ok(url)
+    }
+
+    pub async fn finish_login(&self, provider: &str, query: OidcCallbackQuery, client_info: &ClientInfo) -> Result<LoginOutcome, ServiceError> {
+        let client = self.client(provider)?;
+        if let Some(error) = query.error {
+            warn!("OIDC provider {} returned an error: {} {:?}", provider, error, query.error_description);
+            return Err(ServiceError::unauthorized(&format!("The identity provider returned an error: {}", error)));
+        }
+        let (Some(code), Some(state)) = (query.code, query.state) else {
+            return Err(ServiceError::bad_request("Missing code or state"));
+        };
+
+        let login_state = self.identity_repository.consume_login_state(&hash_token(&state)).await?
+            .filter(|login_state| login_state.provider == client.name())
+            .ok_or_else(|| ServiceError::bad_request("Invalid or expired login state"))?;
+
+        let id_token = client.exchange_code(&code, &login_state.code_verifier).await?;
+        let claims = client.validate_id_token(&id_token, &login_state.nonce).await?;
+        let identity = external_identity(client.name(), claims)?;
+
+        let user = match self.identity_repository.find_user_uid(&identity.provider, &identity.subject).await? {
+            Some(user_uid) => {
+                self.identity_repository.touch_identity(&identity.provider, &identity.subject, &identity.email).await?;
+                self.user_repository.get_by_id(&user_uid).await?
+            }
+            // Linking to an account whose own address was never confirmed would hand the identity
+            // to whoever registered it first.
+            None => match self.user_repository.get_by_email(&identity.email).await {
+                Ok(user) if client.auto_link_verified_email() && user.email_verified_at.is_some() => {
+                    self.identity_repository.link_identity(&user.uid, &identity).await?;
+                    user
+                }
+                Ok(user) => {
+                    warn!("Not linking {} identity {} to existing user {}", identity.provider, identity.subject, user.uid);
+                    return Err(ServiceError::conflict("An account with this email already exists, sign in to it instead"));
+                }
+                Err(e) if e.is_not_found() => self.identity_repository.create_user_with_identity(&identity).await?,
+                Err(e) => return Err(e.into()),
+            },
+        };
+
+        info!("User {} signed in through {}", user.email, identity.provider);
+        self.auth_service.complete_login(user, client_info).await
+    }
+
+    fn client(&self, provider: &str) -> Result<&OidcClient, ServiceError> {
+        self.providers
+            .get(provider)
+            .ok_or_else(|| ServiceError::not_found(&format!("Identity provider {} not found", provider)))
+    }
+}
+
+// Linking by email is only safe when the provider vouches for the address.
+fn external_identity(provider: &str, claims: IdTokenClaims) -> Result<ExternalIdentity, ServiceError> {
+    let email = claims.email
+        .filter(|_| claims.email_verified)
+        .ok_or_else(|| ServiceError::forbidden("The identity provider did not return a verified email address"))?;
+    let username = suggested_username(claims.preferred_username.as_deref().unwrap_or(&email));
+    Ok(ExternalIdentity { provider: provider.to_string(), subject: claims.sub, email, username })
+}
+
+fn suggested_username(hint: &str) -> String {
+    let local_part = hint.split('@').next().unwrap_or_default();
+    let mut username: String = local_part
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '.' || *c == '-')
+        .take(20)
+        .collect();
+    while username.len() < 3 {
+        username.push('_');
+    }
+    username
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::Url;
+    use crate::models::user::UserDTO;
+    use crate::oidc::mock_issuer::{MockIssuer, CLIENT_ID};
+    use crate::repositories::fakes::{FakeIdentityRepository, FakeMfaRepository, FakeTokenRepository, FakeUserRepository};
+    use crate::services::auth_service::testing::{client, TestAuth};
+
+    type TestOidc = OidcService<FakeUserRepository, FakeIdentityRepository, FakeTokenRepository, FakeMfaRepository>;
+
+    fn oidc(auth: &TestAuth, issuer: &MockIssuer, identities: &FakeIdentityRepository) -> TestOidc {
+        let config = OidcProviderConfig {
+            name: "mock".to_string(),
+            issuer: issuer.issuer.clone(),
+            client_id: CLIENT_ID.to_string(),
+            client_secret: None,
+            scopes: "openid email profile".to_string(),
+            auto_link_verified_email: true,
+        };
+        let client = OidcClient::new(config, "http://localhost/api/auth/oidc/mock/callback".to_string()).unwrap();
+        let providers = HashMap::from([("mock".to_string(), client)]);
+        OidcService::with_repositories(auth.users.clone(), identities.clone(), auth.service.clone(), providers, Duration::minutes(10))
+    }
+
+    async fn sign_in(oidc: &TestOidc, issuer: &MockIssuer, subject: &str, email: &str) -> Result<LoginOutcome, ServiceError> {
+        let url = Url::parse(&oidc.start_login("mock").await.unwrap()).unwrap();
+        let params: HashMap<_, _> = url.query_pairs().into_owned().collect();
+        let code = issuer.issue_code(&params["code_challenge"], &params["nonce"], subject, email);
+        let query = OidcCallbackQuery { code: Some(code), state: Some(params["state"].clone()), error: None, error_description: None };
+        oidc.finish_login("mock", query, &client()).await
+    }
+
+    #[actix_web::test]
+    async fn links_by_email_only_to_verified_accounts() {
+        let auth = TestAuth::default();
+        let issuer = MockIssuer::start().await;
+        let identities = FakeIdentityRepository::new(auth.users.clone());
+        let oidc = oidc(&auth, &issuer, &identities);
+
+        let squatted = UserDTO { username: "mallory".into(), email: "jane@example.com".into(), password: "hash".into() };
+        auth.users.create(&squatted).await.unwrap();
+        let error = sign_in(&oidc, &issuer, "user-1", "jane@example.com").await.err().unwrap();
+        assert_eq!(error.status_code, 409);
+        assert!(identities.find_user_uid("mock", "user-1").await.unwrap().is_none());
+
+        let bob = auth.add_user("bob");
+        let outcome = sign_in(&oidc, &issuer, "user-2", &bob.email).await.unwrap();
+        assert!(matches!(outcome, LoginOutcome::Authenticated(response) if response.user.uid == bob.uid));
+        assert_eq!(identities.find_user_uid("mock", "user-2").await.unwrap(), Some(bob.uid));
+    }
+
+    #[test]
+    fn usernames_are_derived_from_the_provider_hint() {
+        assert_eq!(suggested_username("jane.doe@example.com"), "jane.doe");
+        assert_eq!(suggested_username("Jöhn Smith"), "JhnSmith");
+        assert_eq!(suggested_username("a"), "a__");
+        assert_eq!(suggested_username("a-very-long-username-from-sso").len(), 20);
+    }
+}
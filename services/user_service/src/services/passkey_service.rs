@@ -0,0 +1,343 @@
+use std::sync::Arc;
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+use validator::Validate;
+use crate::models::passkey::{Passkey, FinishPasskeyRegistrationDTO, PasskeyLoginDTO, PasskeyMfaDTO};
+use crate::models::mfa::ReauthDTO;
+use crate::models::user::LoginResponse;
+use crate::models::session::ClientInfo;
+use crate::repositories::user_repository::{UserRepository, PgUserRepository};
+use crate::repositories::token_repository::{TokenRepository, PgTokenRepository};
+use crate::repositories::mfa_repository::{MfaRepository, PgMfaRepository};
+use crate::repositories::passkey_repository::{PasskeyRepository, PgPasskeyRepository};
+use crate::services::auth_service::{AuthService, generate_token, hash_token};
+use crate::webauthn::{self, AssertionCredential, CreationOptions, RequestOptions, VerifiedCredential, Webauthn, WebauthnConfig};
+use crate::errors::service_error::ServiceError;
+use shared::errors::repository_error::RepositoryError;
+use log::{info, warn};
+
+const REGISTRATION: &str = "registration";
+const LOGIN: &str = "login";
+const MFA: &str = "mfa";
+
+pub struct PasskeyService<U: UserRepository, P: PasskeyRepository, T: TokenRepository = PgTokenRepository, M: MfaRepository = PgMfaRepository> {
+    user_repository: U,
+    passkey_repository: P,
+    auth_service: Arc<AuthService<U, T, M>>,
+    webauthn: Webauthn,
+    challenge_ttl: Duration,
+}
+
+impl PasskeyService<PgUserRepository, PgPasskeyRepository> {
+    pub fn new(
+        pool: PgPool,
+        auth_service: Arc<AuthService<PgUserRepository, PgTokenRepository>>,
+        config: WebauthnConfig,
+        challenge_ttl: Duration,
+    ) -> Self {
+        Self::with_repositories(PgUserRepository::new(pool.clone()), PgPasskeyRepository::new(pool), auth_service, config, challenge_ttl)
+    }
+}
+
+impl<U: UserRepository, P: PasskeyRepository, T: TokenRepository, M: MfaRepository> PasskeyService<U, P, T, M> {
+    pub fn with_repositories(
+        user_repository: U,
+        passkey_repository: P,
+        auth_service: Arc<AuthService<U, T, M>>,
+        config: WebauthnConfig,
+        challenge_ttl: Duration,
+    ) -> Self {
+        Self {
+            user_repository,
+            passkey_repository,
+            auth_service,
+            webauthn: Webauthn::new(config),
+            challenge_ttl,
+        }
+    }
+
+    // The identity check happens here rather than on finishing, as only this hands out the challenge.
+    pub async fn start_registration(&self, user_uid: &Uuid, dto: ReauthDTO) -> Result<CreationOptions, ServiceError> {
+        let user = self.user_repository.get_by_id(user_uid).await?;
+        self.auth_service.confirm_identity(&user, dto.current_password.as_deref(), dto.code.as_deref()).await?;
+        let existing: Vec<String> = self.passkey_repository.get_for_user(user_uid).await?
+            .into_iter()
+            .map(|passkey| passkey.credential_id)
+            .collect();
+
+        let challenge = self.new_challenge(Some(user_uid), REGISTRATION).await?;
+        Ok(self.webauthn.creation_options(&challenge, &user.uid, &user.email, &user.username, &existing))
+    }
+
+    pub async fn finish_registration(&self, user_uid: &Uuid, dto: FinishPasskeyRegistrationDTO) -> Result<Passkey, ServiceError> {
+        dto.validate().map_err(|e| ServiceError::bad_request(&e.to_string()))?;
+        let challenge = webauthn::client_challenge(&dto.credential.response.client_data_json)
+            .map_err(|e| ServiceError::bad_request(&format!("Invalid passkey: {}", e)))?;
+        if self.passkey_repository.consume_challenge(&hash_token(&challenge), REGISTRATION).await? != Some(Some(*user_uid)) {
+            return Err(ServiceError::bad_request("Invalid or expired passkey challenge"));
+        }
+
+        let verified = self.webauthn.verify_registration(&dto.credential, &challenge)
+            .map_err(|e| ServiceError::bad_request(&format!("Invalid passkey: {}", e)))?;
+        let passkey = Passkey {
+            uid: Uuid::new_v4(),
+            user_uid: *user_uid,
+            credential_id: verified.credential_id,
+            public_key: verified.public_key,
+            algorithm: verified.algorithm,
+            sign_count: verified.sign_count as i64,
+            name: dto.name.unwrap_or("Passkey".into()),
+            created_at: Utc::now(),
+            last_used_at: None,
+        };
+        self.passkey_repository.create(&passkey).await.map_err(|e| match e {
+            RepositoryError::Conflict { .. } => ServiceError::conflict("This passkey is already registered"),
+            e => e.into(),
+        })?;
+
+        info!("User {} registered passkey {}", user_uid, passkey.uid);
+        Ok(passkey)
+    }
+
+    pub async fn list(&self, user_uid: &Uuid) -> Result<Vec<Passkey>, ServiceError> {
+        Ok(self.passkey_repository.get_for_user(user_uid).await?)
+    }
+
+    pub async fn delete(&self, user_uid: &Uuid, passkey_uid: &str, dto: ReauthDTO) -> Result<(), ServiceError> {
+        let passkey_uid = Uuid::parse_str(passkey_uid).map_err(|_| ServiceError::bad_request("Invalid UUID"))?;
+        let user = self.user_repository.get_by_id(user_uid).await?;
+        self.auth_service.confirm_identity(&user, dto.current_password.as_deref(), dto.code.as_deref()).await?;
+        if !self.passkey_repository.delete(user_uid, &passkey_uid).await? {
+            return Err(ServiceError::not_found(&format!("Passkey {} not found", passkey_uid)));
+        }
+
+        info!("User {} removed passkey {}", user_uid, passkey_uid);
+        Ok(())
+    }
+
+    // Passwordless login: the browser offers any discoverable passkey for this site.
+    pub async fn start_login(&self) -> Result<RequestOptions, ServiceError> {
+        let challenge = self.new_challenge(None, LOGIN).await?;
+        Ok(self.webauthn.request_options(&challenge, &[], "required"))
+    }
+
+    // A user-verified passkey is two factors on its own, so this signs in without an MFA challenge.
+    pub async fn finish_login(&self, dto: PasskeyLoginDTO, client: &ClientInfo) -> Result<LoginResponse, ServiceError> {
+        let (challenge, _) = self.consume_challenge(&dto.credential, LOGIN).await?;
+        let passkey = self.passkey_repository.get_by_credential_id(&dto.credential.id).await?
+            .ok_or_else(|| ServiceError::unauthorized("Unknown passkey"))?;
+        if dto.credential.response.user_handle.as_ref().is_some_and(|handle| *handle != webauthn::user_handle(&passkey.user_uid)) {
+            return Err(ServiceError::unauthorized("Passkey does not belong to this account"));
+        }
+
+        let sign_count = self.check_assertion(&dto.credential, &challenge, &passkey, true)
+            .map_err(|e| ServiceError::unauthorized(&format!("Invalid passkey: {}", e)))?;
+        self.passkey_repository.record_use(&passkey.uid, sign_count).await?;
+        let user = self.user_repository.get_by_id(&passkey.user_uid).await?;
+        self.auth_service.sign_in(user, client).await
+    }
+
+    pub async fn start_mfa(&self, challenge_token: &str) -> Result<RequestOptions, ServiceError> {
+        let user_uid = self.auth_service.mfa_challenge_user(challenge_token).await?;
+        let allowed: Vec<String> = self.passkey_repository.get_for_user(&user_uid).await?
+            .into_iter()
+            .map(|passkey| passkey.credential_id)
+            .collect();
+        if allowed.is_empty() {
+            return Err(ServiceError::bad_request("No passkeys are registered for this account"));
+        }
+
+        let challenge = self.new_challenge(Some(&user_uid), MFA).await?;
+        Ok(self.webauthn.request_options(&challenge, &allowed, "preferred"))
+    }
+
+    pub async fn finish_mfa(&self, dto: PasskeyMfaDTO, client: &ClientInfo) -> Result<LoginResponse, ServiceError> {
+        let credential = dto.credential;
+        self.auth_service.complete_mfa_challenge(&dto.challenge_token, client, |user_uid| async move {
+            let (challenge, challenge_user) = self.consume_challenge(&credential, MFA).await?;
+            let Some(passkey) = self.passkey_repository.get_by_credential_id(&credential.id).await?
+                .filter(|passkey| passkey.user_uid == user_uid && challenge_user == Some(user_uid))
+            else {
+                return Ok(false);
+            };
+            match self.check_assertion(&credential, &challenge, &passkey, false) {
+                Ok(sign_count) => {
+                    self.passkey_repository.record_use(&passkey.uid, sign_count).await?;
+                    Ok(true)
+                }
+                Err(e) => {
+                    warn!("Rejected passkey {} for user {}: {}", passkey.uid, user_uid, e);
+                    Ok(false)
+                }
+            }
+        })
+        .await
+    }
+
+    async fn new_challenge(&self, user_uid: Option<&Uuid>, purpose: &str) -> Result<String, ServiceError> {
+        let challenge = generate_token();
+        let expires_at = Utc::now() + self.challenge_ttl;
+        self.passkey_repository.save_challenge(&hash_token(&challenge), user_uid, purpose, expires_at).await?;
+        Ok(challenge)
+    }
+
+    async fn consume_challenge(&self, credential: &AssertionCredential, purpose: &str) -> Result<(String, Option<Uuid>), ServiceError> {
+        let challenge = webauthn::client_challenge(&credential.response.client_data_json)
+            .map_err(|e| ServiceError::bad_request(&format!("Invalid passkey: {}", e)))?;
+        let user_uid = self.passkey_repository.consume_challenge(&hash_token(&challenge), purpose).await?
+            .ok_or_else(|| ServiceError::unauthorized("Invalid or expired passkey challenge"))?;
+        Ok((challenge, user_uid))
+    }
+
+    fn check_assertion(&self, credential: &AssertionCredential, challenge: &str, passkey: &Passkey, require_user_verification: bool) -> Result<i64, String> {
+        let stored = VerifiedCredential {
+            credential_id: passkey.credential_id.clone(),
+            public_key: passkey.public_key.clone(),
+            algorithm: passkey.algorithm,
+            sign_count: passkey.sign_count as u32,
+        };
+        self.webauthn
+            .verify_assertion(credential, challenge, &stored, require_user_verification)
+            .map(i64::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::mfa::{LoginOutcome, MfaChallenge};
+    use crate::models::user::{LoginDTO, User};
+    use crate::repositories::fakes::{FakeMfaRepository, FakePasskeyRepository, FakeTokenRepository, FakeUserRepository};
+    use crate::services::auth_service::testing::{client, TestAuth, PASSWORD};
+    use crate::webauthn::software_authenticator::SoftwareAuthenticator;
+
+    const RP_ID: &str = "tickets.example.com";
+    const ORIGIN: &str = "https://tickets.example.com";
+
+    type TestPasskeys = PasskeyService<FakeUserRepository, FakePasskeyRepository, FakeTokenRepository, FakeMfaRepository>;
+
+    fn passkeys(auth: &TestAuth) -> TestPasskeys {
+        let config = WebauthnConfig {
+            rp_id: RP_ID.to_string(),
+            rp_name: "TicketBan".to_string(),
+            origin: ORIGIN.to_string(),
+        };
+        PasskeyService::with_repositories(auth.users.clone(), FakePasskeyRepository::default(), auth.service.clone(), config, Duration::minutes(5))
+    }
+
+    fn reauth(current_password: Option<&str>, code: Option<&str>) -> ReauthDTO {
+        ReauthDTO { current_password: current_password.map(str::to_string), code: code.map(str::to_string) }
+    }
+
+    async fn register(passkeys: &TestPasskeys, user: &User, authenticator: &SoftwareAuthenticator) -> Passkey {
+        let options = passkeys.start_registration(&user.uid, reauth(Some(PASSWORD), None)).await.unwrap();
+        let credential = authenticator.register(RP_ID, ORIGIN, &options.challenge);
+        passkeys.finish_registration(&user.uid, FinishPasskeyRegistrationDTO { name: None, credential }).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn signs_in_with_passkeys_of_every_algorithm() {
+        let auth = TestAuth::default();
+        let passkeys = passkeys(&auth);
+        let user = auth.add_user("alice");
+
+        for authenticator in [SoftwareAuthenticator::es256(), SoftwareAuthenticator::ed25519(), SoftwareAuthenticator::rs256()] {
+            let passkey = register(&passkeys, &user, &authenticator).await;
+            assert_eq!(passkey.credential_id, authenticator.credential_id());
+
+            let options = passkeys.start_login().await.unwrap();
+            let credential = authenticator.assert(RP_ID, ORIGIN, &options.challenge, true);
+            let response = passkeys.finish_login(PasskeyLoginDTO { credential, device_name: None }, &client()).await.unwrap();
+            assert_eq!(response.user.uid, user.uid);
+        }
+        assert_eq!(passkeys.list(&user.uid).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn adding_and_removing_passkeys_needs_the_password() {
+        let auth = TestAuth::default();
+        let passkeys = passkeys(&auth);
+        let user = auth.add_user("alice");
+        let recovery_codes = auth.enable_totp(&user).await;
+
+        for (dto, message) in [
+            (reauth(None, None), "Current password is required"),
+            (reauth(None, Some(&recovery_codes[0])), "Current password is required"),
+            (reauth(Some("wrong-password"), None), "Current password is incorrect"),
+        ] {
+            assert_eq!(passkeys.start_registration(&user.uid, dto).await.err().unwrap().message, message);
+        }
+
+        let passkey = register(&passkeys, &user, &SoftwareAuthenticator::es256()).await;
+        let passkey_uid = passkey.uid.to_string();
+        let error = passkeys.delete(&user.uid, &passkey_uid, reauth(Some("wrong-password"), None)).await.err().unwrap();
+        assert_eq!(error.message, "Current password is incorrect");
+        assert_eq!(passkeys.list(&user.uid).await.unwrap().len(), 1);
+
+        passkeys.delete(&user.uid, &passkey_uid, reauth(Some(PASSWORD), None)).await.unwrap();
+        assert!(passkeys.list(&user.uid).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn accounts_without_a_password_confirm_with_a_code() {
+        let auth = TestAuth::default();
+        let passkeys = passkeys(&auth);
+        let user = auth.users.add("alice", "alice@example.com", None);
+        passkeys.start_registration(&user.uid, reauth(None, None)).await.unwrap();
+
+        let recovery_codes = auth.enable_totp(&user).await;
+        let error = passkeys.start_registration(&user.uid, reauth(None, None)).await.err().unwrap();
+        assert_eq!(error.message, "An authentication code is required");
+        passkeys.start_registration(&user.uid, reauth(None, Some(&recovery_codes[0]))).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn wrong_codes_lock_the_account() {
+        let auth = TestAuth::default();
+        let passkeys = passkeys(&auth);
+        let user = auth.users.add("alice", "alice@example.com", None);
+        let recovery_codes = auth.enable_totp(&user).await;
+
+        for _ in 0..4 {
+            let error = passkeys.start_registration(&user.uid, reauth(None, Some("00000-00000"))).await.err().unwrap();
+            assert_eq!(error.status_code, 400);
+        }
+        let error = passkeys.start_registration(&user.uid, reauth(None, Some(&recovery_codes[0]))).await.err().unwrap();
+        assert_eq!(error.status_code, 429);
+        assert!(auth.mfa_service.verify_code(&user.uid, &recovery_codes[0]).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn passkeys_work_as_a_second_factor_for_their_owner_only() {
+        let auth = TestAuth::default();
+        let passkeys = passkeys(&auth);
+        let user = auth.add_user("alice");
+        let authenticator = SoftwareAuthenticator::rs256();
+        register(&passkeys, &user, &authenticator).await;
+        auth.mfa.add_passkey_user(&user.uid);
+        let other = SoftwareAuthenticator::ed25519();
+        register(&passkeys, &auth.add_user("bob"), &other).await;
+
+        let login_dto = LoginDTO { identifier: user.username.clone(), password: PASSWORD.to_string(), device_name: None };
+        let challenge: MfaChallenge = match auth.service.login(login_dto, &client()).await.unwrap() {
+            LoginOutcome::MfaRequired(challenge) => challenge,
+            LoginOutcome::Authenticated(_) => panic!("Expected a second factor to be required"),
+        };
+        assert_eq!(challenge.methods, vec!["passkey"]);
+
+        let mfa_dto = |authenticator: &SoftwareAuthenticator, options: &RequestOptions| PasskeyMfaDTO {
+            challenge_token: challenge.challenge_token.clone(),
+            credential: authenticator.assert(RP_ID, ORIGIN, &options.challenge, false),
+            device_name: None,
+        };
+        let options = passkeys.start_mfa(&challenge.challenge_token).await.unwrap();
+        assert_eq!(options.allow_credentials.len(), 1);
+        let error = passkeys.finish_mfa(mfa_dto(&other, &options), &client()).await.err().unwrap();
+        assert_eq!(error.status_code, 401);
+
+        let options = passkeys.start_mfa(&challenge.challenge_token).await.unwrap();
+        let response = passkeys.finish_mfa(mfa_dto(&authenticator, &options), &client()).await.unwrap();
+        assert_eq!(response.user.uid, user.uid);
+    }
+}
@@ -0,0 +1,167 @@
+use std::fmt;
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, KeyId, Params, ParamsBuilder, Version};
+use crate::errors::service_error::ServiceError;
+use log::error;
+
+#[derive(Clone)]
+pub struct Argon2Settings {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    pub pepper: Option<String>,
+    pub pepper_id: String,
+}
+
+impl fmt::Debug for Argon2Settings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Argon2Settings")
+            .field("memory_kib", &self.memory_kib)
+            .field("iterations", &self.iterations)
+            .field("parallelism", &self.parallelism)
+            .field("pepper", &self.pepper.as_ref().map(|_| "<redacted>"))
+            .field("pepper_id", &self.pepper_id)
+            .finish()
+    }
+}
+
+// Hashes are argon2id with the configured cost. A peppered hash records the pepper id as the PHC
+// `keyid`, so hashes made before the pepper was introduced still verify and get upgraded on login.
+pub struct Argon2Hasher {
+    params: Params,
+    pepper: Option<(Vec<u8>, KeyId)>,
+    // Verified against when there is no real hash, so a missing account takes as long as a wrong password.
+    dummy_hash: String,
+}
+
+impl Argon2Hasher {
+    pub fn new(settings: &Argon2Settings) -> Result<Self, String> {
+        let pepper = match &settings.pepper {
+            Some(pepper) => {
+                let keyid = KeyId::new(settings.pepper_id.as_bytes())
+                    .map_err(|e| format!("Invalid pepper id: {}", e))?;
+                Some((pepper.as_bytes().to_vec(), keyid))
+            }
+            None => None,
+        };
+        let mut builder = ParamsBuilder::new();
+        builder
+            .m_cost(settings.memory_kib)
+            .t_cost(settings.iterations)
+            .p_cost(settings.parallelism);
+        if let Some((_, keyid)) = &pepper {
+            builder.keyid(*keyid);
+        }
+        let params = builder.build().map_err(|e| format!("Invalid Argon2 parameters: {}", e))?;
+        let mut hasher = Self { params, pepper, dummy_hash: String::new() };
+        hasher.dummy_hash = hasher.hash("dummy password").map_err(|e| e.message)?;
+        Ok(hasher)
+    }
+
+    pub fn hash(&self, password: &str) -> Result<String, ServiceError> {
+        let salt = SaltString::generate(&mut OsRng);
+        let secret = self.pepper.as_ref().map(|(pepper, _)| pepper.as_slice());
+        self.argon2(secret, self.params.clone())?
+            .hash_password(password.as_bytes(), &salt)
+            .map(|h| h.to_string())
+            .map_err(|e| ServiceError::internal_error(&format!("Password hashing error: {}", e)))
+    }
+
+    // Unknown accounts and those created through an identity provider have no password and never
+    // match, but still pay for a verification.
+    pub fn verify(&self, password: &str, hashed_password: Option<&str>) -> Result<(), ServiceError> {
+        let Some(hashed_password) = hashed_password else {
+            let _ = self.verify(password, Some(&self.dummy_hash));
+            return Err(ServiceError::bad_request("Incorrect email or password"));
+        };
+        let password_hash = PasswordHash::new(hashed_password)
+            .map_err(|e| ServiceError::internal_error(&format!("Error parsing password hash: {}", e)))?;
+        let keyid = Params::try_from(&password_hash)
+            .map_err(|e| ServiceError::internal_error(&format!("Error parsing password hash: {}", e)))?
+            .keyid()
+            .to_vec();
+
+        let secret = match &self.pepper {
+            _ if keyid.is_empty() => None,
+            Some((pepper, pepper_id)) if pepper_id.as_bytes() == keyid.as_slice() => Some(pepper.as_slice()),
+            _ => {
+                error!("Password hash uses pepper id {:?}, which is not configured", String::from_utf8_lossy(&keyid));
+                return Err(ServiceError::bad_request("Incorrect email or password"));
+            }
+        };
+        self.argon2(secret, Params::default())?
+            .verify_password(password.as_bytes(), &password_hash)
+            .map_err(|_| ServiceError::bad_request("Incorrect email or password"))
+    }
+
+    // Only meaningful for a hash that has just been verified.
+    pub fn needs_rehash(&self, hashed_password: &str) -> bool {
+        let Ok(password_hash) = PasswordHash::new(hashed_password) else {
+            return true;
+        };
+        let Ok(params) = Params::try_from(&password_hash) else {
+            return true;
+        };
+        let expected_keyid = self.pepper.as_ref().map(|(_, keyid)| keyid.as_bytes()).unwrap_or_default();
+
+        password_hash.algorithm != argon2::ARGON2ID_IDENT
+            || params.m_cost() < self.params.m_cost()
+            || params.t_cost() < self.params.t_cost()
+            || params.p_cost() < self.params.p_cost()
+            || params.keyid() != expected_keyid
+    }
+
+    fn argon2<'a>(&self, secret: Option<&'a [u8]>, params: Params) -> Result<Argon2<'a>, ServiceError> {
+        match secret {
+            Some(secret) => Argon2::new_with_secret(secret, Algorithm::Argon2id, Version::V0x13, params)
+                .map_err(|e| ServiceError::internal_error(&format!("Invalid password pepper: {}", e))),
+            None => Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hasher(memory_kib: u32, iterations: u32, pepper: Option<&str>) -> Argon2Hasher {
+        Argon2Hasher::new(&Argon2Settings {
+            memory_kib,
+            iterations,
+            parallelism: 1,
+            pepper: pepper.map(String::from),
+            pepper_id: "p1".to_string(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn hashes_verify_with_the_same_settings() {
+        let hasher = hasher(1024, 1, Some("pepper"));
+        let hash = hasher.hash("password1").unwrap();
+        assert!(hasher.verify("password1", Some(&hash)).is_ok());
+        assert!(hasher.verify("password2", Some(&hash)).is_err());
+        assert!(!hasher.needs_rehash(&hash));
+    }
+
+    #[test]
+    fn weaker_parameters_need_a_rehash() {
+        let weak = hasher(1024, 1, None).hash("password1").unwrap();
+        let strong = hasher(2048, 2, None);
+        assert!(strong.verify("password1", Some(&weak)).is_ok());
+        assert!(strong.needs_rehash(&weak));
+        assert!(!hasher(512, 1, None).needs_rehash(&weak));
+    }
+
+    #[test]
+    fn unpeppered_hashes_still_verify_once_a_pepper_is_set() {
+        let legacy = hasher(1024, 1, None).hash("password1").unwrap();
+        let peppered = hasher(1024, 1, Some("pepper"));
+        assert!(peppered.verify("password1", Some(&legacy)).is_ok());
+        assert!(peppered.needs_rehash(&legacy));
+
+        let hash = peppered.hash("password1").unwrap();
+        assert!(hasher(1024, 1, None).verify("password1", Some(&hash)).is_err());
+        assert!(hasher(1024, 1, Some("other")).verify("password1", Some(&hash)).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::HashSet;
+use std::fs;
+use validator::Validate;
+use crate::errors::service_error::{field_errors, ServiceError};
+
+const BUNDLED_BREACHED_PASSWORDS: &str = include_str!("../../data/breached_passwords.txt");
+
+#[derive(Debug, Clone)]
+pub struct PasswordPolicySettings {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub min_strength_bits: f64,
+    pub breached_list_path: Option<String>,
+    // Set in production, where the bundled list alone is too short to be worth much.
+    pub require_breached_list: bool,
+}
+
+// One policy for every place a password is chosen: signup, change and reset.
+pub struct PasswordPolicy {
+    settings: PasswordPolicySettings,
+    breached: HashSet<String>,
+}
+
+impl PasswordPolicy {
+    pub fn new(settings: &PasswordPolicySettings) -> Result<Self, String> {
+        if settings.min_length > settings.max_length {
+            return Err("PASSWORD_MIN_LENGTH must not be greater than PASSWORD_MAX_LENGTH".into());
+        }
+        if settings.require_breached_list && settings.breached_list_path.is_none() {
+            return Err("PASSWORD_BREACHED_LIST_PATH must be set when PASSWORD_REQUIRE_BREACHED_LIST is true".into());
+        }
+        let mut breached: HashSet<String> = parse_list(BUNDLED_BREACHED_PASSWORDS).collect();
+        if let Some(path) = &settings.breached_list_path {
+            let list = fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read breached password list {}: {}", path, e))?;
+            breached.extend(parse_list(&list));
+        }
+        Ok(Self { settings: settings.clone(), breached })
+    }
+
+    // `user_inputs` are values the password must not contain, such as the username and email.
+    pub fn check(&self, password: &str, user_inputs: &[&str]) -> Vec<String> {
+        let mut problems = Vec::new();
+        let length = password.chars().count();
+        if length < self.settings.min_length {
+            problems.push(format!("Password must be at least {} characters long", self.settings.min_length));
+        }
+        if length > self.settings.max_length {
+            problems.push(format!("Password must be at most {} characters long", self.settings.max_length));
+        }
+
+        let lowercase = password.to_lowercase();
+        if user_inputs.iter()
+            .flat_map(|input| personal_terms(input))
+            .any(|term| lowercase.contains(&term))
+        {
+            problems.push("Password must not contain your username or email".into());
+        }
+        if self.breached.contains(&lowercase) {
+            problems.push("Password appears in a list of breached passwords".into());
+        } else if length >= self.settings.min_length && strength_bits(password) < self.settings.min_strength_bits {
+            problems.push("Password is too easy to guess, use a longer mix of words, numbers and symbols".into());
+        }
+        problems
+    }
+
+    // Validates the rest of the DTO as usual and adds policy problems under the password field,
+    // so clients get every field error in one response.
+    pub fn validate<D: Validate>(&self, dto: &D, field: &str, password: &str, user_inputs: &[&str]) -> Result<(), ServiceError> {
+        let mut errors = dto.validate().err().map(|e| field_errors(&e)).unwrap_or_default();
+        let problems = self.check(password, user_inputs);
+        if !problems.is_empty() {
+            errors.entry(field.to_string()).or_default().extend(problems);
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ServiceError::invalid_fields(errors))
+        }
+    }
+}
+
+fn parse_list(list: &str) -> impl Iterator<Item = String> + '_ {
+    list.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_lowercase)
+}
+
+// An email contributes its local part as well, since that is what people tend to reuse.
+fn personal_terms(input: &str) -> Vec<String> {
+    let input = input.trim().to_lowercase();
+    let mut terms = vec![input.clone()];
+    if let Some((local, _)) = input.split_once('@') {
+        terms.push(local.to_string());
+    }
+    terms.retain(|term| term.chars().count() >= 3);
+    terms
+}
+
+// A rough guessing-entropy estimate: every character is worth log2 of the alphabet the password
+// draws from, except repeats and runs like `aaa` or `1234`, which are worth a single bit each.
+fn strength_bits(password: &str) -> f64 {
+    let chars: Vec<char> = password.chars().collect();
+    let mut pool = 0u32;
+    if chars.iter().any(char::is_ascii_lowercase) { pool += 26; }
+    if chars.iter().any(char::is_ascii_uppercase) { pool += 26; }
+    if chars.iter().any(char::is_ascii_digit) { pool += 10; }
+    if chars.iter().any(char::is_ascii_punctuation) || chars.contains(&' ') { pool += 33; }
+    if chars.iter().any(|c| !c.is_ascii()) { pool += 100; }
+    let per_char = f64::from(pool.max(1)).log2();
+
+    chars.iter().enumerate()
+        .map(|(i, c)| {
+            let predictable = i > 0 && (*c as i64 - chars[i - 1] as i64).abs() <= 1;
+            if predictable { 1.0 } else { per_char }
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy::new(&PasswordPolicySettings {
+            min_length: 8,
+            max_length: 128,
+            min_strength_bits: 40.0,
+            breached_list_path: None,
+            require_breached_list: false,
+        }).unwrap()
+    }
+
+    #[test]
+    fn accepts_a_strong_password() {
+        assert!(policy().check("violet-Harbor-73", &["alice", "alice@example.com"]).is_empty());
+    }
+
+    #[test]
+    fn rejects_breached_and_weak_passwords() {
+        let policy = policy();
+        assert_eq!(policy.check("Password123", &[]), vec!["Password appears in a list of breached passwords"]);
+        assert_eq!(policy.check("aaaaaaaaaaaa", &[]).len(), 1);
+        assert_eq!(policy.check("abcdefghijk", &[]).len(), 1);
+        assert_eq!(policy.check("short", &[]), vec!["Password must be at least 8 characters long"]);
+    }
+
+    #[test]
+    fn rejects_passwords_containing_the_username_or_email() {
+        let policy = policy();
+        let inputs = ["Alice", "alice.smith@example.com"];
+        assert!(policy.check("my-ALICE-pass-91", &inputs).contains(&"Password must not contain your username or email".to_string()));
+        assert!(!policy.check("xQalice.smith!7", &inputs).is_empty());
+        assert!(policy.check("violet-Harbor-73", &inputs).is_empty());
+    }
+
+    #[test]
+    fn reads_the_configured_breached_list() {
+        let path = std::env::temp_dir().join(format!("breached-{}.txt", uuid::Uuid::new_v4()));
+        fs::write(&path, "# local additions\nviolet-Harbor-73\n").unwrap();
+        let policy = PasswordPolicy::new(&PasswordPolicySettings {
+            breached_list_path: Some(path.to_string_lossy().into_owned()),
+            ..policy().settings
+        }).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(policy.check("VIOLET-harbor-73", &[]).len(), 1);
+    }
+
+    #[test]
+    fn a_required_breached_list_must_be_configured() {
+        let settings = PasswordPolicySettings { require_breached_list: true, ..policy().settings };
+        assert!(PasswordPolicy::new(&settings).is_err());
+    }
+
+    #[test]
+    fn reports_policy_problems_next_to_other_field_errors() {
+        #[derive(Validate)]
+        struct Signup {
+            #[validate(email(message = "Invalid email format"))]
+            email: String,
+        }
+        let dto = Signup { email: "nope".into() };
+        let error = policy().validate(&dto, "password", "qwerty", &[]).unwrap_err();
+        let errors = error.errors.unwrap();
+        assert_eq!(errors["email"], vec!["Invalid email format"]);
+        assert_eq!(errors["password"].len(), 2);
+        assert_eq!(error.status_code, 400);
+    }
+}
@@ -1,53 +1,73 @@
-use std::sync::Arc;
 use actix_web::Result;
+use std::sync::Arc;
 use sqlx::PgPool;
 use uuid::Uuid;
-use argon2::{password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, SaltString, PasswordVerifier}, Argon2};
-use chrono::{Utc, DateTime};
-use crate::models::user::{LoginDTO, User, UserDTO, LoginResponse};
-use shared::models::user_token::UserToken;
+use crate::models::user::{User, UserDTO};
+use crate::models::profile::UpdateProfileDTO;
+use crate::models::directory::{UserCursor, UserPage, UserSearch, UserSearchQuery, UserSort, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+use crate::models::role::{Role, RoleDTO};
 use crate::repositories::user_repository::{UserRepository, PgUserRepository};
+use crate::services::password_hasher::Argon2Hasher;
+use crate::services::password_policy::PasswordPolicy;
 use crate::errors::service_error::ServiceError;
 use log::{info, error};
 use validator::Validate;
 
 pub struct UserService<T: UserRepository> {
     repository: T,
-    jwt_secret: Arc<String>,
+    password_hasher: Arc<Argon2Hasher>,
+    password_policy: Arc<PasswordPolicy>,
 }
 
 impl UserService<PgUserRepository> {
-    pub fn new(pool: PgPool, jwt_secret: Arc<String>) -> Self {
-        Self {
-            repository: PgUserRepository::new(pool),
-            jwt_secret,
-        }
+    pub fn new(pool: PgPool, password_hasher: Arc<Argon2Hasher>, password_policy: Arc<PasswordPolicy>) -> Self {
+        Self::with_repository(PgUserRepository::new(pool), password_hasher, password_policy)
     }
 }
 
 impl<T: UserRepository> UserService<T> {
-    pub async fn get_all(&self) -> Result<Vec<User>, ServiceError> {
-        info!("Fetching all users");
-        self.repository.get_all().await
+    pub fn with_repository(repository: T, password_hasher: Arc<Argon2Hasher>, password_policy: Arc<PasswordPolicy>) -> Self {
+        Self { repository, password_hasher, password_policy }
+    }
+
+    // Searches sort by relevance unless asked otherwise; larger limits are capped at MAX_PAGE_SIZE.
+    pub async fn get_all(&self, query: UserSearchQuery) -> Result<UserPage, ServiceError> {
+        query.validate().map_err(|e| ServiceError::invalid_input(&e))?;
+        let search_query = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty()).map(str::to_string);
+        let sort = query.sort.unwrap_or(if search_query.is_some() { UserSort::Relevance } else { UserSort::Username });
+        if sort == UserSort::Relevance && search_query.is_none() {
+            return Err(ServiceError::bad_request("Sorting by relevance requires a search query"));
+        }
+        let after = query.cursor.as_deref()
+            .map(|cursor| UserCursor::decode(cursor)
+                .filter(|cursor| cursor.sort() == sort)
+                .ok_or_else(|| ServiceError::bad_request("Invalid cursor")))
+            .transpose()?;
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+
+        info!("Fetching users, query: {:?}, sort: {:?}", search_query, sort);
+        // One extra row tells whether there is a next page.
+        let mut users = self.repository.get_all(&UserSearch { query: search_query, sort, after, limit: limit + 1 }).await?;
+        let next_cursor = if users.len() as i64 > limit {
+            users.truncate(limit as usize);
+            users.last().map(|user| UserCursor::after(user, sort).encode())
+        } else {
+            None
+        };
+        Ok(UserPage { users, next_cursor })
     }
     
     pub async fn get_by_id(&self, uid: &str) -> Result<User, ServiceError> {
         let uid = Uuid::parse_str(uid).map_err(|_| ServiceError::bad_request("Invalid UUID"))?;
 
-        self.repository.get_by_id(&uid).await
+        Ok(self.repository.get_by_id(&uid).await?)
     }
     
     pub async fn signup(&self, user_dto: UserDTO) -> Result<User, ServiceError> {
         info!("Signing up user with email: {}", user_dto.email);
-        user_dto.validate().map_err(|e| {
-            let errors = e.to_string();
-            ServiceError::bad_request(&errors)
-        })?;
-        if user_dto.username.is_empty() { return Err(ServiceError::bad_request("Username cannot be empty")); }
-        if !user_dto.email.contains('@') { return Err(ServiceError::bad_request("Invalid email")); }
-        if user_dto.password.len() < 8 { return Err(ServiceError::bad_request("Password too short")); }
-
-        let password_hash = hash_password(&user_dto.password)?;
+        self.password_policy.validate(&user_dto, "password", &user_dto.password, &[&user_dto.username, &user_dto.email])?;
+
+        let password_hash = self.password_hasher.hash(&user_dto.password)?;
         let new_user_dto = UserDTO {
             username: user_dto.username,
             email: user_dto.email,
@@ -56,49 +76,148 @@ impl<T: UserRepository> UserService<T> {
 
         self.repository.create(&new_user_dto).await.map_err(|e| {
             error!("Signup error: {}", e);
-            if e.message.contains("duplicate key") {
-                ServiceError::bad_request("Email already exists")
-            } else {
-                e
-            }
+            e.into()
         })
     }
 
-    pub async fn login(&self, login_dto: LoginDTO) -> Result<LoginResponse, ServiceError> {
-        info!("Login attempt for email: {}", login_dto.email);
-        login_dto.validate().map_err(|e| {
-            let errors = e.to_string();
-            ServiceError::bad_request(&errors)
-        })?;
+    // Setting a new status without an expiry also clears the old expiry, so it doesn't vanish early.
+    pub async fn update_profile(&self, uid: &Uuid, mut dto: UpdateProfileDTO) -> Result<User, ServiceError> {
+        dto.validate().map_err(|e| ServiceError::invalid_input(&e))?;
+        if dto.is_empty() {
+            return Ok(self.repository.get_by_id(uid).await?);
+        }
+        if dto.status_text.is_some() && dto.status_expires_at.is_none() {
+            dto.status_expires_at = Some(None);
+        }
 
-        let user = self.repository.get_by_email(&login_dto.email).await?;
-        verify_password(&login_dto.password, &user.password_hash)?;
-        
-        let ttl = chrono::Duration::weeks(1);
-        let user_token = UserToken::new(user.uid, ttl);
-        let token = user_token.generate_token(&self.jwt_secret).map_err(|e| {
-            error!("Token generation failed: {:?}", e);
-            ServiceError::internal_error(&format!("Error generating token: {:?}", e))
-        })?;
-        let expires_at = DateTime::<Utc>::from_timestamp(user_token.exp, 0).unwrap();
+        let user = self.repository.update_profile(uid, &dto).await?;
+        info!("User {} updated their profile", uid);
+        Ok(user)
+    }
 
-        info!("User {} logged in successfully", user.email);
-        Ok(LoginResponse { user, token, expires_at })
+    pub async fn get_roles(&self, uid: &str) -> Result<Vec<Role>, ServiceError> {
+        let uid = Uuid::parse_str(uid).map_err(|_| ServiceError::bad_request("Invalid UUID"))?;
+        self.repository.get_by_id(&uid).await?;
+        Ok(self.repository.get_roles(&uid).await?)
     }
 
-}
+    pub async fn add_role(&self, uid: &str, role_dto: RoleDTO) -> Result<Vec<Role>, ServiceError> {
+        let uid = Uuid::parse_str(uid).map_err(|_| ServiceError::bad_request("Invalid UUID"))?;
+        self.repository.get_by_id(&uid).await?;
+        if !self.repository.role_exists(&role_dto.role).await? {
+            return Err(ServiceError::not_found(&format!("Role {} not found", role_dto.role)));
+        }
 
-fn hash_password(password: &str) -> Result<String, ServiceError> {
-    let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
-    argon2.hash_password(password.as_bytes(), &salt)
-        .map(|h| h.to_string())
-        .map_err(|e| ServiceError::internal_error(&format!("Password hashing error: {}", e)))
+        self.repository.add_role(&uid, &role_dto.role).await?;
+        Ok(self.repository.get_roles(&uid).await?)
+    }
+
+    pub async fn remove_role(&self, uid: &str, role: &str) -> Result<Vec<Role>, ServiceError> {
+        let uid = Uuid::parse_str(uid).map_err(|_| ServiceError::bad_request("Invalid UUID"))?;
+        if !self.repository.remove_role(&uid, role).await? {
+            return Err(ServiceError::not_found(&format!("User {} does not have role {}", uid, role)));
+        }
+        Ok(self.repository.get_roles(&uid).await?)
+    }
 }
 
-fn verify_password(password: &str, hashed_password: &str) -> Result<(), ServiceError> {
-    let password_hash = PasswordHash::new(hashed_password)
-        .map_err(|e| ServiceError::internal_error(&format!("Error parsing password hash: {}", e)))?;
-    Argon2::default().verify_password(password.as_bytes(), &password_hash)
-        .map_err(|_| ServiceError::bad_request("Incorrect email or password"))
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::fakes::FakeUserRepository;
+    use crate::services::auth_service::testing::TestAuth;
+
+    fn users(auth: &TestAuth) -> UserService<FakeUserRepository> {
+        UserService::with_repository(auth.users.clone(), auth.password_hasher.clone(), auth.password_policy.clone())
+    }
+
+    fn search(q: Option<&str>, sort: Option<UserSort>, cursor: Option<String>, limit: Option<i64>) -> UserSearchQuery {
+        UserSearchQuery { q: q.map(str::to_string), sort, cursor, limit }
+    }
+
+    fn usernames(page: &UserPage) -> Vec<&str> {
+        page.users.iter().map(|user| user.username.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn pages_end_when_no_extra_user_is_left() {
+        let auth = TestAuth::default();
+        let service = users(&auth);
+        for username in ["dave", "Alice", "carol", "bob"] {
+            auth.add_user(username);
+        }
+
+        let page = service.get_all(search(None, None, None, Some(2))).await.unwrap();
+        assert_eq!(usernames(&page), ["Alice", "bob"]);
+        let page = service.get_all(search(None, None, page.next_cursor, Some(2))).await.unwrap();
+        assert_eq!(usernames(&page), ["carol", "dave"]);
+        assert!(page.next_cursor.is_none());
+
+        let page = service.get_all(search(None, Some(UserSort::Newest), None, Some(3))).await.unwrap();
+        assert_eq!(page.users.len(), 3);
+        let page = service.get_all(search(None, Some(UserSort::Newest), page.next_cursor, Some(3))).await.unwrap();
+        assert_eq!(page.users.len(), 1);
+        assert!(page.next_cursor.is_none());
+    }
+
+    #[tokio::test]
+    async fn caps_the_page_size() {
+        let auth = TestAuth::default();
+        let service = users(&auth);
+        for i in 0..MAX_PAGE_SIZE + 2 {
+            auth.users.add(&format!("user{:03}", i), &format!("user{:03}@example.com", i), None);
+        }
+
+        let page = service.get_all(search(None, None, None, Some(1000))).await.unwrap();
+        assert_eq!(page.users.len() as i64, MAX_PAGE_SIZE);
+        let page = service.get_all(search(None, None, page.next_cursor, Some(1000))).await.unwrap();
+        assert_eq!(usernames(&page), ["user100", "user101"]);
+
+        let error = service.get_all(search(None, None, None, Some(0))).await.err().unwrap();
+        assert_eq!(error.status_code, 400);
+    }
+
+    #[tokio::test]
+    async fn cursors_only_continue_their_own_sort() {
+        let auth = TestAuth::default();
+        let service = users(&auth);
+        for username in ["alice", "bob"] {
+            auth.add_user(username);
+        }
+
+        let cursor = service.get_all(search(None, None, None, Some(1))).await.unwrap().next_cursor;
+        assert!(cursor.is_some());
+        let error = service.get_all(search(None, Some(UserSort::Newest), cursor, None)).await.err().unwrap();
+        assert_eq!((error.status_code, error.message.as_str()), (400, "Invalid cursor"));
+
+        let error = service.get_all(search(None, None, Some("not a cursor".into()), None)).await.err().unwrap();
+        assert_eq!(error.message, "Invalid cursor");
+    }
+
+    #[tokio::test]
+    async fn searches_sort_by_relevance_which_needs_a_query() {
+        let auth = TestAuth::default();
+        let service = users(&auth);
+        for username in ["malcolm", "alina", "alice"] {
+            auth.add_user(username);
+        }
+        let bob = auth.add_user("bob");
+        auth.users.update_profile(&bob.uid, &UpdateProfileDTO {
+            display_name: Some(Some("Alfred Bob".into())),
+            ..Default::default()
+        }).await.unwrap();
+
+        let page = service.get_all(search(Some(" AL "), None, None, Some(2))).await.unwrap();
+        assert_eq!(usernames(&page), ["alice", "alina"]);
+        let page = service.get_all(search(Some("al"), None, page.next_cursor, Some(2))).await.unwrap();
+        assert_eq!(usernames(&page), ["bob", "malcolm"]);
+        assert!(page.users[0].profile.display_name.is_some());
+
+        for q in [None, Some("   ")] {
+            let error = service.get_all(search(q, Some(UserSort::Relevance), None, None)).await.err().unwrap();
+            assert_eq!(error.message, "Sorting by relevance requires a search query");
+        }
+        let page = service.get_all(search(Some("   "), None, None, None)).await.unwrap();
+        assert_eq!(page.users.len(), 4);
+    }
+}
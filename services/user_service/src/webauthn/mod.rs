@@ -0,0 +1,421 @@
+#[cfg(test)]
+pub mod software_authenticator;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use ciborium::value::{Integer, Value};
+use ring::signature::{self, RsaPublicKeyComponents, UnparsedPublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+pub const COSE_ES256: i32 = -7;
+pub const COSE_EDDSA: i32 = -8;
+pub const COSE_RS256: i32 = -257;
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_CREDENTIAL: u8 = 0x40;
+const TIMEOUT_MS: u64 = 300_000;
+
+#[derive(Debug, Clone)]
+pub struct WebauthnConfig {
+    pub rp_id: String,
+    pub rp_name: String,
+    pub origin: String,
+}
+
+// The JSON a browser produces for navigator.credentials.create(), binary fields in base64url.
+#[derive(Serialize, Deserialize)]
+pub struct RegistrationCredential {
+    pub id: String,
+    pub response: AttestationResponse,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AttestationResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    #[serde(rename = "attestationObject")]
+    pub attestation_object: String,
+}
+
+// The JSON a browser produces for navigator.credentials.get().
+#[derive(Serialize, Deserialize)]
+pub struct AssertionCredential {
+    pub id: String,
+    pub response: AssertionResponse,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AssertionResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    #[serde(rename = "authenticatorData")]
+    pub authenticator_data: String,
+    pub signature: String,
+    #[serde(rename = "userHandle", default)]
+    pub user_handle: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreationOptions {
+    pub challenge: String,
+    pub rp: RelyingParty,
+    pub user: UserEntity,
+    pub pub_key_cred_params: Vec<CredentialParameter>,
+    pub timeout: u64,
+    pub exclude_credentials: Vec<CredentialDescriptor>,
+    pub authenticator_selection: AuthenticatorSelection,
+    pub attestation: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestOptions {
+    pub challenge: String,
+    pub rp_id: String,
+    pub timeout: u64,
+    pub allow_credentials: Vec<CredentialDescriptor>,
+    pub user_verification: &'static str,
+}
+
+#[derive(Serialize)]
+pub struct RelyingParty {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserEntity {
+    pub id: String,
+    pub name: String,
+    pub display_name: String,
+}
+
+#[derive(Serialize)]
+pub struct CredentialParameter {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub alg: i32,
+}
+
+#[derive(Serialize)]
+pub struct CredentialDescriptor {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub id: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticatorSelection {
+    pub resident_key: &'static str,
+    pub user_verification: &'static str,
+}
+
+#[derive(Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    kind: String,
+    challenge: String,
+    origin: String,
+}
+
+// What registration yields and what an assertion is checked against.
+pub struct VerifiedCredential {
+    pub credential_id: String,
+    pub public_key: Vec<u8>,
+    pub algorithm: i32,
+    pub sign_count: u32,
+}
+
+struct AuthenticatorData<'a> {
+    flags: u8,
+    sign_count: u32,
+    attested_credential: &'a [u8],
+}
+
+// Registration and assertion checks from the WebAuthn spec. Attestation statements are not
+// verified: we ask for "none" and trust the platform, as passkey providers don't attest anyway.
+pub struct Webauthn {
+    config: WebauthnConfig,
+}
+
+impl Webauthn {
+    pub fn new(config: WebauthnConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn creation_options(&self, challenge: &str, user_uid: &Uuid, email: &str, username: &str, existing: &[String]) -> CreationOptions {
+        CreationOptions {
+            challenge: challenge.to_string(),
+            rp: RelyingParty { id: self.config.rp_id.clone(), name: self.config.rp_name.clone() },
+            user: UserEntity {
+                id: URL_SAFE_NO_PAD.encode(user_uid.as_bytes()),
+                name: email.to_string(),
+                display_name: username.to_string(),
+            },
+            pub_key_cred_params: [COSE_ES256, COSE_EDDSA, COSE_RS256]
+                .into_iter()
+                .map(|alg| CredentialParameter { kind: "public-key", alg })
+                .collect(),
+            timeout: TIMEOUT_MS,
+            exclude_credentials: descriptors(existing),
+            authenticator_selection: AuthenticatorSelection { resident_key: "preferred", user_verification: "preferred" },
+            attestation: "none",
+        }
+    }
+
+    // An empty allow list lets the authenticator offer any discoverable passkey for this site.
+    pub fn request_options(&self, challenge: &str, allowed: &[String], user_verification: &'static str) -> RequestOptions {
+        RequestOptions {
+            challenge: challenge.to_string(),
+            rp_id: self.config.rp_id.clone(),
+            timeout: TIMEOUT_MS,
+            allow_credentials: descriptors(allowed),
+            user_verification,
+        }
+    }
+
+    pub fn verify_registration(&self, credential: &RegistrationCredential, challenge: &str) -> Result<VerifiedCredential, String> {
+        let client_data_json = decode(&credential.response.client_data_json, "clientDataJSON")?;
+        self.check_client_data(&client_data_json, "webauthn.create", challenge)?;
+
+        let attestation = decode(&credential.response.attestation_object, "attestationObject")?;
+        let attestation: Value = ciborium::de::from_reader(attestation.as_slice())
+            .map_err(|e| format!("malformed attestationObject: {}", e))?;
+        let auth_data = map_entry(&attestation, &Value::Text("authData".into()))
+            .and_then(Value::as_bytes)
+            .ok_or("attestationObject has no authData")?;
+        let auth_data = self.parse_authenticator_data(auth_data)?;
+        if auth_data.flags & FLAG_ATTESTED_CREDENTIAL == 0 {
+            return Err("no attested credential data".into());
+        }
+
+        let data = auth_data.attested_credential;
+        if data.len() < 18 {
+            return Err("attested credential data is truncated".into());
+        }
+        let id_len = u16::from_be_bytes([data[16], data[17]]) as usize;
+        let credential_id = data.get(18..18 + id_len).ok_or("credential id is truncated")?;
+        let public_key = &data[18 + id_len..];
+        let cose_key: Value = ciborium::de::from_reader(public_key)
+            .map_err(|e| format!("malformed credential public key: {}", e))?;
+        let algorithm = cose_algorithm(&cose_key)?;
+        // Re-encoding drops any extension data the authenticator appended after the key.
+        let mut public_key = Vec::new();
+        ciborium::ser::into_writer(&cose_key, &mut public_key).map_err(|e| e.to_string())?;
+
+        let credential_id = URL_SAFE_NO_PAD.encode(credential_id);
+        if credential_id != credential.id.trim_end_matches('=') {
+            return Err("credential id does not match the authenticator data".into());
+        }
+        Ok(VerifiedCredential { credential_id, public_key, algorithm, sign_count: auth_data.sign_count })
+    }
+
+    // Returns the new signature counter to store.
+    pub fn verify_assertion(
+        &self,
+        credential: &AssertionCredential,
+        challenge: &str,
+        stored: &VerifiedCredential,
+        require_user_verification: bool,
+    ) -> Result<u32, String> {
+        let client_data_json = decode(&credential.response.client_data_json, "clientDataJSON")?;
+        self.check_client_data(&client_data_json, "webauthn.get", challenge)?;
+
+        let raw_auth_data = decode(&credential.response.authenticator_data, "authenticatorData")?;
+        let auth_data = self.parse_authenticator_data(&raw_auth_data)?;
+        if require_user_verification && auth_data.flags & FLAG_USER_VERIFIED == 0 {
+            return Err("user verification is required".into());
+        }
+
+        let mut signed = raw_auth_data.clone();
+        signed.extend_from_slice(&Sha256::digest(&client_data_json));
+        let signature = decode(&credential.response.signature, "signature")?;
+        verify_signature(&stored.public_key, &signed, &signature)?;
+
+        // Authenticators that don't count always report zero; otherwise the counter must move forward.
+        if (auth_data.sign_count != 0 || stored.sign_count != 0) && auth_data.sign_count <= stored.sign_count {
+            return Err("signature counter did not increase, the authenticator may be cloned".into());
+        }
+        Ok(auth_data.sign_count)
+    }
+
+    fn check_client_data(&self, client_data_json: &[u8], kind: &str, challenge: &str) -> Result<(), String> {
+        let client_data: ClientData = serde_json::from_slice(client_data_json)
+            .map_err(|e| format!("malformed clientDataJSON: {}", e))?;
+        if client_data.kind != kind {
+            return Err(format!("unexpected ceremony type {}", client_data.kind));
+        }
+        if client_data.challenge.trim_end_matches('=') != challenge {
+            return Err("challenge mismatch".into());
+        }
+        if client_data.origin != self.config.origin {
+            return Err(format!("unexpected origin {}", client_data.origin));
+        }
+        Ok(())
+    }
+
+    fn parse_authenticator_data<'a>(&self, auth_data: &'a [u8]) -> Result<AuthenticatorData<'a>, String> {
+        if auth_data.len() < 37 {
+            return Err("authenticator data is truncated".into());
+        }
+        if auth_data[..32] != Sha256::digest(self.config.rp_id.as_bytes())[..] {
+            return Err("authenticator data is for another relying party".into());
+        }
+        let flags = auth_data[32];
+        if flags & FLAG_USER_PRESENT == 0 {
+            return Err("user presence is required".into());
+        }
+        Ok(AuthenticatorData {
+            flags,
+            sign_count: u32::from_be_bytes([auth_data[33], auth_data[34], auth_data[35], auth_data[36]]),
+            attested_credential: &auth_data[37..],
+        })
+    }
+}
+
+// The challenge a response was made for, so the server-side record can be found before verifying.
+pub fn client_challenge(client_data_json: &str) -> Result<String, String> {
+    let client_data: ClientData = serde_json::from_slice(&decode(client_data_json, "clientDataJSON")?)
+        .map_err(|e| format!("malformed clientDataJSON: {}", e))?;
+    Ok(client_data.challenge.trim_end_matches('=').to_string())
+}
+
+pub fn user_handle(user_uid: &Uuid) -> String {
+    URL_SAFE_NO_PAD.encode(user_uid.as_bytes())
+}
+
+fn descriptors(credential_ids: &[String]) -> Vec<CredentialDescriptor> {
+    credential_ids
+        .iter()
+        .map(|id| CredentialDescriptor { kind: "public-key", id: id.clone() })
+        .collect()
+}
+
+fn decode(value: &str, field: &str) -> Result<Vec<u8>, String> {
+    URL_SAFE_NO_PAD
+        .decode(value.trim_end_matches('='))
+        .map_err(|_| format!("{} is not base64url", field))
+}
+
+fn map_entry<'a>(map: &'a Value, key: &Value) -> Option<&'a Value> {
+    map.as_map()?.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+}
+
+fn cose_param(key: &Value, label: i64) -> Option<&Value> {
+    map_entry(key, &Value::Integer(Integer::from(label)))
+}
+
+fn cose_bytes(key: &Value, label: i64) -> Result<&[u8], String> {
+    cose_param(key, label)
+        .and_then(Value::as_bytes)
+        .map(Vec::as_slice)
+        .ok_or_else(|| format!("COSE key is missing parameter {}", label))
+}
+
+fn cose_algorithm(key: &Value) -> Result<i32, String> {
+    let algorithm = cose_param(key, 3)
+        .and_then(Value::as_integer)
+        .and_then(|alg| i32::try_from(alg).ok())
+        .ok_or("COSE key has no algorithm")?;
+    match algorithm {
+        COSE_ES256 | COSE_EDDSA | COSE_RS256 => Ok(algorithm),
+        _ => Err(format!("unsupported algorithm {}", algorithm)),
+    }
+}
+
+fn verify_signature(cose_key: &[u8], message: &[u8], signature: &[u8]) -> Result<(), String> {
+    let key: Value = ciborium::de::from_reader(cose_key).map_err(|e| format!("stored key is invalid: {}", e))?;
+    let result = match cose_algorithm(&key)? {
+        COSE_ES256 => {
+            let mut point = vec![0x04];
+            point.extend_from_slice(cose_bytes(&key, -2)?);
+            point.extend_from_slice(cose_bytes(&key, -3)?);
+            UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_ASN1, point).verify(message, signature)
+        }
+        COSE_EDDSA => UnparsedPublicKey::new(&signature::ED25519, cose_bytes(&key, -2)?).verify(message, signature),
+        _ => RsaPublicKeyComponents { n: cose_bytes(&key, -1)?, e: cose_bytes(&key, -2)? }
+            .verify(&signature::RSA_PKCS1_2048_8192_SHA256, message, signature),
+    };
+    result.map_err(|_| "signature verification failed".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::software_authenticator::SoftwareAuthenticator;
+
+    const ORIGIN: &str = "https://tickets.example.com";
+
+    fn webauthn() -> Webauthn {
+        Webauthn::new(WebauthnConfig {
+            rp_id: "tickets.example.com".to_string(),
+            rp_name: "TicketBan".to_string(),
+            origin: ORIGIN.to_string(),
+        })
+    }
+
+    fn register(authenticator: &SoftwareAuthenticator) -> VerifiedCredential {
+        let credential = authenticator.register("tickets.example.com", ORIGIN, "challenge-1");
+        webauthn().verify_registration(&credential, "challenge-1").unwrap()
+    }
+
+    #[test]
+    fn registers_and_signs_in_with_every_algorithm() {
+        for authenticator in [SoftwareAuthenticator::es256(), SoftwareAuthenticator::ed25519(), SoftwareAuthenticator::rs256()] {
+            let stored = register(&authenticator);
+            assert_eq!(stored.credential_id, authenticator.credential_id());
+
+            let assertion = authenticator.assert("tickets.example.com", ORIGIN, "challenge-2", true);
+            let sign_count = webauthn().verify_assertion(&assertion, "challenge-2", &stored, true).unwrap();
+            assert_eq!(sign_count, 1);
+        }
+    }
+
+    #[test]
+    fn registration_is_bound_to_challenge_origin_and_rp() {
+        let authenticator = SoftwareAuthenticator::es256();
+        let webauthn = webauthn();
+        let credential = authenticator.register("tickets.example.com", ORIGIN, "challenge-1");
+        assert!(webauthn.verify_registration(&credential, "other").is_err());
+
+        let phished = authenticator.register("tickets.example.com", "https://tickets.example.evil", "challenge-1");
+        assert!(webauthn.verify_registration(&phished, "challenge-1").is_err());
+
+        let other_rp = authenticator.register("example.evil", ORIGIN, "challenge-1");
+        assert!(webauthn.verify_registration(&other_rp, "challenge-1").is_err());
+    }
+
+    #[test]
+    fn rejects_bad_assertions() {
+        let authenticator = SoftwareAuthenticator::es256();
+        let stored = register(&authenticator);
+        let webauthn = webauthn();
+
+        let mut tampered = authenticator.assert("tickets.example.com", ORIGIN, "challenge-2", true);
+        tampered.response.signature = SoftwareAuthenticator::ed25519()
+            .assert("tickets.example.com", ORIGIN, "challenge-2", true)
+            .response
+            .signature;
+        assert!(webauthn.verify_assertion(&tampered, "challenge-2", &stored, true).is_err());
+
+        let unverified = authenticator.assert("tickets.example.com", ORIGIN, "challenge-3", false);
+        assert!(webauthn.verify_assertion(&unverified, "challenge-3", &stored, true).is_err());
+        let sign_count = webauthn.verify_assertion(&unverified, "challenge-3", &stored, false).unwrap();
+
+        let replayed = VerifiedCredential { sign_count, ..stored };
+        let stale = authenticator.assert_with_count("tickets.example.com", ORIGIN, "challenge-4", sign_count);
+        assert!(webauthn.verify_assertion(&stale, "challenge-4", &replayed, false).is_err());
+    }
+
+    #[test]
+    fn reads_the_challenge_from_client_data() {
+        let assertion = SoftwareAuthenticator::es256().assert("tickets.example.com", ORIGIN, "abc", true);
+        assert_eq!(client_challenge(&assertion.response.client_data_json).unwrap(), "abc");
+    }
+}
@@ -0,0 +1,158 @@
+// An in-memory authenticator for tests, producing the same JSON a browser would.
+use std::cell::Cell;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use ciborium::value::{Integer, Value};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::signature::{EcdsaKeyPair, Ed25519KeyPair, KeyPair, RsaKeyPair, RsaPublicKeyComponents, ECDSA_P256_SHA256_ASN1_SIGNING, RSA_PKCS1_SHA256};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use crate::webauthn::{AssertionCredential, AssertionResponse, AttestationResponse, RegistrationCredential, COSE_EDDSA, COSE_ES256, COSE_RS256};
+
+// ring can't generate RSA keys, so every RS256 authenticator shares this 2048 bit one.
+const RS256_KEY: &[u8] = include_bytes!("testdata/rs256_test_key.der");
+
+enum Key {
+    Es256(EcdsaKeyPair),
+    Ed25519(Ed25519KeyPair),
+    Rs256(RsaKeyPair),
+}
+
+pub struct SoftwareAuthenticator {
+    key: Key,
+    credential_id: Vec<u8>,
+    sign_count: Cell<u32>,
+    rng: SystemRandom,
+}
+
+impl SoftwareAuthenticator {
+    pub fn es256() -> Self {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+        let key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref(), &rng).unwrap();
+        Self::new(Key::Es256(key), rng)
+    }
+
+    pub fn ed25519() -> Self {
+        let rng = SystemRandom::new();
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+        Self::new(Key::Ed25519(Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap()), rng)
+    }
+
+    pub fn rs256() -> Self {
+        Self::new(Key::Rs256(RsaKeyPair::from_der(RS256_KEY).unwrap()), SystemRandom::new())
+    }
+
+    fn new(key: Key, rng: SystemRandom) -> Self {
+        let mut credential_id = vec![0u8; 16];
+        rng.fill(&mut credential_id).unwrap();
+        Self { key, credential_id, sign_count: Cell::new(0), rng }
+    }
+
+    pub fn credential_id(&self) -> String {
+        URL_SAFE_NO_PAD.encode(&self.credential_id)
+    }
+
+    pub fn register(&self, rp_id: &str, origin: &str, challenge: &str) -> RegistrationCredential {
+        let mut auth_data = authenticator_data(rp_id, 0x45, 0);
+        auth_data.extend_from_slice(&[0u8; 16]);
+        auth_data.extend_from_slice(&(self.credential_id.len() as u16).to_be_bytes());
+        auth_data.extend_from_slice(&self.credential_id);
+        ciborium::ser::into_writer(&self.cose_key(), &mut auth_data).unwrap();
+
+        let attestation = Value::Map(vec![
+            (Value::Text("fmt".into()), Value::Text("none".into())),
+            (Value::Text("attStmt".into()), Value::Map(vec![])),
+            (Value::Text("authData".into()), Value::Bytes(auth_data)),
+        ]);
+        let mut attestation_object = Vec::new();
+        ciborium::ser::into_writer(&attestation, &mut attestation_object).unwrap();
+
+        RegistrationCredential {
+            id: self.credential_id(),
+            response: AttestationResponse {
+                client_data_json: client_data("webauthn.create", challenge, origin),
+                attestation_object: URL_SAFE_NO_PAD.encode(attestation_object),
+            },
+        }
+    }
+
+    pub fn assert(&self, rp_id: &str, origin: &str, challenge: &str, user_verified: bool) -> AssertionCredential {
+        self.sign_count.set(self.sign_count.get() + 1);
+        let flags = if user_verified { 0x05 } else { 0x01 };
+        self.sign(rp_id, origin, challenge, flags, self.sign_count.get())
+    }
+
+    pub fn assert_with_count(&self, rp_id: &str, origin: &str, challenge: &str, sign_count: u32) -> AssertionCredential {
+        self.sign(rp_id, origin, challenge, 0x05, sign_count)
+    }
+
+    fn sign(&self, rp_id: &str, origin: &str, challenge: &str, flags: u8, sign_count: u32) -> AssertionCredential {
+        let auth_data = authenticator_data(rp_id, flags, sign_count);
+        let client_data_json = client_data("webauthn.get", challenge, origin);
+        let mut signed = auth_data.clone();
+        signed.extend_from_slice(&Sha256::digest(URL_SAFE_NO_PAD.decode(&client_data_json).unwrap()));
+        let signature = match &self.key {
+            Key::Es256(key) => key.sign(&self.rng, &signed).unwrap().as_ref().to_vec(),
+            Key::Ed25519(key) => key.sign(&signed).as_ref().to_vec(),
+            Key::Rs256(key) => {
+                let mut signature = vec![0u8; key.public().modulus_len()];
+                key.sign(&RSA_PKCS1_SHA256, &self.rng, &signed, &mut signature).unwrap();
+                signature
+            }
+        };
+
+        AssertionCredential {
+            id: self.credential_id(),
+            response: AssertionResponse {
+                client_data_json,
+                authenticator_data: URL_SAFE_NO_PAD.encode(auth_data),
+                signature: URL_SAFE_NO_PAD.encode(signature),
+                user_handle: None,
+            },
+        }
+    }
+
+    fn cose_key(&self) -> Value {
+        let int = |value: i64| Value::Integer(Integer::from(value));
+        match &self.key {
+            Key::Es256(key) => {
+                let point = key.public_key().as_ref();
+                Value::Map(vec![
+                    (int(1), int(2)),
+                    (int(3), int(COSE_ES256 as i64)),
+                    (int(-1), int(1)),
+                    (int(-2), Value::Bytes(point[1..33].to_vec())),
+                    (int(-3), Value::Bytes(point[33..].to_vec())),
+                ])
+            }
+            Key::Ed25519(key) => Value::Map(vec![
+                (int(1), int(1)),
+                (int(3), int(COSE_EDDSA as i64)),
+                (int(-1), int(6)),
+                (int(-2), Value::Bytes(key.public_key().as_ref().to_vec())),
+            ]),
+            Key::Rs256(key) => {
+                let public = RsaPublicKeyComponents::<Vec<u8>>::from(key.public());
+                Value::Map(vec![
+                    (int(1), int(3)),
+                    (int(3), int(COSE_RS256 as i64)),
+                    (int(-1), Value::Bytes(public.n)),
+                    (int(-2), Value::Bytes(public.e)),
+                ])
+            }
+        }
+    }
+}
+
+fn authenticator_data(rp_id: &str, flags: u8, sign_count: u32) -> Vec<u8> {
+    let mut auth_data = Sha256::digest(rp_id.as_bytes()).to_vec();
+    auth_data.push(flags);
+    auth_data.extend_from_slice(&sign_count.to_be_bytes());
+    auth_data
+}
+
+fn client_data(kind: &str, challenge: &str, origin: &str) -> String {
+    let client_data = json!({ "type": kind, "challenge": challenge, "origin": origin, "crossOrigin": false });
+    URL_SAFE_NO_PAD.encode(client_data.to_string())
+}
@@ -1,6 +1,5 @@
 use std::path::PathBuf;
 use std::env;
-use tonic_build;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let manifest_dir = env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR not set");
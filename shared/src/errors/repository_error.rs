@@ -0,0 +1,108 @@
+use std::fmt;
+use sqlx::postgres::PgDatabaseError;
+
+// What went wrong in the database, in terms a service can turn into a response. Constraint names
+// come from Postgres; `field` is the column part of a conventionally named constraint such as
+// `users_email_key`, when it can be read off the name.
+#[derive(Debug)]
+pub enum RepositoryError {
+    NotFound(String),
+    Conflict { constraint: Option<String>, field: Option<String> },
+    ForeignKey { constraint: Option<String>, field: Option<String> },
+    Transient(String),
+    Other(String),
+}
+
+impl RepositoryError {
+    pub fn not_found(message: &str) -> Self {
+        Self::NotFound(message.to_string())
+    }
+
+    pub fn other(message: &str) -> Self {
+        Self::Other(message.to_string())
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, Self::NotFound(_))
+    }
+
+    // The constraint a conflict or foreign key violation was reported for.
+    pub fn constraint(&self) -> Option<&str> {
+        match self {
+            Self::Conflict { constraint, .. } | Self::ForeignKey { constraint, .. } => constraint.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for RepositoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound(message) | Self::Transient(message) | Self::Other(message) => write!(f, "{}", message),
+            Self::Conflict { constraint, .. } => write!(f, "Unique constraint {} violated", constraint.as_deref().unwrap_or("<unknown>")),
+            Self::ForeignKey { constraint, .. } => write!(f, "Foreign key constraint {} violated", constraint.as_deref().unwrap_or("<unknown>")),
+        }
+    }
+}
+
+impl std::error::Error for RepositoryError {}
+
+impl From<sqlx::Error> for RepositoryError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::RowNotFound => Self::NotFound("Row not found".into()),
+            sqlx::Error::Database(db) => {
+                let constraint = db.constraint().map(String::from);
+                let table = db.try_downcast_ref::<PgDatabaseError>().and_then(PgDatabaseError::table);
+                let field = constraint.as_deref().and_then(|constraint| constraint_field(table, constraint));
+                match db.code().as_deref() {
+                    Some("23505") => Self::Conflict { constraint, field },
+                    Some("23503") => Self::ForeignKey { constraint, field },
+                    Some(code) if is_transient(code) => Self::Transient(db.message().to_string()),
+                    _ => Self::Other(db.message().to_string()),
+                }
+            }
+            sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed | sqlx::Error::Io(_) => Self::Transient(e.to_string()),
+            e => Self::Other(e.to_string()),
+        }
+    }
+}
+
+// Serialization failures, deadlocks, and the server refusing or dropping connections.
+fn is_transient(code: &str) -> bool {
+    matches!(code, "40001" | "40P01" | "53300" | "57P01" | "57P02" | "57P03") || code.starts_with("08")
+}
+
+// `users_email_key` -> `email`, `users_email_lower_key` -> `email`, `chat_participants_chat_uid_fkey`
+// -> `chat_uid`. Primary keys and unconventional names give no field.
+fn constraint_field(table: Option<&str>, constraint: &str) -> Option<String> {
+    let columns = match table {
+        Some(table) => constraint.strip_prefix(table)?.strip_prefix('_')?,
+        None => constraint,
+    };
+    let field = ["_lower_key", "_key", "_fkey"]
+        .iter()
+        .find_map(|suffix| columns.strip_suffix(suffix))?;
+    (!field.is_empty()).then(|| field.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_field_off_conventional_constraint_names() {
+        assert_eq!(constraint_field(Some("users"), "users_email_key").as_deref(), Some("email"));
+        assert_eq!(constraint_field(Some("users"), "users_username_lower_key").as_deref(), Some("username"));
+        assert_eq!(constraint_field(Some("chat_participants"), "chat_participants_chat_uid_fkey").as_deref(), Some("chat_uid"));
+        assert_eq!(constraint_field(Some("users"), "users_pkey"), None);
+        assert_eq!(constraint_field(Some("users"), "roles_name_key"), None);
+    }
+
+    #[test]
+    fn classifies_connection_problems_as_transient() {
+        assert!(matches!(RepositoryError::from(sqlx::Error::PoolTimedOut), RepositoryError::Transient(_)));
+        assert!(RepositoryError::from(sqlx::Error::RowNotFound).is_not_found());
+        assert!(is_transient("40P01") && is_transient("08006") && !is_transient("23505"));
+    }
+}
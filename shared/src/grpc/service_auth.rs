@@ -0,0 +1,114 @@
+use std::sync::Arc;
+use ring::digest::{digest, SHA256};
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::service::Interceptor;
+use tonic::{Request, Status};
+
+const SERVICE_AUTH_HEADER: &str = "authorization";
+const MIN_SERVICE_KEY_LEN: usize = 32;
+
+pub fn validate_service_key(key: &str) -> Result<(), String> {
+    if key.len() < MIN_SERVICE_KEY_LEN {
+        return Err(format!("Service keys must be at least {} characters long", MIN_SERVICE_KEY_LEN));
+    }
+    if MetadataValue::<Ascii>::try_from(format!("Bearer {}", key)).is_err() {
+        return Err("Service keys must be printable ASCII".to_string());
+    }
+    Ok(())
+}
+
+// Server side: rejects calls that don't carry one of the configured pre-shared keys.
+// Several keys can be accepted at once so they can be rotated without downtime.
+#[derive(Clone)]
+pub struct RequireServiceKey {
+    key_digests: Arc<Vec<Vec<u8>>>,
+}
+
+impl RequireServiceKey {
+    pub fn new(keys: &[String]) -> Self {
+        Self {
+            key_digests: Arc::new(keys.iter().map(|key| key_digest(key)).collect()),
+        }
+    }
+}
+
+impl Interceptor for RequireServiceKey {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        let key = request
+            .metadata()
+            .get(SERVICE_AUTH_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| Status::unauthenticated("Missing service credentials"))?;
+
+        // Comparing digests rather than raw keys means timing differences reveal nothing about the key.
+        let digest = key_digest(key);
+        if !self.key_digests.contains(&digest) {
+            log::warn!("Rejected gRPC call with an unknown service key");
+            return Err(Status::unauthenticated("Invalid service credentials"));
+        }
+
+        Ok(request)
+    }
+}
+
+// Client side: attaches the service key to every outgoing call.
+#[derive(Clone)]
+pub struct AttachServiceKey {
+    value: MetadataValue<Ascii>,
+}
+
+impl AttachServiceKey {
+    pub fn new(key: &str) -> Result<Self, String> {
+        validate_service_key(key)?;
+        let value = format!("Bearer {}", key)
+            .try_into()
+            .map_err(|_| "Service keys must be printable ASCII".to_string())?;
+        Ok(Self { value })
+    }
+}
+
+impl Interceptor for AttachServiceKey {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        request.metadata_mut().insert(SERVICE_AUTH_HEADER, self.value.clone());
+        Ok(request)
+    }
+}
+
+fn key_digest(key: &str) -> Vec<u8> {
+    digest(&SHA256, key.as_bytes()).as_ref().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "0123456789abcdef0123456789abcdef";
+
+    fn server() -> RequireServiceKey {
+        RequireServiceKey::new(&["old-key-old-key-old-key-old-key-".to_string(), KEY.to_string()])
+    }
+
+    #[test]
+    fn accepts_requests_signed_by_the_client_interceptor() {
+        let request = AttachServiceKey::new(KEY).unwrap().call(Request::new(())).unwrap();
+        assert!(server().call(request).is_ok());
+    }
+
+    #[test]
+    fn rejects_missing_and_unknown_keys() {
+        let status = server().call(Request::new(())).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+
+        let request = AttachServiceKey::new("ffffffffffffffffffffffffffffffff").unwrap()
+            .call(Request::new(()))
+            .unwrap();
+        let status = server().call(request).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+    }
+
+    #[test]
+    fn short_keys_are_rejected() {
+        assert!(AttachServiceKey::new("short").is_err());
+    }
+}
@@ -0,0 +1,201 @@
+use std::fs;
+use tonic::transport::{Certificate, ClientTlsConfig, Identity, ServerTlsConfig};
+
+// File locations for the internal gRPC channel. On the server, `ca_path` turns on mutual TLS by
+// requiring clients to present a certificate signed by that CA. On the client, `ca_path` is the CA
+// used to verify the server and `cert_path`/`key_path` is the identity presented for mutual TLS.
+#[derive(Debug, Clone, Default)]
+pub struct GrpcTlsConfig {
+    pub cert_path: Option<String>,
+    pub key_path: Option<String>,
+    pub ca_path: Option<String>,
+    pub domain: Option<String>,
+}
+
+impl GrpcTlsConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.cert_path.is_some() || self.key_path.is_some() || self.ca_path.is_some()
+    }
+
+    pub fn server_config(&self) -> Result<ServerTlsConfig, String> {
+        let identity = self
+            .identity()?
+            .ok_or("gRPC TLS on the server requires both a certificate and a key")?;
+        let mut config = ServerTlsConfig::new().identity(identity);
+        if let Some(ca_path) = &self.ca_path {
+            config = config.client_ca_root(Certificate::from_pem(read(ca_path)?));
+        }
+        Ok(config)
+    }
+
+    pub fn client_config(&self) -> Result<ClientTlsConfig, String> {
+        let mut config = ClientTlsConfig::new();
+        if let Some(ca_path) = &self.ca_path {
+            config = config.ca_certificate(Certificate::from_pem(read(ca_path)?));
+        }
+        if let Some(identity) = self.identity()? {
+            config = config.identity(identity);
+        }
+        if let Some(domain) = &self.domain {
+            config = config.domain_name(domain.clone());
+        }
+        Ok(config)
+    }
+
+    fn identity(&self) -> Result<Option<Identity>, String> {
+        match (&self.cert_path, &self.key_path) {
+            (Some(cert_path), Some(key_path)) => {
+                Ok(Some(Identity::from_pem(read(cert_path)?, read(key_path)?)))
+            }
+            (None, None) => Ok(None),
+            _ => Err("gRPC TLS certificate and key must be configured together".to_string()),
+        }
+    }
+}
+
+fn read(path: &str) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user_service_grpc::user_service_grpc_client::UserServiceGrpcClient;
+    use crate::user_service_grpc::user_service_grpc_server::{UserServiceGrpc, UserServiceGrpcServer};
+    use crate::user_service_grpc::{ApiKeyRequest, ApiKeyResponse, TokenRevocationRequest, TokenRevocationResponse, UserRequest, UserResponse};
+    use rcgen::{BasicConstraints, Certificate as RcgenCertificate, CertificateParams, IsCa};
+    use std::path::{Path, PathBuf};
+    use tonic::transport::server::TcpIncoming;
+    use tonic::transport::{Channel, Server};
+    use tonic::{Request, Response, Status};
+
+    struct StubUserService;
+
+    #[tonic::async_trait]
+    impl UserServiceGrpc for StubUserService {
+        async fn get_user_by_uid(&self, request: Request<UserRequest>) -> Result<Response<UserResponse>, Status> {
+            Ok(Response::new(UserResponse { uid: request.into_inner().uid, ..Default::default() }))
+        }
+
+        async fn is_token_revoked(&self, _: Request<TokenRevocationRequest>) -> Result<Response<TokenRevocationResponse>, Status> {
+            Ok(Response::new(TokenRevocationResponse { revoked: false }))
+        }
+
+        async fn verify_api_key(&self, _: Request<ApiKeyRequest>) -> Result<Response<ApiKeyResponse>, Status> {
+            Ok(Response::new(ApiKeyResponse::default()))
+        }
+    }
+
+    struct Pki {
+        dir: PathBuf,
+        ca: RcgenCertificate,
+    }
+
+    impl Pki {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("grpc-tls-{}-{}", name, std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+
+            let mut params = CertificateParams::new(vec![]);
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            let ca = RcgenCertificate::from_params(params).unwrap();
+            fs::write(dir.join("ca.pem"), ca.serialize_pem().unwrap()).unwrap();
+            Self { dir, ca }
+        }
+
+        fn issue(&self, name: &str) -> (String, String) {
+            let cert = RcgenCertificate::from_params(CertificateParams::new(vec!["localhost".to_string()])).unwrap();
+            let cert_path = self.dir.join(format!("{}.pem", name));
+            let key_path = self.dir.join(format!("{}.key", name));
+            fs::write(&cert_path, cert.serialize_pem_with_signer(&self.ca).unwrap()).unwrap();
+            fs::write(&key_path, cert.serialize_private_key_pem()).unwrap();
+            (path(&cert_path), path(&key_path))
+        }
+
+        fn ca_path(&self) -> String {
+            path(&self.dir.join("ca.pem"))
+        }
+    }
+
+    impl Drop for Pki {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn path(path: &Path) -> String {
+        path.to_string_lossy().into_owned()
+    }
+
+    async fn start_server(tls: &GrpcTlsConfig) -> u16 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let incoming = TcpIncoming::from_listener(listener, true, None).unwrap();
+        let server = Server::builder()
+            .tls_config(tls.server_config().unwrap())
+            .unwrap()
+            .add_service(UserServiceGrpcServer::new(StubUserService));
+        tokio::spawn(server.serve_with_incoming(incoming));
+        port
+    }
+
+    async fn call(port: u16, tls: &GrpcTlsConfig) -> Result<UserResponse, String> {
+        let channel = Channel::from_shared(format!("https://127.0.0.1:{}", port))
+            .unwrap()
+            .tls_config(tls.client_config().unwrap())
+            .unwrap()
+            .connect()
+            .await
+            .map_err(|e| e.to_string())?;
+        UserServiceGrpcClient::new(channel)
+            .get_user_by_uid(UserRequest { uid: "42".to_string() })
+            .await
+            .map(Response::into_inner)
+            .map_err(|e| e.to_string())
+    }
+
+    fn server_tls(pki: &Pki) -> GrpcTlsConfig {
+        let (cert_path, key_path) = pki.issue("server");
+        GrpcTlsConfig {
+            cert_path: Some(cert_path),
+            key_path: Some(key_path),
+            ca_path: Some(pki.ca_path()),
+            domain: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn mutual_tls_accepts_clients_with_a_certificate_from_the_ca() {
+        let pki = Pki::new("accept");
+        let port = start_server(&server_tls(&pki)).await;
+        let (cert_path, key_path) = pki.issue("client");
+        let client = GrpcTlsConfig {
+            cert_path: Some(cert_path),
+            key_path: Some(key_path),
+            ca_path: Some(pki.ca_path()),
+            domain: Some("localhost".to_string()),
+        };
+
+        assert_eq!(call(port, &client).await.unwrap().uid, "42");
+    }
+
+    #[tokio::test]
+    async fn mutual_tls_rejects_clients_without_a_certificate() {
+        let pki = Pki::new("reject");
+        let port = start_server(&server_tls(&pki)).await;
+        let client = GrpcTlsConfig {
+            ca_path: Some(pki.ca_path()),
+            domain: Some("localhost".to_string()),
+            ..Default::default()
+        };
+
+        assert!(call(port, &client).await.is_err());
+    }
+
+    #[test]
+    fn certificate_and_key_must_be_configured_together() {
+        let tls = GrpcTlsConfig { cert_path: Some("cert.pem".to_string()), ..Default::default() };
+        assert!(tls.server_config().is_err());
+        assert!(tls.client_config().is_err());
+    }
+}
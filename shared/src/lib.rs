@@ -1,5 +1,7 @@
 pub mod models;
 pub mod middleware;
+pub mod grpc;
+pub mod errors;
 pub mod user_service_grpc {
     tonic::include_proto!("user_service_grpc");     
 }
\ No newline at end of file
@@ -1,14 +1,47 @@
-use crate::models::user_token::UserToken;
-use actix_web::{error::ErrorUnauthorized ,Error};
+use crate::models::keys::KeySet;
+use crate::models::user_token::{TokenError, UserToken};
+use crate::middleware::rules::{AuthMode, AuthRules};
+use actix_web::{error::ErrorUnauthorized, Error, HttpMessage};
 use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
 use futures_util::future::{LocalBoxFuture, ok};
-use std::collections::HashSet;
 use std::rc::Rc;
 use std::sync::Arc;
+
+// Bearer credentials starting with this are API keys rather than JWTs.
+pub const API_KEY_PREFIX: &str = "tb_";
+
+#[async_trait::async_trait(?Send)]
+pub trait TokenRevocation: Send + Sync {
+    async fn is_revoked(&self, token: &UserToken) -> Result<bool, Error>;
+}
+
+#[async_trait::async_trait(?Send)]
+pub trait ApiKeyVerifier: Send + Sync {
+    // Ok(None) for unknown, revoked or expired keys.
+    async fn verify(&self, api_key: &str) -> Result<Option<UserToken>, Error>;
+}
+
+// Fetches the issuer's keys again when a token names a kid the key set doesn't know, which is what
+// a key rotation looks like until the next scheduled refresh.
+#[async_trait::async_trait(?Send)]
+pub trait KeyRefresh: Send + Sync {
+    async fn refresh(&self);
+}
+
+enum Credential {
+    Missing,
+    Jwt(Result<UserToken, TokenError>),
+    UnknownKey(String),
+    ApiKey(String),
+}
+
 pub struct AuthenticationMiddleware<S> {
     service: Rc<S>,
-    secret: Arc<String>,
-    excluded_paths: HashSet<&'static str>,
+    keys: Arc<KeySet>,
+    revocation: Arc<dyn TokenRevocation>,
+    api_keys: Option<Arc<dyn ApiKeyVerifier>>,
+    key_refresh: Option<Arc<dyn KeyRefresh>>,
+    rules: AuthRules,
 }
 
 impl<S, B> Service<ServiceRequest> for AuthenticationMiddleware<S>
@@ -28,60 +61,115 @@ where
     }
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
-        if self.excluded_paths.contains(req.path()) {
+        let mode = self.rules.mode(req.method(), req.path());
+        if mode == AuthMode::Public {
             return Box::pin(self.service.call(req));
         }
-    
-        let auth_header = req.headers().get("Authorization");
-        if let Some(auth_header) = auth_header {
-            if let Ok(auth_str) = auth_header.to_str() {
-                if auth_str.starts_with("Bearer ") {
-                    
-                    let token = &auth_str[7..];
-                    match UserToken::validate_token(token, &self.secret) {
-                        Ok(user_token) if user_token.is_valid() => {
-                            let fut = self.service.call(req);
-                            return Box::pin(async {
-                                match fut.await {
-                                    Ok(res) => Ok(res),
-                                    Err(err) => Err(err),
-                                }
-                            });
-                        }
-                        Ok(_) => {
-                            return Box::pin(async {
-                                Err(ErrorUnauthorized("Token expired"))
-                            });
-                        }
-                        Err(_e) => {
-                            return Box::pin(async {
-                                Err(ErrorUnauthorized("Invalid token"))
-                            });
-                        }
-                    }
+
+        let credential = match req
+            .headers()
+            .get("Authorization")
+            .and_then(|header| header.to_str().ok())
+            .and_then(|header| header.strip_prefix("Bearer "))
+        {
+            None => Credential::Missing,
+            Some(token) if token.starts_with(API_KEY_PREFIX) => Credential::ApiKey(token.to_string()),
+            Some(token) => match UserToken::validate_token(token, &self.keys) {
+                Err(TokenError::UnknownKey(_)) if self.key_refresh.is_some() => Credential::UnknownKey(token.to_string()),
+                validation => Credential::Jwt(validation),
+            },
+        };
+
+        let service = self.service.clone();
+        let keys = self.keys.clone();
+        let revocation = self.revocation.clone();
+        let api_keys = self.api_keys.clone();
+        let key_refresh = self.key_refresh.clone();
+        Box::pin(async move {
+            let credential = match (credential, key_refresh) {
+                (Credential::UnknownKey(token), Some(key_refresh)) => {
+                    key_refresh.refresh().await;
+                    Credential::Jwt(UserToken::validate_token(&token, &keys))
+                }
+                (credential, _) => credential,
+            };
+            match authenticate(credential, revocation.as_ref(), api_keys.as_deref()).await {
+                Ok(user_token) => {
+                    req.extensions_mut().insert(user_token);
+                }
+                Err(e) if mode == AuthMode::Optional => {
+                    log::debug!("Continuing without identity on {}: {}", req.path(), e);
                 }
+                Err(e) => return Err(e),
             }
-        }
-    
-        Box::pin(async {
-            Err(ErrorUnauthorized("Missing or invalid Authorization header"))
+            service.call(req).await
         })
     }
 }
 
+async fn authenticate(
+    credential: Credential,
+    revocation: &dyn TokenRevocation,
+    api_keys: Option<&dyn ApiKeyVerifier>,
+) -> Result<UserToken, Error> {
+    let validation = match credential {
+        Credential::Missing => return Err(ErrorUnauthorized("Missing or invalid Authorization header")),
+        Credential::ApiKey(api_key) => {
+            let api_keys = api_keys.ok_or_else(|| ErrorUnauthorized("API keys are not accepted"))?;
+            return match api_keys.verify(&api_key).await? {
+                Some(user_token) if user_token.is_valid() => Ok(user_token),
+                _ => Err(ErrorUnauthorized("Invalid API key")),
+            };
+        }
+        Credential::Jwt(validation) => validation,
+        Credential::UnknownKey(_) => return Err(ErrorUnauthorized("Invalid token")),
+    };
+    let user_token = match validation {
+        Ok(user_token) if user_token.is_valid() => user_token,
+        Ok(_) | Err(TokenError::Expired) => return Err(ErrorUnauthorized("Token expired")),
+        Err(_) => return Err(ErrorUnauthorized("Invalid token")),
+    };
+
+    if revocation.is_revoked(&user_token).await? {
+        return Err(ErrorUnauthorized("Token revoked"));
+    }
+
+    Ok(user_token)
+}
+
 pub struct Authentication {
-    secret: Arc<String>,
-    excluded_paths: HashSet<&'static str>,
+    keys: Arc<KeySet>,
+    revocation: Arc<dyn TokenRevocation>,
+    api_keys: Option<Arc<dyn ApiKeyVerifier>>,
+    key_refresh: Option<Arc<dyn KeyRefresh>>,
+    rules: AuthRules,
 }
 
 
 impl Authentication {
-    pub fn new(secret: Arc<String>, excluded_paths: Option<HashSet<&'static str>>) -> Self {
+    pub fn new(
+        keys: Arc<KeySet>,
+        revocation: Arc<dyn TokenRevocation>,
+        rules: AuthRules,
+    ) -> Self {
         Self {
-            secret,
-            excluded_paths: excluded_paths.unwrap_or_else(HashSet::new),
+            keys,
+            revocation,
+            api_keys: None,
+            key_refresh: None,
+            rules,
         }
     }
+
+    pub fn with_api_keys(mut self, api_keys: Arc<dyn ApiKeyVerifier>) -> Self {
+        self.api_keys = Some(api_keys);
+        self
+    }
+
+    pub fn with_key_refresh(mut self, key_refresh: Arc<dyn KeyRefresh>) -> Self {
+        self.key_refresh = Some(key_refresh);
+        self
+    }
 }
 
 impl<S, B> Transform<S, ServiceRequest> for Authentication
@@ -98,8 +186,117 @@ where
     fn new_transform(&self, service: S) -> Self::Future {
         ok(AuthenticationMiddleware {
             service: Rc::new(service),
-            secret: self.secret.clone(),
-            excluded_paths: self.excluded_paths.clone(),
+            keys: self.keys.clone(),
+            revocation: self.revocation.clone(),
+            api_keys: self.api_keys.clone(),
+            key_refresh: self.key_refresh.clone(),
+            rules: self.rules.clone(),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App, HttpResponse};
+    use crate::middleware::auth_user::AuthUser;
+    use crate::models::keys::SigningKey;
+    use jsonwebtoken::jwk::JwkSet;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use uuid::Uuid;
+
+    struct NeverRevoked;
+
+    #[async_trait::async_trait(?Send)]
+    impl TokenRevocation for NeverRevoked {
+        async fn is_revoked(&self, _: &UserToken) -> Result<bool, Error> {
+            Ok(false)
+        }
+    }
+
+    struct OneKey(Uuid);
+
+    #[async_trait::async_trait(?Send)]
+    impl ApiKeyVerifier for OneKey {
+        async fn verify(&self, api_key: &str) -> Result<Option<UserToken>, Error> {
+            Ok((api_key == "tb_valid").then(|| UserToken::for_api_key(self.0, Uuid::new_v4(), vec!["chats:read".into()], None)))
+        }
+    }
+
+    // Stands in for the JWKS endpoint: the first refresh publishes the key.
+    struct PublishOnRefresh {
+        keys: Arc<KeySet>,
+        key: SigningKey,
+        refreshes: AtomicUsize,
+    }
+
+    #[async_trait::async_trait(?Send)]
+    impl KeyRefresh for PublishOnRefresh {
+        async fn refresh(&self) {
+            self.refreshes.fetch_add(1, Ordering::SeqCst);
+            self.keys.replace(JwkSet { keys: vec![self.key.public_jwk().clone()] }).unwrap();
+        }
+    }
+
+    async fn status_for(authentication: Authentication, bearer: &str) -> u16 {
+        status_at(authentication, "/me", bearer).await
+    }
+
+    async fn status_at(authentication: Authentication, path: &str, bearer: &str) -> u16 {
+        let app = test::init_service(
+            App::new()
+                .wrap(authentication)
+                .route("/me", web::get().to(|user: AuthUser| async move {
+                    assert!(user.token.api_key_id.is_some());
+                    assert!(user.token.roles.is_empty());
+                    HttpResponse::Ok().finish()
+                }))
+                .route("/jwt", web::get().to(|_: AuthUser| async { HttpResponse::Ok().finish() })),
+        )
+        .await;
+        let req = test::TestRequest::get()
+            .uri(path)
+            .insert_header(("Authorization", format!("Bearer {}", bearer)))
+            .to_request();
+        match test::try_call_service(&app, req).await {
+            Ok(resp) => resp.status().as_u16(),
+            Err(e) => e.as_response_error().status_code().as_u16(),
+        }
+    }
+
+    fn authentication() -> Authentication {
+        Authentication::new(Arc::new(KeySet::default()), Arc::new(NeverRevoked), AuthRules::default())
+    }
+
+    #[actix_web::test]
+    async fn accepts_known_api_keys() {
+        let authentication = authentication().with_api_keys(Arc::new(OneKey(Uuid::new_v4())));
+        assert_eq!(status_for(authentication, "tb_valid").await, 200);
+    }
+
+    #[actix_web::test]
+    async fn rejects_unknown_api_keys() {
+        let authentication = authentication().with_api_keys(Arc::new(OneKey(Uuid::new_v4())));
+        assert_eq!(status_for(authentication, "tb_other").await, 401);
+    }
+
+    #[actix_web::test]
+    async fn rejects_api_keys_without_a_verifier() {
+        assert_eq!(status_for(authentication(), "tb_valid").await, 401);
+    }
+
+    #[actix_web::test]
+    async fn refetches_keys_for_an_unknown_kid() {
+        let keys = Arc::new(KeySet::default());
+        let key_pair = rcgen::KeyPair::generate(&rcgen::PKCS_ED25519).unwrap();
+        let key = SigningKey::from_ed_pem("rotated", key_pair.serialize_pem().as_bytes()).unwrap();
+        let token = UserToken::new(Uuid::new_v4(), vec![], vec![], chrono::Duration::minutes(5)).generate_token(&key).unwrap();
+        let key_refresh = Arc::new(PublishOnRefresh { keys: keys.clone(), key, refreshes: AtomicUsize::new(0) });
+
+        assert_eq!(status_at(authentication(), "/jwt", &token).await, 401);
+        let authentication = Authentication::new(keys, Arc::new(NeverRevoked), AuthRules::default())
+            .with_key_refresh(key_refresh.clone());
+        assert_eq!(status_at(authentication, "/jwt", &token).await, 200);
+        assert_eq!(key_refresh.refreshes.load(Ordering::SeqCst), 1);
+    }
+}
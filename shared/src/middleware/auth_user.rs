@@ -0,0 +1,28 @@
+use crate::models::user_token::UserToken;
+use actix_web::{dev::Payload, error::ErrorUnauthorized, Error, FromRequest, HttpMessage, HttpRequest};
+use futures_util::future::{ready, Ready};
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub uid: Uuid,
+    pub token: UserToken,
+}
+
+impl FromRequest for AuthUser {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let Some(token) = req.extensions().get::<UserToken>().cloned() else {
+            return ready(Err(ErrorUnauthorized("Missing authentication")));
+        };
+
+        ready(
+            token
+                .get_user_id()
+                .map(|uid| AuthUser { uid, token })
+                .map_err(|_| ErrorUnauthorized("Invalid token subject")),
+        )
+    }
+}
@@ -1 +1,4 @@
-pub mod auth;
\ No newline at end of file
+pub mod auth;
+pub mod auth_user;
+pub mod require;
+pub mod rules;
\ No newline at end of file
@@ -0,0 +1,212 @@
+use crate::models::user_token::UserToken;
+use actix_web::{error::{ErrorForbidden, ErrorUnauthorized}, Error, HttpMessage};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::Method;
+use futures_util::future::{LocalBoxFuture, Ready, ok};
+use std::rc::Rc;
+
+// These guards rely on `Authentication` having already put the caller's token into the request
+// extensions, so they must be wrapped inside it (e.g. on a scope in `config_services`).
+pub struct RequireRole(pub &'static str);
+
+pub struct RequireScope(pub &'static str);
+
+// `<resource>:read` for GET and HEAD, `<resource>:write` for everything else.
+pub struct RequireAccess(pub &'static str);
+
+// Rejects API keys, for routes that manage the account itself and so need a real login.
+pub struct RequireLogin;
+
+#[derive(Debug, Clone, Copy)]
+enum Requirement {
+    Role(&'static str),
+    Scope(&'static str),
+    Access(&'static str),
+    Login,
+}
+
+impl Requirement {
+    fn is_met_by(&self, token: &UserToken, method: &Method) -> bool {
+        match self {
+            Requirement::Role(role) => token.has_role(role),
+            Requirement::Scope(scope) => token.has_scope(scope),
+            Requirement::Access(resource) => {
+                let action = if method == Method::GET || method == Method::HEAD { "read" } else { "write" };
+                token.has_scope(&format!("{}:{}", resource, action))
+            }
+            Requirement::Login => token.api_key_id.is_none(),
+        }
+    }
+}
+
+pub struct RequireMiddleware<S> {
+    service: Rc<S>,
+    requirement: Requirement,
+}
+
+impl<S, B> Service<ServiceRequest> for RequireMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &self,
+        ctx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(ctx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let allowed = match req.extensions().get::<UserToken>() {
+            None => return Box::pin(async { Err(ErrorUnauthorized("Missing authentication")) }),
+            Some(token) => self.requirement.is_met_by(token, req.method()),
+        };
+
+        if !allowed {
+            log::warn!("Rejected {} {}: missing {:?}", req.method(), req.path(), self.requirement);
+            return Box::pin(async { Err(ErrorForbidden("Insufficient privileges")) });
+        }
+
+        Box::pin(self.service.call(req))
+    }
+}
+
+impl RequireRole {
+    fn requirement(&self) -> Requirement {
+        Requirement::Role(self.0)
+    }
+}
+
+impl RequireScope {
+    fn requirement(&self) -> Requirement {
+        Requirement::Scope(self.0)
+    }
+}
+
+impl RequireAccess {
+    fn requirement(&self) -> Requirement {
+        Requirement::Access(self.0)
+    }
+}
+
+impl RequireLogin {
+    fn requirement(&self) -> Requirement {
+        Requirement::Login
+    }
+}
+
+macro_rules! require_transform {
+    ($($guard:ty),*) => {$(
+        impl<S, B> Transform<S, ServiceRequest> for $guard
+        where
+            S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+            B: 'static,
+        {
+            type Response = ServiceResponse<B>;
+            type Error = Error;
+            type InitError = ();
+            type Transform = RequireMiddleware<S>;
+            type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+            fn new_transform(&self, service: S) -> Self::Future {
+                ok(RequireMiddleware {
+                    service: Rc::new(service),
+                    requirement: self.requirement(),
+                })
+            }
+        }
+    )*};
+}
+
+require_transform!(RequireRole, RequireScope, RequireAccess, RequireLogin);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App, HttpResponse};
+    use chrono::Duration;
+    use uuid::Uuid;
+
+    async fn status_for(roles: Option<&[&str]>) -> u16 {
+        let token = roles.map(|roles| {
+            let roles = roles.iter().map(|r| r.to_string()).collect();
+            UserToken::new(Uuid::new_v4(), roles, vec![], Duration::minutes(5))
+        });
+        let app = test::init_service(
+            App::new()
+                .service(
+                    web::scope("/admin")
+                        .wrap(RequireRole("admin"))
+                        .route("", web::get().to(HttpResponse::Ok)),
+                )
+                .wrap_fn(move |req, srv| {
+                    if let Some(token) = token.clone() {
+                        req.extensions_mut().insert(token);
+                    }
+                    srv.call(req)
+                }),
+        )
+        .await;
+        let resp = test::try_call_service(&app, test::TestRequest::get().uri("/admin").to_request()).await;
+        match resp {
+            Ok(resp) => resp.status().as_u16(),
+            Err(e) => e.as_response_error().status_code().as_u16(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn allows_tokens_with_the_role() {
+        assert_eq!(status_for(Some(&["user", "admin"])).await, 200);
+    }
+
+    #[actix_web::test]
+    async fn forbids_tokens_without_the_role() {
+        assert_eq!(status_for(Some(&["user"])).await, 403);
+    }
+
+    #[actix_web::test]
+    async fn rejects_anonymous_requests() {
+        assert_eq!(status_for(None).await, 401);
+    }
+
+    async fn status_with(path: &str, method: Method, token: UserToken) -> u16 {
+        let app = test::init_service(
+            App::new()
+                .service(web::scope("/chats").wrap(RequireAccess("chats")).route("", web::route().to(HttpResponse::Ok)))
+                .service(web::scope("/me").wrap(RequireLogin).route("", web::route().to(HttpResponse::Ok)))
+                .wrap_fn(move |req, srv| {
+                    req.extensions_mut().insert(token.clone());
+                    srv.call(req)
+                }),
+        )
+        .await;
+        let req = test::TestRequest::default().method(method).uri(path).to_request();
+        match test::try_call_service(&app, req).await {
+            Ok(resp) => resp.status().as_u16(),
+            Err(e) => e.as_response_error().status_code().as_u16(),
+        }
+    }
+
+    fn api_key(scopes: &[&str]) -> UserToken {
+        UserToken::for_api_key(Uuid::new_v4(), Uuid::new_v4(), scopes.iter().map(|s| s.to_string()).collect(), None)
+    }
+
+    #[actix_web::test]
+    async fn access_needs_the_read_or_write_scope_for_the_method() {
+        assert_eq!(status_with("/chats", Method::GET, api_key(&["chats:read"])).await, 200);
+        assert_eq!(status_with("/chats", Method::POST, api_key(&["chats:read"])).await, 403);
+        assert_eq!(status_with("/chats", Method::DELETE, api_key(&["chats:write"])).await, 200);
+        assert_eq!(status_with("/chats", Method::GET, api_key(&["users:read"])).await, 403);
+    }
+
+    #[actix_web::test]
+    async fn login_routes_reject_api_keys() {
+        let user_token = UserToken::new(Uuid::new_v4(), vec![], vec![], Duration::minutes(5));
+        assert_eq!(status_with("/me", Method::POST, user_token).await, 200);
+        assert_eq!(status_with("/me", Method::POST, api_key(&["users:read", "users:write"])).await, 403);
+    }
+}
@@ -0,0 +1,150 @@
+use actix_web::http::Method;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthMode {
+    Required,
+    Optional,
+    Public,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthRule {
+    method: Option<Method>,
+    segments: Vec<String>,
+    mode: AuthMode,
+}
+
+impl AuthRule {
+    // Rules look like "GET /api/invites/*/preview" or "/static/**"; the method is optional.
+    // `*` matches within a single path segment and a `**` segment matches any number of segments.
+    pub fn parse(rule: &str, mode: AuthMode) -> Result<Self, String> {
+        let rule = rule.trim();
+        let (method, pattern) = match rule.split_once(char::is_whitespace) {
+            Some((method, pattern)) => {
+                let method = Method::from_bytes(method.to_uppercase().as_bytes())
+                    .map_err(|_| format!("Invalid method in auth rule: {}", rule))?;
+                (Some(method), pattern.trim())
+            }
+            None => (None, rule),
+        };
+
+        if !pattern.starts_with('/') {
+            return Err(format!("Auth rule path must start with '/': {}", rule));
+        }
+
+        Ok(Self {
+            method,
+            segments: pattern.split('/').skip(1).map(String::from).collect(),
+            mode,
+        })
+    }
+
+    pub fn matches(&self, method: &Method, path: &str) -> bool {
+        if self.method.as_ref().is_some_and(|m| m != method) {
+            return false;
+        }
+        let path: Vec<&str> = path.split('/').skip(1).collect();
+        match_segments(&self.segments, &path)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AuthRules {
+    rules: Vec<AuthRule>,
+}
+
+impl AuthRules {
+    // Both lists are comma separated; public rules are checked before optional ones.
+    pub fn from_config(public: &str, optional: &str) -> Result<Self, String> {
+        let mut rules = Vec::new();
+        for (list, mode) in [(public, AuthMode::Public), (optional, AuthMode::Optional)] {
+            for rule in list.split(',').filter(|rule| !rule.trim().is_empty()) {
+                rules.push(AuthRule::parse(rule, mode)?);
+            }
+        }
+        Ok(Self { rules })
+    }
+
+    pub fn mode(&self, method: &Method, path: &str) -> AuthMode {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(method, path))
+            .map(|rule| rule.mode)
+            .unwrap_or(AuthMode::Required)
+    }
+}
+
+fn match_segments(pattern: &[String], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=path.len()).any(|skip| match_segments(rest, &path[skip..]))
+        }
+        Some((first, rest)) => match path.split_first() {
+            Some((segment, path_rest)) => match_segment(first, segment) && match_segments(rest, path_rest),
+            None => false,
+        },
+    }
+}
+
+fn match_segment(pattern: &str, segment: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == segment,
+        Some((prefix, rest)) => {
+            let Some(remaining) = segment.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=remaining.len())
+                .filter(|&i| remaining.is_char_boundary(i))
+                .any(|i| match_segment(rest, &remaining[i..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(public: &str, optional: &str) -> AuthRules {
+        AuthRules::from_config(public, optional).unwrap()
+    }
+
+    #[test]
+    fn exact_paths_respect_method() {
+        let rules = rules("POST /api/auth/login", "");
+        assert_eq!(rules.mode(&Method::POST, "/api/auth/login"), AuthMode::Public);
+        assert_eq!(rules.mode(&Method::GET, "/api/auth/login"), AuthMode::Required);
+        assert_eq!(rules.mode(&Method::POST, "/api/auth/login/extra"), AuthMode::Required);
+    }
+
+    #[test]
+    fn single_segment_wildcards() {
+        let rules = rules("", "GET /api/invites/*/preview, /static/*.css");
+        assert_eq!(rules.mode(&Method::GET, "/api/invites/abc/preview"), AuthMode::Optional);
+        assert_eq!(rules.mode(&Method::GET, "/api/invites/a/b/preview"), AuthMode::Required);
+        assert_eq!(rules.mode(&Method::GET, "/static/site.css"), AuthMode::Optional);
+        assert_eq!(rules.mode(&Method::GET, "/static/site.js"), AuthMode::Required);
+    }
+
+    #[test]
+    fn double_star_matches_prefixes() {
+        let rules = rules("/assets/**, OPTIONS /**", "");
+        assert_eq!(rules.mode(&Method::GET, "/assets"), AuthMode::Public);
+        assert_eq!(rules.mode(&Method::GET, "/assets/js/app.js"), AuthMode::Public);
+        assert_eq!(rules.mode(&Method::OPTIONS, "/api/chats"), AuthMode::Public);
+        assert_eq!(rules.mode(&Method::GET, "/api/chats"), AuthMode::Required);
+    }
+
+    #[test]
+    fn public_rules_take_precedence() {
+        let rules = rules("GET /api/**", "/api/**");
+        assert_eq!(rules.mode(&Method::GET, "/api/x"), AuthMode::Public);
+        assert_eq!(rules.mode(&Method::POST, "/api/x"), AuthMode::Optional);
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        assert!(AuthRules::from_config("api/no-slash", "").is_err());
+        assert!(AuthRules::from_config("G(T /api", "").is_err());
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::RwLock;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jsonwebtoken::{DecodingKey, EncodingKey};
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+    OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse,
+};
+use ring::signature::{Ed25519KeyPair, KeyPair};
+
+// An Ed25519 SubjectPublicKeyInfo is a fixed DER header naming the Ed25519 OID (1.3.101.112),
+// followed by the raw 32-byte key.
+const ED25519_SPKI_HEADER: [u8; 12] = [0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00];
+const ED25519_PUBLIC_KEY_LEN: usize = 32;
+
+#[derive(Debug)]
+pub enum KeyError {
+    InvalidPem(String),
+    InvalidKey(String),
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyError::InvalidPem(e) => write!(f, "Invalid PEM: {}", e),
+            KeyError::InvalidKey(e) => write!(f, "Invalid key: {}", e),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SigningKey {
+    pub kid: String,
+    encoding_key: EncodingKey,
+    public_jwk: Jwk,
+}
+
+impl SigningKey {
+    pub fn from_ed_pem(kid: &str, pem: &[u8]) -> Result<Self, KeyError> {
+        let encoding_key = EncodingKey::from_ed_pem(pem)
+            .map_err(|e| KeyError::InvalidKey(e.to_string()))?;
+        let der = pem::parse(pem).map_err(|e| KeyError::InvalidPem(e.to_string()))?;
+        let key_pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(der.contents())
+            .map_err(|e| KeyError::InvalidKey(e.to_string()))?;
+
+        Ok(Self {
+            kid: kid.to_string(),
+            encoding_key,
+            public_jwk: ed25519_jwk(kid, key_pair.public_key().as_ref()),
+        })
+    }
+
+    pub fn encoding_key(&self) -> &EncodingKey {
+        &self.encoding_key
+    }
+
+    pub fn public_jwk(&self) -> &Jwk {
+        &self.public_jwk
+    }
+}
+
+pub fn public_jwk_from_ed_pem(kid: &str, pem: &[u8]) -> Result<Jwk, KeyError> {
+    let der = pem::parse(pem).map_err(|e| KeyError::InvalidPem(e.to_string()))?;
+    let public_key = der.contents()
+        .strip_prefix(ED25519_SPKI_HEADER.as_slice())
+        .filter(|public_key| public_key.len() == ED25519_PUBLIC_KEY_LEN)
+        .ok_or_else(|| KeyError::InvalidKey(format!("{} is not an Ed25519 public key", kid)))?;
+    Ok(ed25519_jwk(kid, public_key))
+}
+
+fn ed25519_jwk(kid: &str, public_key: &[u8]) -> Jwk {
+    Jwk {
+        common: CommonParameters {
+            public_key_use: Some(PublicKeyUse::Signature),
+            key_algorithm: Some(KeyAlgorithm::EdDSA),
+            key_id: Some(kid.to_string()),
+            ..Default::default()
+        },
+        algorithm: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+            key_type: OctetKeyPairType::OctetKeyPair,
+            curve: EllipticCurve::Ed25519,
+            x: URL_SAFE_NO_PAD.encode(public_key),
+        }),
+    }
+}
+
+#[derive(Default)]
+pub struct KeySet {
+    keys: RwLock<HashMap<String, (Jwk, DecodingKey)>>,
+}
+
+impl KeySet {
+    pub fn new(jwks: JwkSet) -> Result<Self, KeyError> {
+        let key_set = Self::default();
+        key_set.replace(jwks)?;
+        Ok(key_set)
+    }
+
+    pub fn replace(&self, jwks: JwkSet) -> Result<(), KeyError> {
+        let mut keys = HashMap::new();
+        for jwk in jwks.keys {
+            let kid = jwk.common.key_id.clone()
+                .ok_or_else(|| KeyError::InvalidKey("JWK without kid".to_string()))?;
+            let decoding_key = DecodingKey::from_jwk(&jwk)
+                .map_err(|e| KeyError::InvalidKey(format!("{}: {}", kid, e)))?;
+            keys.insert(kid, (jwk, decoding_key));
+        }
+
+        *self.keys.write().unwrap() = keys;
+        Ok(())
+    }
+
+    pub fn decoding_key(&self, kid: &str) -> Option<DecodingKey> {
+        self.keys.read().unwrap().get(kid).map(|(_, key)| key.clone())
+    }
+
+    pub fn jwks(&self) -> JwkSet {
+        let keys = self.keys.read().unwrap();
+        let mut jwks: Vec<Jwk> = keys.values().map(|(jwk, _)| jwk.clone()).collect();
+        jwks.sort_by(|a, b| a.common.key_id.cmp(&b.common.key_id));
+        JwkSet { keys: jwks }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rcgen::{KeyPair, PKCS_ECDSA_P256_SHA256, PKCS_ED25519};
+    use base64::engine::general_purpose::STANDARD;
+    use crate::models::user_token::{TokenError, UserToken};
+    use uuid::Uuid;
+
+    fn signing_key(kid: &str) -> (SigningKey, String) {
+        let key_pair = KeyPair::generate(&PKCS_ED25519).unwrap();
+        let signing_key = SigningKey::from_ed_pem(kid, key_pair.serialize_pem().as_bytes()).unwrap();
+        (signing_key, key_pair.public_key_pem())
+    }
+
+    fn token(key: &SigningKey) -> String {
+        UserToken::new(Uuid::new_v4(), vec![], vec![], chrono::Duration::minutes(5))
+            .generate_token(key)
+            .unwrap()
+    }
+
+    #[test]
+    fn public_keys_match_their_signing_key() {
+        let (signing_key, public_pem) = signing_key("k1");
+        let jwk = public_jwk_from_ed_pem("k1", public_pem.as_bytes()).unwrap();
+        assert_eq!(&jwk, signing_key.public_jwk());
+    }
+
+    #[test]
+    fn rejects_public_keys_of_other_types() {
+        let p256 = KeyPair::generate(&PKCS_ECDSA_P256_SHA256).unwrap().public_key_pem();
+        assert!(public_jwk_from_ed_pem("p256", p256.as_bytes()).is_err());
+
+        // An X25519 key has exactly the length of an Ed25519 one, only the OID tells them apart.
+        let mut x25519 = ED25519_SPKI_HEADER.to_vec();
+        x25519[8] = 0x6e;
+        x25519.extend([7u8; ED25519_PUBLIC_KEY_LEN]);
+        let pem = format!("-----BEGIN PUBLIC KEY-----\n{}\n-----END PUBLIC KEY-----\n", STANDARD.encode(x25519));
+        assert!(public_jwk_from_ed_pem("x25519", pem.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn key_sets_verify_tokens_by_kid() {
+        let (old_key, _) = signing_key("old");
+        let (new_key, _) = signing_key("new");
+        let keys = KeySet::new(JwkSet { keys: vec![new_key.public_jwk().clone(), old_key.public_jwk().clone()] }).unwrap();
+        assert!(UserToken::validate_token(&token(&old_key), &keys).is_ok());
+        assert!(UserToken::validate_token(&token(&new_key), &keys).is_ok());
+        let kids: Vec<_> = keys.jwks().keys.into_iter().map(|jwk| jwk.common.key_id.unwrap()).collect();
+        assert_eq!(kids, vec!["new", "old"]);
+
+        keys.replace(JwkSet { keys: vec![new_key.public_jwk().clone()] }).unwrap();
+        assert!(matches!(UserToken::validate_token(&token(&old_key), &keys), Err(TokenError::UnknownKey(Some(kid))) if kid == "old"));
+    }
+
+    #[test]
+    fn a_token_signed_by_another_key_with_the_same_kid_is_rejected() {
+        let (key, _) = signing_key("k1");
+        let (impostor, _) = signing_key("k1");
+        let keys = KeySet::new(JwkSet { keys: vec![key.public_jwk().clone()] }).unwrap();
+        assert!(UserToken::validate_token(&token(&impostor), &keys).is_err());
+    }
+
+    #[test]
+    fn key_sets_require_a_kid() {
+        let (key, _) = signing_key("k1");
+        let mut jwk = key.public_jwk().clone();
+        jwk.common.key_id = None;
+        assert!(KeySet::new(JwkSet { keys: vec![jwk] }).is_err());
+    }
+}
@@ -1 +1,2 @@
-pub mod user_token;
\ No newline at end of file
+pub mod user_token;
+pub mod keys;
\ No newline at end of file
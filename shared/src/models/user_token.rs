@@ -1,13 +1,24 @@
 use serde::{Deserialize, Serialize};
 use chrono::{Utc, Duration};
 use uuid::Uuid;
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation, errors::Error as JwtError};
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, Header, Validation, errors::Error as JwtError};
+use crate::models::keys::{KeySet, SigningKey};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct UserToken {
    pub exp: i64,
    pub iat: i64,
    pub sub: String,
+   pub jti: String,
+   #[serde(default)]
+   pub roles: Vec<String>,
+   #[serde(default)]
+   pub scopes: Vec<String>,
+   #[serde(default, skip_serializing_if = "Option::is_none")]
+   pub sid: Option<String>,
+   // Set when the caller authenticated with an API key; such tokens are never encoded as JWTs.
+   #[serde(skip)]
+   pub api_key_id: Option<Uuid>,
 }
 
 #[derive(Debug)]
@@ -15,25 +26,56 @@ pub enum TokenError {
     Creation(JwtError),
     Expired,
     Invalid(JwtError),
+    UnknownKey(Option<String>),
 }
 
 impl UserToken {
-    pub fn new(user_id: Uuid, ttl: Duration) -> Self {
+    pub fn new(user_id: Uuid, roles: Vec<String>, scopes: Vec<String>, ttl: Duration) -> Self {
         let now = Utc::now();
         Self {
             exp: (now + ttl).timestamp(),
             iat: now.timestamp(),
             sub: user_id.to_string(),
+            jti: Uuid::new_v4().to_string(),
+            roles,
+            scopes,
+            sid: None,
+            api_key_id: None,
         }
     }
+
+    // The identity behind an API key: its scopes only and no roles, so role-guarded routes need a login.
+    pub fn for_api_key(user_id: Uuid, key_id: Uuid, scopes: Vec<String>, expires_at: Option<i64>) -> Self {
+        Self {
+            exp: expires_at.unwrap_or(i64::MAX),
+            iat: Utc::now().timestamp(),
+            sub: user_id.to_string(),
+            jti: key_id.to_string(),
+            roles: vec![],
+            scopes,
+            sid: None,
+            api_key_id: Some(key_id),
+        }
+    }
+
+    pub fn with_session(mut self, session_id: Uuid) -> Self {
+        self.sid = Some(session_id.to_string());
+        self
+    }
     
-    pub fn generate_token(&self, secret: &str) -> Result<String, TokenError> {
-        encode(&Header::default(), self, &EncodingKey::from_secret(secret.as_bytes()))
+    pub fn generate_token(&self, key: &SigningKey) -> Result<String, TokenError> {
+        let mut header = Header::new(Algorithm::EdDSA);
+        header.kid = Some(key.kid.clone());
+        encode(&header, self, key.encoding_key())
             .map_err(TokenError::Creation)
     }
     
-    pub fn validate_token(token: &str, secret: &str) -> Result<Self, TokenError> {
-        let decoded = decode::<Self>(token, &DecodingKey::from_secret(secret.as_bytes()), &Validation::default())
+    pub fn validate_token(token: &str, keys: &KeySet) -> Result<Self, TokenError> {
+        let header = decode_header(token).map_err(TokenError::Invalid)?;
+        let decoding_key = header.kid.as_deref()
+            .and_then(|kid| keys.decoding_key(kid))
+            .ok_or(TokenError::UnknownKey(header.kid))?;
+        let decoded = decode::<Self>(token, &decoding_key, &Validation::new(Algorithm::EdDSA))
             .map_err(|e| match e.kind() {
                 jsonwebtoken::errors::ErrorKind::ExpiredSignature => TokenError::Expired,
                 _ => TokenError::Invalid(e),
@@ -49,4 +91,20 @@ impl UserToken {
     pub fn get_user_id(&self) -> Result<Uuid, uuid::Error> {
         Uuid::parse_str(&self.sub)
     }
+
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|r| r == role)
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+
+    pub fn get_token_id(&self) -> Result<Uuid, uuid::Error> {
+        Uuid::parse_str(&self.jti)
+    }
+
+    pub fn get_session_id(&self) -> Option<Uuid> {
+        self.sid.as_deref().and_then(|sid| Uuid::parse_str(sid).ok())
+    }
 }
\ No newline at end of file